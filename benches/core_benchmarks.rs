@@ -1,7 +1,16 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use ed25519_dalek::SigningKey;
+use jamliquor::authorer::{Authorer, ExtrinsicPool};
+use jamliquor::chain_import::{ChainSource, InvalidBlockPolicy};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::codec::Encode;
+use jamliquor::schema::{self, OpaqueHash};
 use jamliquor::state::State;
 use jamliquor::Importer;
 
+/// Number of blocks in the synthetic chain used for throughput benchmarks.
+const CHAIN_LENGTH: u32 = 200;
+
 fn benchmark_block_import(c: &mut Criterion) {
     let mut group = c.benchmark_group("Block Import");
     let mut importer = Importer::new();
@@ -32,5 +41,72 @@ fn benchmark_state_transition(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, benchmark_block_import, benchmark_state_transition);
+/// Validators of the synthetic chain, all authoring on the tiny spec.
+fn authorers() -> Vec<Authorer> {
+    (1..=6u8)
+        .map(|i| Authorer::new(SigningKey::from_bytes(&[i; 32]), ChainSpec::tiny()))
+        .collect()
+}
+
+/// Importer for the synthetic chain, rooted at a state naming its validators.
+fn chain_importer() -> Importer {
+    let mut state = schema::State::new();
+    state.active_validators = authorers().iter().map(|a| *a.public_key()).collect();
+    Importer::from_state(ChainSpec::tiny(), OpaqueHash::default(), None, state)
+}
+
+/// Write a linear chain of empty blocks, each sealed by its slot's fallback key, as a
+/// concatenated binary stream.
+fn write_chain(path: &std::path::Path) {
+    let authorers = authorers();
+    let mut importer = chain_importer();
+    let pool = ExtrinsicPool::new();
+    let mut stream = Vec::new();
+    for slot in 1..=CHAIN_LENGTH {
+        let state = importer.state().clone();
+        let (authorer, claim) = authorers
+            .iter()
+            .find_map(|a| Some((a, a.claim(&state, slot)?)))
+            .expect("every slot has a fallback key");
+        let block = authorer
+            .build(importer.best_head(), &state, &claim, &pool)
+            .unwrap()
+            .block;
+        block.encode_to(&mut stream);
+        importer.import_decoded(block).unwrap();
+    }
+    std::fs::write(path, stream).unwrap();
+}
+
+fn benchmark_chain_import(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Chain Import");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chain.jlb");
+    write_chain(&path);
+    let source = ChainSource::open(&path).unwrap();
+
+    group.throughput(Throughput::Elements(u64::from(CHAIN_LENGTH)));
+    group.bench_function("import binary chain stream", |b| {
+        b.iter_batched(
+            chain_importer,
+            |mut importer| {
+                let report = importer
+                    .import_chain_from(&source, InvalidBlockPolicy::Stop)
+                    .unwrap();
+                assert_eq!(report.imported, CHAIN_LENGTH as usize);
+                black_box(report)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_block_import,
+    benchmark_state_transition,
+    benchmark_chain_import
+);
 criterion_main!(benches);
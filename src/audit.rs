@@ -0,0 +1,452 @@
+//! Auditing of reports made available by a block (GP 17).
+//!
+//! Every validator audits a few of the reports that became available in a block. Audits
+//! are assigned in tranches, one every [`TRANCHE_PERIOD`] after the block:
+//! - tranche 0: the reports on the first [`TRANCHE0_AUDITS`] cores of a shuffle seeded
+//!   by the auditor's VRF output;
+//! - tranche `n > 0`: each report is picked up with a probability that grows with its
+//!   no-shows, validators that announced an audit in an earlier tranche and have not
+//!   judged it since.
+//!
+//! An [`Auditor`] announces its picks, re-executes each picked report through the refine
+//! pipeline and signs a [`Judgement`]. Judgements use the dispute vote messages, so
+//! [`Auditor::disputes`] turns a negative one straight into a disputes extrinsic.
+//!
+//! Time is passed in explicitly (an offset on any monotonic clock), so audits are
+//! deterministic and testable offline. Bandersnatch VRFs are not implemented yet; until
+//! they are, a VRF output is the hash of the auditor's Ed25519 signature of the VRF input,
+//! which is likewise unpredictable to others and fixed for the auditor.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use ed25519_dalek::{Signer, SigningKey};
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+
+use crate::codec::{encode_seq, Encode};
+use crate::erasure::ErasureCoder;
+use crate::finality::verify_ed25519;
+use crate::refine::{refine, Pvm};
+use crate::schema::{blake2b_256, BlockchainError, Header, OpaqueHash};
+use crate::verification::{INVALID_CONTEXT, VALID_CONTEXT};
+use crate::work_package::{WorkPackageBundle, WorkReport};
+
+/// Context string of audit selection VRFs.
+pub const AUDIT_CONTEXT: &[u8] = b"jam_audit";
+/// Context string of audit announcements.
+pub const ANNOUNCE_CONTEXT: &[u8] = b"jam_announce";
+/// Time between audit tranches `A`.
+pub const TRANCHE_PERIOD: Duration = Duration::from_secs(8);
+/// Cores audited by each validator in tranche 0.
+pub const TRANCHE0_AUDITS: usize = 10;
+/// No-show bias factor `F`.
+pub const NO_SHOW_BIAS: u64 = 2;
+
+/// A report that became available on a core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditTarget {
+    pub core: u16,
+    pub report: WorkReport,
+}
+
+/// A validator's signed intent to audit reports in a tranche.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub tranche: u32,
+    /// Block the reports became available in.
+    pub block: OpaqueHash,
+    pub validator_index: u16,
+    /// Cores and hashes of the announced reports.
+    pub reports: Vec<(u16, OpaqueHash)>,
+    pub signature: Vec<u8>,
+}
+
+impl Announcement {
+    /// The signed message: context, tranche, reports and block hash.
+    pub fn message(tranche: u32, block: &OpaqueHash, reports: &[(u16, OpaqueHash)]) -> Vec<u8> {
+        let mut message = ANNOUNCE_CONTEXT.to_vec();
+        tranche.encode_to(&mut message);
+        let reports: Vec<Vec<u8>> = reports
+            .iter()
+            .map(|(core, hash)| [&core.to_le_bytes()[..], hash.as_bytes()].concat())
+            .collect();
+        encode_seq(&reports, &mut message);
+        block.encode_to(&mut message);
+        message
+    }
+
+    pub fn verify(&self, key: &OpaqueHash) -> Result<(), String> {
+        let message = Self::message(self.tranche, &self.block, &self.reports);
+        verify_ed25519(key, &message, &self.signature)
+    }
+}
+
+/// A validator's signed verdict on a report, in the form of a dispute vote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Judgement {
+    pub report_hash: OpaqueHash,
+    pub valid: bool,
+    pub validator_index: u16,
+    pub signature: Vec<u8>,
+}
+
+impl Judgement {
+    /// The signed message: the valid or invalid context followed by the report hash.
+    pub fn message(report_hash: &OpaqueHash, valid: bool) -> Vec<u8> {
+        let context = if valid {
+            VALID_CONTEXT
+        } else {
+            INVALID_CONTEXT
+        };
+        [context, report_hash.as_bytes()].concat()
+    }
+
+    pub fn verify(&self, key: &OpaqueHash) -> Result<(), String> {
+        verify_ed25519(
+            key,
+            &Self::message(&self.report_hash, self.valid),
+            &self.signature,
+        )
+    }
+}
+
+/// Audits the reports made available by one block, as one validator.
+///
+/// Memory Usage:
+/// - Per target: its work report
+/// - Per announcement or judgement seen: ~40 bytes
+#[derive(Debug)]
+pub struct Auditor {
+    key: SigningKey,
+    validator_index: u16,
+    /// Ed25519 keys of the active validators, to check announcements and judgements.
+    validators: Vec<OpaqueHash>,
+    cores_count: u16,
+    block: OpaqueHash,
+    /// VRF output of the block author, seeding every selection.
+    entropy: [u8; 32],
+    started: Duration,
+    targets: Vec<AuditTarget>,
+    /// Next tranche to select audits for.
+    next_tranche: u32,
+    /// Reports this auditor announced.
+    assigned: HashSet<OpaqueHash>,
+    /// Announcing validators per report, with the tranche of their announcement.
+    announced: HashMap<OpaqueHash, BTreeMap<u16, u32>>,
+    judgements: HashMap<OpaqueHash, BTreeMap<u16, Judgement>>,
+}
+
+impl Auditor {
+    /// Audit `targets`, the reports that became available in the block with header
+    /// `header`, imported at `started`.
+    pub fn new(
+        key: SigningKey,
+        validators: Vec<OpaqueHash>,
+        cores_count: u16,
+        header: &Header,
+        targets: Vec<AuditTarget>,
+        started: Duration,
+    ) -> Result<Self, BlockchainError> {
+        let public = OpaqueHash::new(key.verifying_key().to_bytes());
+        let validator_index = validators
+            .iter()
+            .position(|validator| *validator == public)
+            .ok_or_else(|| BlockchainError::InvalidSignature {
+                reason: "auditor key is not an active validator key".to_string(),
+            })? as u16;
+        Ok(Auditor {
+            key,
+            validator_index,
+            validators,
+            cores_count,
+            block: header.hash(),
+            entropy: blake2b_256(&header.entropy_source),
+            started,
+            targets,
+            next_tranche: 0,
+            assigned: HashSet::new(),
+            announced: HashMap::new(),
+            judgements: HashMap::new(),
+        })
+    }
+
+    pub fn validator_index(&self) -> u16 {
+        self.validator_index
+    }
+
+    pub fn targets(&self) -> &[AuditTarget] {
+        &self.targets
+    }
+
+    /// Tranche in progress at `now`.
+    pub fn tranche_at(&self, now: Duration) -> u32 {
+        let elapsed = now.saturating_sub(self.started);
+        (elapsed.as_secs() / TRANCHE_PERIOD.as_secs()) as u32
+    }
+
+    /// Select and announce audits for every tranche that started by `now`, returning the
+    /// announcements to gossip.
+    pub fn tick(&mut self, now: Duration) -> Vec<Announcement> {
+        let current = self.tranche_at(now);
+        let mut announcements = Vec::new();
+        while self.next_tranche <= current {
+            let tranche = self.next_tranche;
+            self.next_tranche += 1;
+            let picks: Vec<(u16, OpaqueHash)> = self
+                .select(tranche)
+                .into_iter()
+                .filter(|(_, hash)| self.assigned.insert(*hash))
+                .collect();
+            if picks.is_empty() {
+                continue;
+            }
+            info!(
+                "Validator {} audits {} reports in tranche {tranche}",
+                self.validator_index,
+                picks.len()
+            );
+            let message = Announcement::message(tranche, &self.block, &picks);
+            let announcement = Announcement {
+                tranche,
+                block: self.block,
+                validator_index: self.validator_index,
+                reports: picks,
+                signature: self.key.sign(&message).to_bytes().to_vec(),
+            };
+            self.record_announcement(&announcement);
+            announcements.push(announcement);
+        }
+        announcements
+    }
+
+    /// Reports selected in `tranche`, as core and report hash.
+    fn select(&self, tranche: u32) -> Vec<(u16, OpaqueHash)> {
+        let targets = self
+            .targets
+            .iter()
+            .map(|target| (target.core, target.report.hash()));
+        if tranche == 0 {
+            let seed = self.vrf_output(&[AUDIT_CONTEXT, &self.entropy[..]].concat());
+            let mut cores: Vec<u16> = (0..self.cores_count).collect();
+            cores.sort_by_key(|core| blake2b_256(&[&seed[..], &core.to_le_bytes()].concat()));
+            cores.truncate(TRANCHE0_AUDITS);
+            return targets.filter(|(core, _)| cores.contains(core)).collect();
+        }
+
+        let validators = self.validators.len() as u64;
+        targets
+            .filter(|(_, hash)| {
+                let no_shows = self.no_shows(hash, tranche) as u64;
+                let input = [
+                    AUDIT_CONTEXT,
+                    &self.entropy[..],
+                    hash.as_bytes(),
+                    &tranche.to_le_bytes(),
+                ]
+                .concat();
+                let draw = u64::from(self.vrf_output(&input)[0]);
+                // Picked with probability `256 F m / V` per 256 draws.
+                validators * draw < 256 * NO_SHOW_BIAS * no_shows
+            })
+            .collect()
+    }
+
+    /// Validators that announced `report` before `tranche - 1` ended without judging it.
+    pub fn no_shows(&self, report: &OpaqueHash, tranche: u32) -> usize {
+        let judged = self.judgements.get(report);
+        self.announced.get(report).map_or(0, |announcers| {
+            announcers
+                .iter()
+                .filter(|(validator, announced)| {
+                    **announced < tranche && judged.is_none_or(|j| !j.contains_key(validator))
+                })
+                .count()
+        })
+    }
+
+    /// Record another validator's announcement.
+    pub fn observe_announcement(
+        &mut self,
+        announcement: &Announcement,
+    ) -> Result<(), BlockchainError> {
+        if announcement.block != self.block {
+            return Ok(());
+        }
+        self.check_signature(announcement.validator_index, |key| announcement.verify(key))?;
+        self.record_announcement(announcement);
+        Ok(())
+    }
+
+    /// Record another validator's judgement.
+    pub fn observe_judgement(&mut self, judgement: &Judgement) -> Result<(), BlockchainError> {
+        self.check_signature(judgement.validator_index, |key| judgement.verify(key))?;
+        if !judgement.valid {
+            warn!(
+                "Validator {} judged report {} invalid",
+                judgement.validator_index,
+                hex::encode(judgement.report_hash.as_bytes())
+            );
+        }
+        self.record_judgement(judgement.clone());
+        Ok(())
+    }
+
+    /// Announced reports this auditor has not judged yet.
+    pub fn pending(&self) -> Vec<&AuditTarget> {
+        self.targets
+            .iter()
+            .filter(|target| {
+                let hash = target.report.hash();
+                self.assigned.contains(&hash)
+                    && !self
+                        .judgements
+                        .get(&hash)
+                        .is_some_and(|j| j.contains_key(&self.validator_index))
+            })
+            .collect()
+    }
+
+    /// Re-execute the report on `core` from its bundle and sign the verdict: valid only
+    /// if refinement reproduces the report exactly.
+    pub fn audit(
+        &mut self,
+        core: u16,
+        bundle: &WorkPackageBundle,
+        pvm: &mut dyn Pvm,
+        coder: &ErasureCoder,
+    ) -> Result<Judgement, BlockchainError> {
+        let target = self
+            .targets
+            .iter()
+            .find(|target| target.core == core)
+            .ok_or_else(|| BlockchainError::InvalidWorkPackage {
+                reason: format!("no report to audit on core {core}"),
+            })?;
+        let report_hash = target.report.hash();
+        let valid = match refine(pvm, coder, bundle, core) {
+            Ok(refined) => refined.report == target.report,
+            Err(e) => {
+                debug!("Audit of core {core} failed to refine: {e}");
+                false
+            }
+        };
+        let judgement = Judgement {
+            report_hash,
+            valid,
+            validator_index: self.validator_index,
+            signature: self
+                .key
+                .sign(&Judgement::message(&report_hash, valid))
+                .to_bytes()
+                .to_vec(),
+        };
+        self.record_judgement(judgement.clone());
+        Ok(judgement)
+    }
+
+    /// Whether `report` is audited: judged valid, never judged invalid, and judged by
+    /// every validator that announced it.
+    pub fn is_audited(&self, report: &OpaqueHash) -> bool {
+        let Some(judgements) = self.judgements.get(report) else {
+            return false;
+        };
+        judgements.values().all(|j| j.valid)
+            && self
+                .announced
+                .get(report)
+                .is_none_or(|announcers| announcers.keys().all(|v| judgements.contains_key(v)))
+    }
+
+    /// A disputes extrinsic with a verdict on every report judged invalid by anyone, or
+    /// `None` if no verdict can be formed yet. `age` is the epoch of the judgements
+    /// relative to the reports.
+    ///
+    /// A verdict holds exactly `⌊2V/3⌋ + 1` votes, of which 0 (bad) or `⌊V/3⌋` (wonky)
+    /// are positive, so a report stays out until enough judgements are in to fill one.
+    pub fn disputes(&self, age: u64) -> Option<Value> {
+        let supermajority = self.validators.len() * 2 / 3 + 1;
+        let one_third = self.validators.len() / 3;
+        let verdicts: Vec<Value> = self
+            .targets
+            .iter()
+            .filter_map(|target| {
+                let hash = target.report.hash();
+                let judgements = self.judgements.get(&hash)?;
+                let (valid, invalid): (Vec<&Judgement>, Vec<&Judgement>) =
+                    judgements.values().partition(|j| j.valid);
+                if invalid.is_empty() {
+                    return None;
+                }
+                let positive = if invalid.len() >= supermajority {
+                    0
+                } else if valid.len() >= one_third && invalid.len() >= supermajority - one_third {
+                    one_third
+                } else {
+                    return None;
+                };
+                let mut chosen: Vec<&Judgement> = valid[..positive]
+                    .iter()
+                    .chain(&invalid[..supermajority - positive])
+                    .copied()
+                    .collect();
+                chosen.sort_by_key(|j| j.validator_index);
+                let votes: Vec<Value> = chosen
+                    .into_iter()
+                    .map(|j| {
+                        json!({
+                            "vote": j.valid,
+                            "index": j.validator_index,
+                            "signature": format!("0x{}", hex::encode(&j.signature)),
+                        })
+                    })
+                    .collect();
+                Some(json!({
+                    "target": format!("0x{}", hex::encode(hash.as_bytes())),
+                    "age": age,
+                    "votes": votes,
+                }))
+            })
+            .collect();
+        (!verdicts.is_empty()).then(|| json!({ "verdicts": verdicts }))
+    }
+
+    /// Stand-in for a Bandersnatch VRF output over `input`.
+    fn vrf_output(&self, input: &[u8]) -> [u8; 32] {
+        blake2b_256(&self.key.sign(input).to_bytes())
+    }
+
+    fn check_signature(
+        &self,
+        validator_index: u16,
+        verify: impl FnOnce(&OpaqueHash) -> Result<(), String>,
+    ) -> Result<(), BlockchainError> {
+        let key = self
+            .validators
+            .get(usize::from(validator_index))
+            .ok_or_else(|| BlockchainError::InvalidAuthorIndex {
+                author_index: u64::from(validator_index),
+                max_validators: self.validators.len(),
+            })?;
+        verify(key).map_err(|reason| BlockchainError::InvalidSignature {
+            reason: format!("validator {validator_index}: {reason}"),
+        })
+    }
+
+    fn record_announcement(&mut self, announcement: &Announcement) {
+        for (_, hash) in &announcement.reports {
+            self.announced
+                .entry(*hash)
+                .or_default()
+                .entry(announcement.validator_index)
+                .or_insert(announcement.tranche);
+        }
+    }
+
+    fn record_judgement(&mut self, judgement: Judgement) {
+        self.judgements
+            .entry(judgement.report_hash)
+            .or_default()
+            .insert(judgement.validator_index, judgement);
+    }
+}
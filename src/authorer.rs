@@ -0,0 +1,575 @@
+//! Block authoring: slot claims and block building (GP 6, 5).
+//!
+//! An [`Authorer`] checks whether its key may seal a slot, either because the epoch is
+//! ticket-sealed and the slot's ticket is one of its own, or because the epoch falls back
+//! to keys drawn from the epoch entropy and the draw names it. It then assembles an
+//! extrinsic from an [`ExtrinsicPool`], orders and bounds each part the way the STF
+//! expects, fills in the header commitments and seals the header. Every built block is
+//! run through [`stf::apply`] before it is returned, so it imports on our own node.
+//!
+//! The modelled Safrole state keeps one epoch entropy and the sealing tickets of the
+//! current and next epochs in place of `η` and `γ_s`; [`EpochSealing::for_block`] derives
+//! how a slot is sealed from them, and [`verify_author`] checks a header against it, the
+//! same way for the STF and the light client. Bandersnatch is not implemented; until it
+//! is, a VRF signature is an Ed25519 signature of the VRF input and auxiliary data,
+//! followed by the VRF output: the hash of the signer's key and the input, which
+//! [`verify_seal`] recomputes. Unlike a Bandersnatch output it can be predicted by anyone,
+//! so it adds no unpredictability to the entropy, but the author cannot choose it.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use ed25519_dalek::{Signer, SigningKey};
+use log::{debug, info};
+use serde_json::{json, Value};
+
+use crate::chain_spec::ChainSpec;
+use crate::codec::Encode;
+use crate::coretime::{MAX_ASSURANCES_PER_BLOCK, MAX_DISPUTE_AGE};
+use crate::error_code::ErrorCode;
+use crate::finality::verify_ed25519;
+use crate::schema::{
+    blake2b_256, Block, BlockchainError, EpochMark, Extrinsic, Header, OpaqueHash, Preimage, State,
+    TicketBody, TicketEnvelope,
+};
+use crate::stf;
+use crate::verification::TICKET_SEAL_CONTEXT;
+
+/// Context string of fallback seals (GP 6.16).
+pub const FALLBACK_SEAL_CONTEXT: &[u8] = b"jam_fallback_seal";
+/// Context string of the entropy-source VRF (GP 6.17).
+pub const ENTROPY_CONTEXT: &[u8] = b"jam_entropy";
+/// Length of an Ed25519 signature, the signed part of a VRF signature.
+const SIGNATURE_LEN: usize = 64;
+
+/// How the blocks of an epoch are sealed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpochSealing {
+    /// Epoch entropy `η_3`, drawn from for fallback keys and bound into seals.
+    pub entropy: OpaqueHash,
+    /// Sealing tickets of the epoch in slot order, when it is ticket-sealed.
+    pub tickets: Vec<TicketBody>,
+}
+
+impl EpochSealing {
+    /// Sealing of the epoch `state` is in.
+    pub fn of_state(state: &State) -> Self {
+        EpochSealing {
+            entropy: state.ticket_state.entropy,
+            tickets: state.ticket_state.sealing_tickets.clone(),
+        }
+    }
+
+    /// Sealing of a block at `slot` on top of `state`, which carries `epoch_mark`.
+    pub fn for_block(
+        spec: &ChainSpec,
+        state: &State,
+        slot: u32,
+        epoch_mark: Option<&EpochMark>,
+    ) -> Self {
+        Self::of_state(state).advance(
+            spec,
+            state.last_slot,
+            &state.ticket_state.next_tickets,
+            slot,
+            epoch_mark,
+        )
+    }
+
+    /// Sealing of a block at `slot` following one at `prior_slot` sealed by `self`.
+    ///
+    /// A block opening the next epoch is sealed by `next_tickets`, if any, and by the
+    /// entropy its `epoch_mark` announces; after a skipped epoch it falls back to keys.
+    pub fn advance(
+        &self,
+        spec: &ChainSpec,
+        prior_slot: u64,
+        next_tickets: &[TicketBody],
+        slot: u32,
+        epoch_mark: Option<&EpochMark>,
+    ) -> Self {
+        let epoch = spec.epoch_of(u64::from(slot));
+        let prior_epoch = spec.epoch_of(prior_slot);
+        if epoch == prior_epoch {
+            return self.clone();
+        }
+        EpochSealing {
+            entropy: epoch_mark.map_or(self.entropy, |mark| mark.entropy),
+            tickets: if epoch == prior_epoch + 1 {
+                next_tickets.to_vec()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
+/// How a claimed slot is sealed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SealKind {
+    /// With one of our tickets.
+    Ticket(TicketBody),
+    /// With our key, drawn as the slot's fallback key.
+    Fallback,
+}
+
+/// A slot our key may seal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotClaim {
+    pub slot: u32,
+    pub author_index: u16,
+    pub seal: SealKind,
+}
+
+/// A built block with the root of its posterior state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthoredBlock {
+    pub block: Block,
+    pub state_root: [u8; 32],
+}
+
+/// Extrinsic items waiting for inclusion.
+///
+/// Memory Usage:
+/// - Per item: its JSON or blob plus ~50 bytes
+#[derive(Debug, Clone, Default)]
+pub struct ExtrinsicPool {
+    tickets: Vec<TicketEnvelope>,
+    preimages: Vec<Preimage>,
+    guarantees: Vec<Value>,
+    assurances: Vec<Value>,
+    verdicts: Vec<Value>,
+}
+
+impl ExtrinsicPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_ticket(&mut self, ticket: TicketEnvelope) {
+        self.tickets.push(ticket);
+    }
+
+    pub fn add_preimage(&mut self, preimage: Preimage) {
+        self.preimages.push(preimage);
+    }
+
+    pub fn add_guarantee(&mut self, guarantee: Value) {
+        self.guarantees.push(guarantee);
+    }
+
+    pub fn add_assurance(&mut self, assurance: Value) {
+        self.assurances.push(assurance);
+    }
+
+    /// Add the verdicts of a disputes extrinsic.
+    pub fn add_disputes(&mut self, disputes: &Value) {
+        if let Some(verdicts) = disputes.get("verdicts").and_then(Value::as_array) {
+            self.verdicts.extend(verdicts.iter().cloned());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+            + self.preimages.len()
+            + self.guarantees.len()
+            + self.assurances.len()
+            + self.verdicts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the items `extrinsic` included.
+    pub fn remove_included(&mut self, extrinsic: &Extrinsic) {
+        self.tickets.retain(|t| !extrinsic.tickets.contains(t));
+        self.preimages.retain(|p| !extrinsic.preimages.contains(p));
+        self.guarantees
+            .retain(|g| !extrinsic.guarantees.contains(g));
+        self.assurances
+            .retain(|a| !extrinsic.assurances.contains(a));
+        let included = extrinsic.disputes.get("verdicts").and_then(Value::as_array);
+        self.verdicts
+            .retain(|v| !included.is_some_and(|included| included.contains(v)));
+    }
+}
+
+/// Claims slots and builds blocks for one validator key.
+///
+/// Memory Usage:
+/// - Fixed: ~100 bytes plus 32 bytes per own ticket
+#[derive(Debug, Clone)]
+pub struct Authorer {
+    key: SigningKey,
+    public: OpaqueHash,
+    spec: ChainSpec,
+    /// Ids of the tickets this validator submitted.
+    tickets: HashSet<OpaqueHash>,
+}
+
+impl Authorer {
+    pub fn new(key: SigningKey, spec: ChainSpec) -> Self {
+        let public = OpaqueHash::new(key.verifying_key().to_bytes());
+        Authorer {
+            key,
+            public,
+            spec,
+            tickets: HashSet::new(),
+        }
+    }
+
+    pub fn public_key(&self) -> &OpaqueHash {
+        &self.public
+    }
+
+    /// Remember a ticket we submitted, so slots it wins can be claimed.
+    pub fn add_ticket(&mut self, id: OpaqueHash) {
+        self.tickets.insert(id);
+    }
+
+    /// Whether our key may seal `slot` on top of `state`.
+    pub fn claim(&self, state: &State, slot: u32) -> Option<SlotClaim> {
+        if u64::from(slot) <= state.get_last_slot() {
+            return None;
+        }
+        let validators = sealing_validators(&self.spec, state, slot);
+        let author_index = validators.iter().position(|key| *key == self.public)?;
+        let phase = slot % self.spec.epoch_length;
+        // Our epoch mark repeats the state's entropy, see `build`.
+        let sealing = EpochSealing::for_block(&self.spec, state, slot, None);
+
+        let seal = if !sealing.tickets.is_empty() {
+            let ticket = &sealing.tickets[phase as usize % sealing.tickets.len()];
+            if !self.tickets.contains(&ticket.id) {
+                return None;
+            }
+            SealKind::Ticket(ticket.clone())
+        } else {
+            if fallback_index(&sealing.entropy, phase, validators.len()) != author_index {
+                return None;
+            }
+            SealKind::Fallback
+        };
+        debug!("Claimed slot {slot} as validator {author_index}: {seal:?}");
+        Some(SlotClaim {
+            slot,
+            author_index: author_index as u16,
+            seal,
+        })
+    }
+
+    /// Build and seal the block for `claim` on top of `parent`, whose posterior state is
+    /// `state`, with what `pool` holds.
+    pub fn build(
+        &self,
+        parent: OpaqueHash,
+        state: &State,
+        claim: &SlotClaim,
+        pool: &ExtrinsicPool,
+    ) -> Result<AuthoredBlock> {
+        let slot = claim.slot;
+        let sealing = EpochSealing::for_block(&self.spec, state, slot, None);
+        let extrinsic = assemble(&self.spec, state, parent, slot, pool);
+        let new_epoch = self.spec.epoch_of(u64::from(slot)) != self.spec.epoch_of(state.last_slot);
+        // No staging set is modelled: the epoch mark re-announces the next epoch's keys.
+        let epoch_mark = new_epoch.then(|| EpochMark {
+            entropy: sealing.entropy,
+            tickets_entropy: sealing.entropy,
+            validators: sealing_validators(&self.spec, state, slot),
+        });
+
+        let header = Header {
+            parent,
+            parent_state_root: OpaqueHash::new(
+                state.last_state_root.unwrap_or_else(|| state.state_root()),
+            ),
+            extrinsic_hash: OpaqueHash::new(extrinsic.compute_hash()?),
+            slot,
+            epoch_mark,
+            tickets_mark: None,
+            offenders_mark: Vec::new(),
+            author_index: claim.author_index,
+            entropy_source: Vec::new(),
+            seal: Vec::new(),
+        };
+        let header = self.seal(header, &sealing.entropy, &claim.seal);
+
+        let block = Block { header, extrinsic };
+        let (posterior, _) = stf::apply(state, &block, &self.spec)?;
+        let state_root = posterior
+            .last_state_root
+            .unwrap_or_else(|| posterior.state_root());
+        info!(
+            "Authored block {} at slot {slot}: {} guarantees, {} assurances, {} tickets",
+            hex::encode(block.header.hash().as_bytes()),
+            block.extrinsic.guarantees.len(),
+            block.extrinsic.assurances.len(),
+            block.extrinsic.tickets.len()
+        );
+        Ok(AuthoredBlock { block, state_root })
+    }
+
+    /// Sign the entropy source of `header` and seal it with epoch `entropy`, as the
+    /// slot's ticket holder or fallback key.
+    pub fn seal(&self, mut header: Header, entropy: &OpaqueHash, kind: &SealKind) -> Header {
+        let seal_input = seal_input(entropy, kind);
+        let seal_output = vrf_output(&self.public, &seal_input);
+        let entropy_input = [ENTROPY_CONTEXT, &seal_output[..]].concat();
+        header.entropy_source = self.vrf_sign(&entropy_input, &[]);
+        header.seal = Vec::new();
+        header.seal = self.vrf_sign(&seal_input, &header.encode());
+        header
+    }
+
+    /// VRF signature stand-in: a signature of `input ++ aux` followed by the output.
+    fn vrf_sign(&self, input: &[u8], aux: &[u8]) -> Vec<u8> {
+        let mut signature = self.key.sign(&[input, aux].concat()).to_bytes().to_vec();
+        signature.extend_from_slice(&vrf_output(&self.public, input));
+        signature
+    }
+}
+
+/// Check that the author of `header` may seal its slot under `sealing` and did.
+///
+/// The author must hold the slot's ticket, or be its fallback key when the epoch has no
+/// tickets, and have signed the seal and entropy source with its key in `validators`.
+/// With no validator set every header is rejected.
+pub fn verify_author(
+    spec: &ChainSpec,
+    header: &Header,
+    validators: &[OpaqueHash],
+    sealing: &EpochSealing,
+) -> Result<(), BlockchainError> {
+    let author_index = usize::from(header.author_index);
+    let Some(author) = validators.get(author_index) else {
+        return Err(BlockchainError::InvalidAuthorIndex {
+            author_index: u64::from(header.author_index),
+            max_validators: validators.len(),
+        });
+    };
+    let phase = header.slot % spec.epoch_length;
+    let ticket = if sealing.tickets.is_empty() {
+        let expected = fallback_index(&sealing.entropy, phase, validators.len());
+        if author_index != expected {
+            return Err(BlockchainError::rejected(
+                ErrorCode::UnexpectedAuthor,
+                None,
+                format!(
+                    "Validator {author_index} sealed slot {} of fallback key {expected}",
+                    header.slot
+                ),
+            ));
+        }
+        None
+    } else {
+        sealing.tickets.get(phase as usize % sealing.tickets.len())
+    };
+    verify_seal(header, author, &sealing.entropy, ticket)
+}
+
+/// Check that `author` sealed `header` and signed its entropy source, both carrying the
+/// VRF outputs of `author`'s key. `ticket` is the slot's sealing ticket in a ticket-sealed
+/// epoch.
+pub fn verify_seal(
+    header: &Header,
+    author: &OpaqueHash,
+    entropy: &OpaqueHash,
+    ticket: Option<&TicketBody>,
+) -> Result<(), BlockchainError> {
+    let kind = ticket.map_or(SealKind::Fallback, |ticket| {
+        SealKind::Ticket(ticket.clone())
+    });
+    let input = seal_input(entropy, &kind);
+    let unsealed = Header {
+        seal: Vec::new(),
+        ..header.clone()
+    };
+    let bad = |what: &str, reason: String| BlockchainError::InvalidSignature {
+        reason: format!("{what} of block at slot {}: {reason}", header.slot),
+    };
+    let (seal, seal_output) = split_vrf(&header.seal).map_err(|e| bad("seal", e))?;
+    check_vrf_output(author, &input, seal_output).map_err(|e| bad("seal", e))?;
+    verify_ed25519(author, &[&input[..], &unsealed.encode()].concat(), seal)
+        .map_err(|e| bad("seal", e))?;
+    let entropy_input = [ENTROPY_CONTEXT, seal_output].concat();
+    let (entropy_signature, entropy_output) =
+        split_vrf(&header.entropy_source).map_err(|e| bad("entropy source", e))?;
+    check_vrf_output(author, &entropy_input, entropy_output)
+        .map_err(|e| bad("entropy source", e))?;
+    verify_ed25519(author, &entropy_input, entropy_signature).map_err(|e| bad("entropy source", e))
+}
+
+/// Index of the fallback key for slot `phase` of an epoch, among `validators` keys
+/// (GP 6.26).
+pub fn fallback_index(entropy: &OpaqueHash, phase: u32, validators: usize) -> usize {
+    if validators == 0 {
+        return 0;
+    }
+    let hash = blake2b_256(&[&entropy.as_bytes()[..], &phase.to_le_bytes()].concat());
+    let draw = u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    draw as usize % validators
+}
+
+/// Ticket or fallback seal VRF input: context, epoch entropy and ticket attempt.
+fn seal_input(entropy: &OpaqueHash, kind: &SealKind) -> Vec<u8> {
+    match kind {
+        SealKind::Ticket(ticket) => {
+            [TICKET_SEAL_CONTEXT, entropy.as_bytes(), &[ticket.attempt]].concat()
+        }
+        SealKind::Fallback => [FALLBACK_SEAL_CONTEXT, entropy.as_bytes()].concat(),
+    }
+}
+
+/// VRF output stand-in for `input` signed by `public`.
+fn vrf_output(public: &OpaqueHash, input: &[u8]) -> [u8; 32] {
+    blake2b_256(&[public.as_bytes(), input].concat())
+}
+
+fn check_vrf_output(public: &OpaqueHash, input: &[u8], output: &[u8]) -> Result<(), String> {
+    if output != vrf_output(public, input) {
+        return Err("VRF output does not match its input".to_string());
+    }
+    Ok(())
+}
+
+fn split_vrf(signature: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    if signature.len() != SIGNATURE_LEN + 32 {
+        return Err(format!(
+            "{} bytes, expected {}",
+            signature.len(),
+            SIGNATURE_LEN + 32
+        ));
+    }
+    Ok(signature.split_at(SIGNATURE_LEN))
+}
+
+/// Validators of the epoch containing `slot`: the pending set takes over at an epoch
+/// change, as in the STF's hand-over.
+fn sealing_validators(spec: &ChainSpec, state: &State, slot: u32) -> Vec<OpaqueHash> {
+    let new_epoch = spec.epoch_of(u64::from(slot)) != spec.epoch_of(state.last_slot);
+    if new_epoch && !state.pending_validators.is_empty() {
+        state.pending_validators.clone()
+    } else {
+        state.active_validators.clone()
+    }
+}
+
+/// Select and order the pool items a block at `slot` on top of `parent` may carry.
+fn assemble(
+    spec: &ChainSpec,
+    state: &State,
+    parent: OpaqueHash,
+    slot: u32,
+    pool: &ExtrinsicPool,
+) -> Extrinsic {
+    // Tickets only during the submission period, at most `K`, sorted by id and unique.
+    // Until ring VRFs are verified, a ticket's id is the hash of its proof.
+    let mut tickets = Vec::new();
+    if slot % spec.epoch_length < spec.ticket_submission_end {
+        let mut pooled: Vec<_> = pool
+            .tickets
+            .iter()
+            .filter(|ticket| ticket.validate().is_ok())
+            .map(|ticket| (blake2b_256(&ticket.signature), ticket))
+            .collect();
+        pooled.sort_by_key(|(id, _)| *id);
+        pooled.dedup_by_key(|(id, _)| *id);
+        tickets = pooled
+            .into_iter()
+            .take(usize::from(spec.max_tickets_per_extrinsic))
+            .map(|(_, ticket)| ticket.clone())
+            .collect();
+    }
+
+    // Preimages sorted by requester and hash, unique and non-empty.
+    let mut preimages: Vec<_> = pool
+        .preimages
+        .iter()
+        .filter(|p| p.requester != 0 && !p.blob.is_empty())
+        .map(|p| ((p.requester, blake2b_256(&p.blob)), p))
+        .collect();
+    preimages.sort_by_key(|(key, _)| *key);
+    preimages.dedup_by_key(|(key, _)| *key);
+    let preimages = preimages.into_iter().map(|(_, p)| p.clone()).collect();
+
+    // Assurances anchored on the parent, one per validator, sorted by validator.
+    let mut assurances: Vec<(u64, &Value)> = pool
+        .assurances
+        .iter()
+        .filter(|a| {
+            a.get("bitfield")
+                .and_then(Value::as_str)
+                .is_some_and(|bitfield| !bitfield.trim().is_empty())
+        })
+        .filter(|a| {
+            a.get("anchor")
+                .and_then(Value::as_str)
+                .and_then(|anchor| OpaqueHash::from_hex(anchor).ok())
+                == Some(parent)
+        })
+        .filter_map(|a| Some((a.get("validator_index")?.as_u64()?, a)))
+        .collect();
+    assurances.sort_by_key(|(index, _)| *index);
+    assurances.dedup_by_key(|(index, _)| *index);
+    let assurances: Vec<Value> = assurances
+        .into_iter()
+        .take(MAX_ASSURANCES_PER_BLOCK)
+        .map(|(_, a)| a.clone())
+        .collect();
+
+    // Verdicts with votes and within the age limit, sorted by target and unique.
+    let mut verdicts: Vec<(String, &Value)> = pool
+        .verdicts
+        .iter()
+        .filter(|v| v.get("age").and_then(Value::as_u64).unwrap_or(0) <= MAX_DISPUTE_AGE)
+        .filter(|v| {
+            v.get("votes")
+                .and_then(Value::as_array)
+                .is_some_and(|votes| !votes.is_empty())
+        })
+        .filter_map(|v| Some((v.get("target")?.as_str()?.to_lowercase(), v)))
+        .collect();
+    verdicts.sort_by(|a, b| a.0.cmp(&b.0));
+    verdicts.dedup_by(|a, b| a.0 == b.0);
+    let disputes = if verdicts.is_empty() {
+        Value::Null
+    } else {
+        json!({ "verdicts": verdicts.into_iter().map(|(_, v)| v.clone()).collect::<Vec<_>>() })
+    };
+
+    // Guarantees sorted by core, one per core, added while the CoreTime limits hold.
+    let mut pooled: Vec<(u64, &Value)> = pool
+        .guarantees
+        .iter()
+        .filter_map(|g| Some((g.get("report")?.get("core_index")?.as_u64()?, g)))
+        .filter(|(core, _)| *core < u64::from(spec.cores_count))
+        .collect();
+    pooled.sort_by_key(|(core, _)| *core);
+    let mut guarantees: Vec<Value> = Vec::new();
+    for (core, guarantee) in pooled {
+        if guarantees
+            .last()
+            .and_then(|g| g["report"]["core_index"].as_u64())
+            == Some(core)
+        {
+            continue;
+        }
+        guarantees.push(guarantee.clone());
+        let mut ledger = state.coretime.clone();
+        if let Err(e) =
+            ledger.validate_and_apply(u64::from(slot), &guarantees, &assurances, &disputes)
+        {
+            debug!("Leaving out guarantee for core {core}: {e}");
+            guarantees.pop();
+        }
+    }
+
+    Extrinsic {
+        tickets,
+        preimages,
+        guarantees,
+        assurances,
+        disputes,
+    }
+}
@@ -0,0 +1,269 @@
+//! Local data availability store for audits and segment imports.
+//!
+//! Two kinds of data are kept, each for its own retention window:
+//! - audit DA: the encoded bundles of packages this node refined or reconstructed, which
+//!   auditors need until the reports are finalized (one epoch);
+//! - imports DA (D3L): exported segments by segments root, which later packages may
+//!   import for 28 days.
+//!
+//! Imports may name the package that exported a segment rather than its segments root.
+//! Those are resolved through the packages reported in the last [`RECENT_HISTORY_LEN`]
+//! blocks, as in the recent-history state. The modelled state only keeps the last block
+//! hash in `C(3)`, so the lookup is fed from the STF's reported packages
+//! ([`crate::stf::StfOutputs::reported`], or [`crate::events::ImportEvent::WorkReported`]).
+//! Bundles carry the roots resolved this way in their segment root lookup, so refinement
+//! can verify every import.
+
+use std::collections::{HashMap, VecDeque};
+
+use log::{debug, info};
+
+use crate::chain_spec::ChainSpec;
+use crate::codec::Encode;
+use crate::erasure::{segment_justification, verify_segment};
+use crate::refine::RefinedPackage;
+use crate::schema::{BlockchainError, OpaqueHash};
+use crate::stf::ReportedPackage;
+use crate::work_package::{ImportSpec, SegmentRootLookup, WorkPackage, WorkPackageBundle};
+
+/// Blocks whose reported packages are kept for segment-root lookups `H`.
+pub const RECENT_HISTORY_LEN: usize = 8;
+/// Slots exported segments stay importable: 28 days of 6-second slots.
+pub const IMPORTS_RETENTION_SLOTS: u64 = 28 * 24 * 600;
+
+/// How long each kind of data is kept, in slots after it was stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub audit_slots: u64,
+    pub imports_slots: u64,
+}
+
+impl Retention {
+    /// Bundles for one epoch, segments for 28 days.
+    pub fn for_spec(spec: &ChainSpec) -> Self {
+        Retention {
+            audit_slots: u64::from(spec.epoch_length),
+            imports_slots: IMPORTS_RETENTION_SLOTS,
+        }
+    }
+}
+
+/// Segments exported by one package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StoredExports {
+    slot: u64,
+    segments: Vec<Vec<u8>>,
+}
+
+/// What [`AvailabilityStore::expire`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expired {
+    pub bundles: usize,
+    pub exports: usize,
+}
+
+/// Audit and imports DA held in memory.
+///
+/// Memory Usage:
+/// - Per bundle: its encoded size plus ~40 bytes
+/// - Per exporting package: 4104 bytes per segment plus ~40 bytes
+/// - Recent history: 64 bytes per reported package over the last 8 blocks
+#[derive(Debug, Clone)]
+pub struct AvailabilityStore {
+    retention: Retention,
+    /// Encoded bundles by package hash, with the slot they were stored at.
+    bundles: HashMap<OpaqueHash, (u64, Vec<u8>)>,
+    exports: HashMap<OpaqueHash, StoredExports>,
+    /// Package hash to segments root, per recent block, oldest first.
+    recent: VecDeque<Vec<(OpaqueHash, OpaqueHash)>>,
+}
+
+impl AvailabilityStore {
+    pub fn new(retention: Retention) -> Self {
+        AvailabilityStore {
+            retention,
+            bundles: HashMap::new(),
+            exports: HashMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn retention(&self) -> &Retention {
+        &self.retention
+    }
+
+    /// Keep the encoded bundle of a package for auditors.
+    pub fn put_bundle(&mut self, slot: u64, package_hash: OpaqueHash, bundle: Vec<u8>) {
+        self.bundles.insert(package_hash, (slot, bundle));
+    }
+
+    /// The encoded bundle of a package, if held.
+    pub fn bundle(&self, package_hash: &OpaqueHash) -> Option<&[u8]> {
+        self.bundles
+            .get(package_hash)
+            .map(|(_, bundle)| bundle.as_slice())
+    }
+
+    /// Keep the segments exported under `root`.
+    pub fn put_exports(&mut self, slot: u64, root: OpaqueHash, segments: Vec<Vec<u8>>) {
+        self.exports.insert(root, StoredExports { slot, segments });
+    }
+
+    /// Keep the bundle and exports of a package refined at `slot`.
+    pub fn put_refined(&mut self, slot: u64, bundle: &WorkPackageBundle, refined: &RefinedPackage) {
+        let spec = &refined.report.package_spec;
+        self.put_bundle(slot, spec.hash, bundle.encode());
+        if !refined.exports.is_empty() {
+            self.put_exports(slot, spec.exports_root, refined.exports.clone());
+        }
+    }
+
+    /// Segment `index` under `root`, if held.
+    pub fn segment(&self, root: &OpaqueHash, index: u16) -> Option<&[u8]> {
+        self.exports
+            .get(root)?
+            .segments
+            .get(usize::from(index))
+            .map(Vec::as_slice)
+    }
+
+    /// Record the packages reported by a newly imported block.
+    pub fn record_reported(&mut self, reported: &[ReportedPackage]) {
+        self.recent.push_back(
+            reported
+                .iter()
+                .map(|package| (package.work_package_hash, package.exports_root))
+                .collect(),
+        );
+        while self.recent.len() > RECENT_HISTORY_LEN {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Segments root of a package reported in the recent history.
+    pub fn segments_root_of(&self, package_hash: &OpaqueHash) -> Option<OpaqueHash> {
+        self.recent
+            .iter()
+            .rev()
+            .flatten()
+            .find(|(hash, _)| hash == package_hash)
+            .map(|(_, root)| *root)
+    }
+
+    /// The segment an import names, by segments root or by the hash of a recently
+    /// reported package, with its justification under the segments root.
+    pub fn resolve(
+        &self,
+        import: &ImportSpec,
+    ) -> Result<(Vec<u8>, Vec<OpaqueHash>), BlockchainError> {
+        let root = if self.exports.contains_key(&import.tree_root) {
+            import.tree_root
+        } else {
+            self.segments_root_of(&import.tree_root)
+                .unwrap_or(import.tree_root)
+        };
+        let unavailable = || BlockchainError::SegmentUnavailable {
+            root: hex::encode(import.tree_root.as_bytes()),
+            index: import.index,
+        };
+        let exports = self.exports.get(&root).ok_or_else(unavailable)?;
+        let index = usize::from(import.index);
+        let segment = exports.segments.get(index).ok_or_else(unavailable)?;
+        let justification = segment_justification(&exports.segments, index)
+            .into_iter()
+            .map(OpaqueHash::new)
+            .collect();
+        Ok((segment.clone(), justification))
+    }
+
+    /// Resolve every import of `package` into a bundle ready for refinement.
+    pub fn bundle_for(
+        &self,
+        package: WorkPackage,
+        extrinsics: Vec<Vec<u8>>,
+    ) -> Result<WorkPackageBundle, BlockchainError> {
+        let imports: Vec<&ImportSpec> = package
+            .items
+            .iter()
+            .flat_map(|item| &item.import_segments)
+            .collect();
+        let mut segment_root_lookup: Vec<SegmentRootLookup> = Vec::new();
+        for import in &imports {
+            if self.exports.contains_key(&import.tree_root)
+                || segment_root_lookup
+                    .iter()
+                    .any(|lookup| lookup.work_package_hash == import.tree_root)
+            {
+                continue;
+            }
+            if let Some(root) = self.segments_root_of(&import.tree_root) {
+                segment_root_lookup.push(SegmentRootLookup {
+                    work_package_hash: import.tree_root,
+                    segment_tree_root: root,
+                });
+            }
+        }
+        let (imports, justifications) = imports
+            .into_iter()
+            .map(|import| self.resolve(import))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        Ok(WorkPackageBundle {
+            package,
+            extrinsics,
+            imports,
+            justifications,
+            segment_root_lookup,
+        })
+    }
+
+    /// Drop bundles and exports whose retention window ended before `slot`.
+    pub fn expire(&mut self, slot: u64) -> Expired {
+        let (bundles, exports) = (self.bundles.len(), self.exports.len());
+        let Retention {
+            audit_slots,
+            imports_slots,
+        } = self.retention;
+        self.bundles
+            .retain(|_, (stored, _)| slot.saturating_sub(*stored) <= audit_slots);
+        self.exports
+            .retain(|_, stored| slot.saturating_sub(stored.slot) <= imports_slots);
+
+        let expired = Expired {
+            bundles: bundles - self.bundles.len(),
+            exports: exports - self.exports.len(),
+        };
+        if expired != Expired::default() {
+            info!(
+                "Expired {} bundles and {} exports at slot {slot}",
+                expired.bundles, expired.exports
+            );
+        }
+        debug!(
+            "Availability store holds {} bundles and {} exports",
+            self.bundles.len(),
+            self.exports.len()
+        );
+        expired
+    }
+}
+
+/// Check an imported segment against the root it was exported under.
+pub fn verify_import(
+    root: &OpaqueHash,
+    index: u16,
+    segment: &[u8],
+    justification: &[OpaqueHash],
+) -> Result<(), BlockchainError> {
+    let path: Vec<[u8; 32]> = justification.iter().map(|hash| *hash.as_bytes()).collect();
+    if verify_segment(root.as_bytes(), usize::from(index), segment, &path) {
+        return Ok(());
+    }
+    Err(BlockchainError::InvalidWorkPackage {
+        reason: format!(
+            "segment {index} does not verify under {}",
+            hex::encode(root.as_bytes())
+        ),
+    })
+}
@@ -0,0 +1,330 @@
+//! Fork-aware tree of imported blocks and their posterior states.
+//!
+//! The tree is rooted at the last finalized block. Every imported block is stored with
+//! its header and posterior state, so a new block may extend any known block rather than
+//! only the most recent one. The best head follows the GP 19 rule: prefer the chain with
+//! the most ticket-sealed blocks and never choose a chain containing an equivocation.
+
+use std::collections::{HashMap, HashSet};
+
+use log::{debug, warn};
+
+use crate::schema::{Header, OpaqueHash, State};
+
+/// A block stored in the tree.
+///
+/// Memory Usage:
+/// - Fixed: ~250 bytes + header + posterior state
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeNode {
+    pub header: Option<Header>,
+    pub state: State,
+    pub parent: Option<OpaqueHash>,
+    pub children: Vec<OpaqueHash>,
+    /// Number of ticket-sealed blocks from the root up to and including this block.
+    pub ticketed: u64,
+    /// Distance from the root.
+    pub depth: u64,
+    /// Another block by the same author exists for the same slot.
+    pub equivocated: bool,
+    /// This block or one of its ancestors is equivocated.
+    pub chain_equivocated: bool,
+}
+
+impl TreeNode {
+    /// Fork-choice score: most ticket-sealed ancestors, then highest slot, then depth.
+    fn score(&self) -> (u64, u64, u64) {
+        (self.ticketed, self.slot(), self.depth)
+    }
+
+    /// Timeslot of the block (the root's last slot if it has no header).
+    pub fn slot(&self) -> u64 {
+        self.header
+            .as_ref()
+            .map(|header| u64::from(header.slot))
+            .unwrap_or_else(|| self.state.get_last_slot())
+    }
+}
+
+/// Tree of non-finalized blocks rooted at the finalized block.
+///
+/// Ticket counts, depth and equivocation are carried down from the parent on insert, so
+/// importing a block costs the same however large the tree is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTree {
+    root: OpaqueHash,
+    best: OpaqueHash,
+    nodes: HashMap<OpaqueHash, TreeNode>,
+    /// Blocks by slot and author, to spot equivocations.
+    authored: HashMap<(u32, u16), Vec<OpaqueHash>>,
+}
+
+impl BlockTree {
+    /// Create a tree whose root (finalized) block has the given hash and posterior state.
+    pub fn new(root: OpaqueHash, root_header: Option<Header>, state: State) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            root,
+            TreeNode {
+                header: root_header,
+                state,
+                parent: None,
+                children: Vec::new(),
+                ticketed: 0,
+                depth: 0,
+                equivocated: false,
+                chain_equivocated: false,
+            },
+        );
+        BlockTree {
+            root,
+            best: root,
+            nodes,
+            authored: HashMap::new(),
+        }
+    }
+
+    /// Hash of the root, i.e. the last finalized block.
+    pub fn root(&self) -> OpaqueHash {
+        self.root
+    }
+
+    /// Hash of the current best head.
+    pub fn best(&self) -> OpaqueHash {
+        self.best
+    }
+
+    /// Node of the current best head.
+    pub fn best_node(&self) -> &TreeNode {
+        &self.nodes[&self.best]
+    }
+
+    /// Look up a block by hash.
+    pub fn get(&self, hash: &OpaqueHash) -> Option<&TreeNode> {
+        self.nodes.get(hash)
+    }
+
+    /// Whether a block is present in the tree.
+    pub fn contains(&self, hash: &OpaqueHash) -> bool {
+        self.nodes.contains_key(hash)
+    }
+
+    /// Number of blocks held, including the root.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// A tree always holds at least its root.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Hashes of every block held, including the root.
+    pub fn hashes(&self) -> impl Iterator<Item = &OpaqueHash> {
+        self.nodes.keys()
+    }
+
+    /// Blocks without children.
+    pub fn leaves(&self) -> impl Iterator<Item = &OpaqueHash> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.children.is_empty())
+            .map(|(hash, _)| hash)
+    }
+
+    /// Insert a validated block as a child of `parent`.
+    ///
+    /// Returns `true` if the best head changed.
+    pub fn insert(
+        &mut self,
+        parent: OpaqueHash,
+        header: Header,
+        state: State,
+        ticket_sealed: bool,
+    ) -> bool {
+        let hash = header.hash();
+        let parent_node = &self.nodes[&parent];
+        let ticketed = parent_node.ticketed + u64::from(ticket_sealed);
+        let depth = parent_node.depth + 1;
+        let parent_equivocated = parent_node.chain_equivocated;
+
+        // Equivocation: a different block by the same author for the same slot.
+        let authored = self
+            .authored
+            .entry((header.slot, header.author_index))
+            .or_default();
+        let others: Vec<OpaqueHash> = authored.iter().filter(|h| **h != hash).copied().collect();
+        authored.push(hash);
+        let equivocated = !others.is_empty();
+        if equivocated {
+            warn!(
+                "Equivocation by author {} at slot {}",
+                header.author_index, header.slot
+            );
+        }
+
+        self.nodes
+            .get_mut(&parent)
+            .expect("parent checked above")
+            .children
+            .push(hash);
+        self.nodes.insert(
+            hash,
+            TreeNode {
+                header: Some(header),
+                state,
+                parent: Some(parent),
+                children: Vec::new(),
+                ticketed,
+                depth,
+                equivocated,
+                chain_equivocated: parent_equivocated || equivocated,
+            },
+        );
+
+        let previous = self.best;
+        let mut best_tainted = false;
+        for other in others {
+            best_tainted |= self.mark_equivocated(other);
+        }
+        if best_tainted {
+            self.best = self.select_best();
+        } else if !self.nodes[&hash].chain_equivocated && self.is_better(&hash, &self.best) {
+            self.best = hash;
+        }
+        previous != self.best
+    }
+
+    /// Flag `hash` as equivocated and its descendants as built on an equivocation.
+    ///
+    /// Returns whether the best head was among them.
+    fn mark_equivocated(&mut self, hash: OpaqueHash) -> bool {
+        let Some(node) = self.nodes.get_mut(&hash) else {
+            return false;
+        };
+        node.equivocated = true;
+        let mut tainted_best = false;
+        let mut pending = vec![hash];
+        while let Some(hash) = pending.pop() {
+            let node = self.nodes.get_mut(&hash).expect("descendant in tree");
+            node.chain_equivocated = true;
+            pending.extend(node.children.iter().copied());
+            tainted_best |= hash == self.best;
+        }
+        tainted_best
+    }
+
+    /// Whether `a` beats `b` under the fork-choice ordering; ties go to the lowest hash
+    /// so the choice is deterministic.
+    fn is_better(&self, a: &OpaqueHash, b: &OpaqueHash) -> bool {
+        (self.nodes[a].score(), std::cmp::Reverse(a))
+            > (self.nodes[b].score(), std::cmp::Reverse(b))
+    }
+
+    /// Pick the best head among all blocks with an equivocation-free chain.
+    ///
+    /// Ordering: most ticket-sealed ancestors, then highest slot, then greatest depth;
+    /// remaining ties are broken by the lowest hash.
+    fn select_best(&self) -> OpaqueHash {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| !node.chain_equivocated)
+            .max_by(|(a_hash, a), (b_hash, b)| {
+                a.score().cmp(&b.score()).then_with(|| b_hash.cmp(a_hash))
+            })
+            .map(|(hash, _)| *hash)
+            .unwrap_or(self.root)
+    }
+
+    /// Ancestor of `hash` `distance` blocks up, or the root if the tree is shallower.
+    pub fn ancestor(&self, mut hash: OpaqueHash, distance: u64) -> OpaqueHash {
+        for _ in 0..distance {
+            match self.nodes.get(&hash).and_then(|node| node.parent) {
+                Some(parent) => hash = parent,
+                None => break,
+            }
+        }
+        hash
+    }
+
+    /// Whether `ancestor` is `hash` or one of its ancestors.
+    pub fn is_ancestor(&self, ancestor: &OpaqueHash, mut hash: OpaqueHash) -> bool {
+        loop {
+            if hash == *ancestor {
+                return true;
+            }
+            match self.nodes.get(&hash).and_then(|node| node.parent) {
+                Some(parent) => hash = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Re-root the tree at `new_root`, pruning every block that does not descend from it.
+    ///
+    /// The ancestors of `new_root` leave the tree with their states but stay part of the
+    /// finalized chain. Returns the hashes of the pruned blocks on competing branches only.
+    pub fn prune_to(&mut self, new_root: OpaqueHash) -> Vec<OpaqueHash> {
+        if !self.nodes.contains_key(&new_root) {
+            return Vec::new();
+        }
+
+        let mut finalized = Vec::new();
+        let mut hash = new_root;
+        while let Some(parent) = self.nodes[&hash].parent {
+            finalized.push(parent);
+            hash = parent;
+        }
+        for hash in &finalized {
+            self.nodes.remove(hash);
+        }
+
+        // Re-base the descendants of the new root; a finalized chain is never tainted by
+        // an equivocation below it.
+        let root = self.nodes.get_mut(&new_root).expect("checked above");
+        root.parent = None;
+        root.chain_equivocated = false;
+        let (base_ticketed, base_depth) = (root.ticketed, root.depth);
+        let mut kept = HashSet::new();
+        let mut pending = vec![(new_root, false)];
+        while let Some((hash, parent_equivocated)) = pending.pop() {
+            kept.insert(hash);
+            let node = self.nodes.get_mut(&hash).expect("descendant in tree");
+            node.ticketed -= base_ticketed;
+            node.depth -= base_depth;
+            if hash != new_root {
+                node.chain_equivocated = parent_equivocated || node.equivocated;
+            }
+            let chain_equivocated = node.chain_equivocated;
+            pending.extend(
+                node.children
+                    .iter()
+                    .map(|child| (*child, chain_equivocated)),
+            );
+        }
+        let pruned: Vec<OpaqueHash> = self
+            .nodes
+            .keys()
+            .filter(|hash| !kept.contains(*hash))
+            .copied()
+            .collect();
+        for hash in &pruned {
+            self.nodes.remove(hash);
+        }
+        let nodes = &self.nodes;
+        self.authored.retain(|_, hashes| {
+            hashes.retain(|hash| nodes.contains_key(hash));
+            !hashes.is_empty()
+        });
+
+        self.root = new_root;
+        self.best = self.select_best();
+        debug!(
+            "Pruned {} fork blocks and released {} finalized ancestors of new root {}",
+            pruned.len(),
+            finalized.len(),
+            hex::encode(new_root.as_bytes())
+        );
+        pruned
+    }
+}
@@ -0,0 +1,150 @@
+//! Bulk import of block sequences.
+//!
+//! [`Importer::import_chain`](crate::Importer::import_chain) reads blocks from a
+//! [`ChainSource`]: a directory of block files imported in file name order, a single JSON
+//! block, or a binary file holding any number of concatenated binary-encoded blocks (the
+//! form produced by `jamliquor encode`, appended back to back).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::codec::{malformed_block, Codec, Decode, BINARY_EXTENSION};
+use crate::schema::{Block, BlockchainError};
+use crate::stf::PhaseTimings;
+
+/// Blocks a chain import leaves unfinalized below the best head. Older ancestors are
+/// finalized as the import goes, which bounds the block tree and the states it holds.
+pub const IMPORT_FINALITY_LAG: u64 = 64;
+
+/// What to do when a block in the chain fails to decode or validate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidBlockPolicy {
+    /// Stop at the first invalid block.
+    #[default]
+    Stop,
+    /// Record the block and carry on with the next one. Descendants of a skipped block
+    /// no longer link to the chain and are skipped too.
+    Skip,
+}
+
+/// Ordered blocks to import.
+///
+/// Memory Usage:
+/// - Directory: one path per block file; blocks are read one at a time
+/// - Binary stream: the whole file, decoded one block at a time
+#[derive(Debug)]
+pub enum ChainSource {
+    Files(Vec<PathBuf>),
+    Stream { path: PathBuf, bytes: Vec<u8> },
+}
+
+impl ChainSource {
+    /// Open a directory of `.json`/`.jlb` block files, a JSON block file, or a binary
+    /// block stream.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut files = Vec::new();
+            for entry in
+                fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))?
+            {
+                let file = entry?.path();
+                if file
+                    .extension()
+                    .is_some_and(|ext| ext == "json" || ext == BINARY_EXTENSION)
+                {
+                    files.push(file);
+                }
+            }
+            files.sort();
+            return Ok(ChainSource::Files(files));
+        }
+        if Codec::from_path(path) == Codec::Json {
+            return Ok(ChainSource::Files(vec![path.to_path_buf()]));
+        }
+        let bytes = fs::read(path)
+            .map_err(BlockchainError::IoError)
+            .with_context(|| format!("Failed to read block stream {}", path.display()))?;
+        Ok(ChainSource::Stream {
+            path: path.to_path_buf(),
+            bytes,
+        })
+    }
+
+    /// Iterate over the blocks, each labelled with where it was read from.
+    ///
+    /// A block that fails to decode is yielded as an error; a stream cannot be resumed
+    /// after a decoding error, so iteration ends there.
+    pub fn blocks(&self) -> Box<dyn Iterator<Item = (String, Result<Block>)> + '_> {
+        match self {
+            ChainSource::Files(files) => Box::new(files.iter().map(|file| {
+                let block = fs::read(file)
+                    .map_err(BlockchainError::IoError)
+                    .and_then(|bytes| Codec::from_path(file).decode_block(&bytes))
+                    .with_context(|| format!("Failed to decode block from {}", file.display()));
+                (file.display().to_string(), block)
+            })),
+            ChainSource::Stream { path, bytes } => {
+                let mut input = bytes.as_slice();
+                let mut failed = false;
+                Box::new(std::iter::from_fn(move || {
+                    if input.is_empty() || failed {
+                        return None;
+                    }
+                    let label = format!("{}@{}", path.display(), bytes.len() - input.len());
+                    let block = Block::decode(&mut input)
+                        .map_err(malformed_block)
+                        .with_context(|| format!("Failed to decode block at {label}"));
+                    failed = block.is_err();
+                    Some((label, block))
+                }))
+            }
+        }
+    }
+}
+
+/// A block the chain import rejected.
+#[derive(Debug)]
+pub struct RejectedBlock {
+    /// File, or `file@offset` within a stream.
+    pub source: String,
+    pub slot: Option<u32>,
+    pub error: anyhow::Error,
+}
+
+/// Outcome of [`Importer::import_chain`](crate::Importer::import_chain).
+#[derive(Debug, Default)]
+pub struct ChainImportReport {
+    pub imported: usize,
+    /// Slot of the last imported block.
+    pub last_slot: Option<u32>,
+    /// Blocks skipped under [`InvalidBlockPolicy::Skip`], or the block the import stopped
+    /// at under [`InvalidBlockPolicy::Stop`].
+    pub rejected: Vec<RejectedBlock>,
+    pub elapsed: Duration,
+    /// Time spent in each STF phase over all imported blocks.
+    pub phases: PhaseTimings,
+}
+
+impl ChainImportReport {
+    /// Import throughput over the whole run.
+    pub fn blocks_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.imported as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Whether an import error is a verdict on the block rather than a failure of the node.
+pub(crate) fn is_invalid_block(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+        .is_some_and(BlockchainError::is_invalid_block)
+}
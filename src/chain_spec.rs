@@ -0,0 +1,71 @@
+//! Protocol parameters for the JAM chain configurations used by the test vectors.
+
+/// Chain-wide constants that parameterise validation (GP Appendix I.4).
+///
+/// Memory Usage:
+/// - Fixed: ~50 bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSpec {
+    /// Human readable configuration name (`tiny` or `full`).
+    pub name: &'static str,
+    /// Total number of validators `V`.
+    pub validators_count: u16,
+    /// Total number of cores `C`.
+    pub cores_count: u16,
+    /// Slots per epoch `E`.
+    pub epoch_length: u32,
+    /// Slot after which tickets are no longer accepted within an epoch `Y`.
+    pub ticket_submission_end: u32,
+    /// Maximum tickets per extrinsic `K`.
+    pub max_tickets_per_extrinsic: u16,
+    /// Octets per erasure-coded piece `W_E`: two per original chunk.
+    pub erasure_piece_size: u16,
+}
+
+impl ChainSpec {
+    /// The `tiny` configuration used by the reduced test vectors.
+    pub fn tiny() -> Self {
+        ChainSpec {
+            name: "tiny",
+            validators_count: 6,
+            cores_count: 2,
+            epoch_length: 12,
+            ticket_submission_end: 10,
+            max_tickets_per_extrinsic: 3,
+            erasure_piece_size: 4,
+        }
+    }
+
+    /// The `full` configuration matching production parameters.
+    pub fn full() -> Self {
+        ChainSpec {
+            name: "full",
+            validators_count: 1023,
+            cores_count: 341,
+            epoch_length: 600,
+            ticket_submission_end: 500,
+            max_tickets_per_extrinsic: 16,
+            erasure_piece_size: 684,
+        }
+    }
+
+    /// Look up a configuration by name.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "tiny" => Some(Self::tiny()),
+            "full" => Some(Self::full()),
+            _ => None,
+        }
+    }
+
+    /// Epoch index containing `slot`.
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / u64::from(self.epoch_length)
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self::full()
+    }
+}
@@ -0,0 +1,533 @@
+//! Binary block codec and codec selection for block import.
+//!
+//! The binary form is a JamLiquor-internal format, not the Gray Paper codec. It
+//! borrows the JAM codec conventions (Appendix C): fixed-width integers are
+//! little-endian, sequences carry a compact natural-number length prefix and
+//! optional values a single discriminator byte. Extrinsic sections that are still
+//! untyped in [`crate::schema`] (guarantees, assurances, disputes) are carried as
+//! length-prefixed JSON, so the encoding of any block carrying them differs from
+//! the jam-test-vectors `*.bin` files. Those files are not read with this codec.
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::coretime::CoreTimeLedger;
+use crate::error_code::ErrorCode;
+use crate::schema::{
+    Block, BlockchainError, EpochMark, Extrinsic, Header, OpaqueHash, Preimage, State, TicketBody,
+    TicketEnvelope, TicketState,
+};
+
+/// Wire format used to decode or encode a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// JSON as used by the jam-test-vectors `*.json` files.
+    #[default]
+    Json,
+    /// JamLiquor's internal binary format (see the module docs), stored as `*.jlb`.
+    Binary,
+}
+
+/// File extension of blocks and block streams in the internal binary format.
+pub const BINARY_EXTENSION: &str = "jlb";
+
+impl Codec {
+    /// Pick a codec from a file extension: `.jlb` is binary, everything else JSON.
+    ///
+    /// GP-encoded `.bin` files are deliberately not mapped to [`Codec::Binary`].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(BINARY_EXTENSION) => Codec::Binary,
+            _ => Codec::Json,
+        }
+    }
+
+    /// Decode a block from an in-memory buffer.
+    ///
+    /// Input that does not decode is rejected as [`ErrorCode::Malformed`].
+    pub fn decode_block(self, bytes: &[u8]) -> Result<Block, BlockchainError> {
+        match self {
+            Codec::Json => serde_json::from_slice(bytes).map_err(BlockchainError::from),
+            Codec::Binary => decode_exact(bytes),
+        }
+        .map_err(malformed_block)
+    }
+
+    /// Decode a block from an arbitrary reader.
+    ///
+    /// JSON is parsed in streaming fashion; binary input is buffered first since
+    /// the codec needs random access to the length prefixes.
+    pub fn read_block<R: Read>(self, mut reader: R) -> Result<Block, BlockchainError> {
+        match self {
+            Codec::Json => serde_json::from_reader(reader).map_err(|e| {
+                if e.is_io() {
+                    BlockchainError::IoError(e.into())
+                } else {
+                    malformed_block(e.into())
+                }
+            }),
+            Codec::Binary => {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                decode_exact(&bytes).map_err(malformed_block)
+            }
+        }
+    }
+
+    /// Encode a block into this wire format.
+    pub fn encode_block(self, block: &Block) -> Result<Vec<u8>, BlockchainError> {
+        match self {
+            Codec::Json => Ok(serde_json::to_vec(block)?),
+            Codec::Binary => Ok(block.encode()),
+        }
+    }
+}
+
+/// Turn a failure to decode a block into a [`ErrorCode::Malformed`] rejection, leaving
+/// other errors (such as I/O) as they are.
+pub(crate) fn malformed_block(error: BlockchainError) -> BlockchainError {
+    match error {
+        BlockchainError::CodecError { .. } | BlockchainError::JsonError(_) => {
+            BlockchainError::rejected(ErrorCode::Malformed, None, error.to_string())
+        }
+        other => other,
+    }
+}
+
+/// Decode a value and reject any trailing input.
+pub fn decode_exact<T: Decode>(bytes: &[u8]) -> Result<T, BlockchainError> {
+    let mut input = bytes;
+    let value = T::decode(&mut input)?;
+    if !input.is_empty() {
+        return Err(BlockchainError::CodecError {
+            reason: format!("{} trailing bytes after value", input.len()),
+        });
+    }
+    Ok(value)
+}
+
+/// Types that can be written in the JAM binary codec.
+pub trait Encode {
+    /// Append the encoding of `self` to `out`.
+    fn encode_to(&self, out: &mut Vec<u8>);
+
+    /// Encode `self` into a fresh buffer.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+}
+
+/// Types that can be read from the JAM binary codec.
+pub trait Decode: Sized {
+    /// Decode a value from the front of `input`, advancing it past the consumed bytes.
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError>;
+}
+
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], BlockchainError> {
+    if input.len() < len {
+        return Err(BlockchainError::CodecError {
+            reason: format!(
+                "unexpected end of input: need {len} bytes, have {}",
+                input.len()
+            ),
+        });
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+/// Append the GP compact encoding of a natural number (Appendix C.2).
+pub fn encode_natural(value: u64, out: &mut Vec<u8>) {
+    if value == 0 {
+        out.push(0);
+        return;
+    }
+    for l in 0..8u32 {
+        if value < 1u64 << (7 * (l + 1)) {
+            let prefix = (256 - (1u16 << (8 - l))) as u8 + (value >> (8 * l)) as u8;
+            out.push(prefix);
+            out.extend_from_slice(&value.to_le_bytes()[..l as usize]);
+            return;
+        }
+    }
+    out.push(0xff);
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Read a GP compact natural number.
+pub fn decode_natural(input: &mut &[u8]) -> Result<u64, BlockchainError> {
+    let prefix = take(input, 1)?[0];
+    let l = prefix.leading_ones() as usize;
+    if l == 8 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(take(input, 8)?);
+        return Ok(u64::from_le_bytes(bytes));
+    }
+    let mut bytes = [0u8; 8];
+    bytes[..l].copy_from_slice(take(input, l)?);
+    let low = u64::from_le_bytes(bytes);
+    let high = u64::from(prefix & (0xffu16 >> (l + 1)) as u8);
+    Ok(low | (high << (8 * l)))
+}
+
+pub(crate) fn decode_len(input: &mut &[u8]) -> Result<usize, BlockchainError> {
+    let len = decode_natural(input)?;
+    // A length can never exceed the remaining input, which also bounds allocations.
+    if len > input.len() as u64 {
+        return Err(BlockchainError::CodecError {
+            reason: format!(
+                "length prefix {len} exceeds remaining {} bytes",
+                input.len()
+            ),
+        });
+    }
+    Ok(len as usize)
+}
+
+macro_rules! impl_fixed_int {
+    ($($ty:ty),*) => {$(
+        impl Encode for $ty {
+            fn encode_to(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+                let mut bytes = [0u8; std::mem::size_of::<$ty>()];
+                bytes.copy_from_slice(take(input, std::mem::size_of::<$ty>())?);
+                Ok(<$ty>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+impl_fixed_int!(u8, u16, u32, u64);
+
+impl Encode for bool {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(BlockchainError::CodecError {
+                reason: format!("invalid boolean {other}"),
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Encode for [u8; N] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<const N: usize> Decode for [u8; N] {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(take(input, N)?);
+        Ok(bytes)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_option(self, out);
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        decode_option(input)
+    }
+}
+
+impl Encode for OpaqueHash {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for OpaqueHash {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(take(input, 32)?);
+        Ok(OpaqueHash::new(bytes))
+    }
+}
+
+/// Length-prefixed octet sequence.
+impl Encode for Vec<u8> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_natural(self.len() as u64, out);
+        out.extend_from_slice(self);
+    }
+}
+
+impl Decode for Vec<u8> {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let len = decode_len(input)?;
+        Ok(take(input, len)?.to_vec())
+    }
+}
+
+pub(crate) fn encode_seq<T: Encode>(items: &[T], out: &mut Vec<u8>) {
+    encode_natural(items.len() as u64, out);
+    for item in items {
+        item.encode_to(out);
+    }
+}
+
+pub(crate) fn decode_seq<T: Decode>(input: &mut &[u8]) -> Result<Vec<T>, BlockchainError> {
+    let len = decode_len(input)?;
+    (0..len).map(|_| T::decode(input)).collect()
+}
+
+pub(crate) fn encode_option<T: Encode>(value: &Option<T>, out: &mut Vec<u8>) {
+    match value {
+        None => out.push(0),
+        Some(inner) => {
+            out.push(1);
+            inner.encode_to(out);
+        }
+    }
+}
+
+pub(crate) fn decode_option<T: Decode>(input: &mut &[u8]) -> Result<Option<T>, BlockchainError> {
+    match u8::decode(input)? {
+        0 => Ok(None),
+        1 => Ok(Some(T::decode(input)?)),
+        other => Err(BlockchainError::CodecError {
+            reason: format!("invalid option discriminator {other}"),
+        }),
+    }
+}
+
+/// Untyped extrinsic sections are carried as length-prefixed JSON text.
+impl Encode for serde_json::Value {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.to_string().into_bytes().encode_to(out);
+    }
+}
+
+impl Decode for serde_json::Value {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let bytes = Vec::<u8>::decode(input)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+impl Encode for EpochMark {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.entropy.encode_to(out);
+        self.tickets_entropy.encode_to(out);
+        encode_seq(&self.validators, out);
+    }
+}
+
+impl Decode for EpochMark {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(EpochMark {
+            entropy: OpaqueHash::decode(input)?,
+            tickets_entropy: OpaqueHash::decode(input)?,
+            validators: decode_seq(input)?,
+        })
+    }
+}
+
+impl Encode for TicketBody {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.id.encode_to(out);
+        self.attempt.encode_to(out);
+    }
+}
+
+impl Decode for TicketBody {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(TicketBody {
+            id: OpaqueHash::decode(input)?,
+            attempt: u8::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Vec<TicketBody> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_seq(self, out);
+    }
+}
+
+impl Decode for Vec<TicketBody> {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        decode_seq(input)
+    }
+}
+
+impl Encode for Header {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.parent.encode_to(out);
+        self.parent_state_root.encode_to(out);
+        self.extrinsic_hash.encode_to(out);
+        self.slot.encode_to(out);
+        encode_option(&self.epoch_mark, out);
+        encode_option(&self.tickets_mark, out);
+        encode_seq(&self.offenders_mark, out);
+        self.author_index.encode_to(out);
+        self.entropy_source.encode_to(out);
+        self.seal.encode_to(out);
+    }
+}
+
+impl Decode for Header {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Header {
+            parent: OpaqueHash::decode(input)?,
+            parent_state_root: OpaqueHash::decode(input)?,
+            extrinsic_hash: OpaqueHash::decode(input)?,
+            slot: u32::decode(input)?,
+            epoch_mark: decode_option(input)?,
+            tickets_mark: decode_option(input)?,
+            offenders_mark: decode_seq(input)?,
+            author_index: u16::decode(input)?,
+            entropy_source: Vec::<u8>::decode(input)?,
+            seal: Vec::<u8>::decode(input)?,
+        })
+    }
+}
+
+impl Encode for TicketEnvelope {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.attempt.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl Decode for TicketEnvelope {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(TicketEnvelope {
+            attempt: u8::decode(input)?,
+            signature: Vec::<u8>::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Preimage {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.requester.encode_to(out);
+        self.blob.encode_to(out);
+    }
+}
+
+impl Decode for Preimage {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Preimage {
+            requester: u32::decode(input)?,
+            blob: Vec::<u8>::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Extrinsic {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_seq(&self.tickets, out);
+        encode_seq(&self.preimages, out);
+        encode_seq(&self.guarantees, out);
+        encode_seq(&self.assurances, out);
+        self.disputes.encode_to(out);
+    }
+}
+
+impl Decode for Extrinsic {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Extrinsic {
+            tickets: decode_seq(input)?,
+            preimages: decode_seq(input)?,
+            guarantees: decode_seq(input)?,
+            assurances: decode_seq(input)?,
+            disputes: serde_json::Value::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Block {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.extrinsic.encode_to(out);
+    }
+}
+
+impl Decode for Block {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Block {
+            header: Header::decode(input)?,
+            extrinsic: Extrinsic::decode(input)?,
+        })
+    }
+}
+
+impl Encode for TicketState {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.total_tickets.encode_to(out);
+        self.valid_tickets.encode_to(out);
+        self.invalid_tickets.encode_to(out);
+        self.last_ticket_id.encode_to(out);
+        self.sealed_by_tickets.encode_to(out);
+        self.next_sealed_by_tickets.encode_to(out);
+        self.entropy.encode_to(out);
+        encode_seq(&self.sealing_tickets, out);
+        encode_seq(&self.next_tickets, out);
+    }
+}
+
+impl Decode for TicketState {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(TicketState {
+            total_tickets: u64::decode(input)?,
+            valid_tickets: u64::decode(input)?,
+            invalid_tickets: u64::decode(input)?,
+            last_ticket_id: Option::decode(input)?,
+            sealed_by_tickets: bool::decode(input)?,
+            next_sealed_by_tickets: bool::decode(input)?,
+            entropy: OpaqueHash::decode(input)?,
+            sealing_tickets: decode_seq(input)?,
+            next_tickets: decode_seq(input)?,
+        })
+    }
+}
+
+impl Encode for State {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.last_slot.encode_to(out);
+        self.counter.encode_to(out);
+        self.ticket_state.encode_to(out);
+        self.last_block_hash.encode_to(out);
+        self.last_state_root.encode_to(out);
+        self.coretime.encode_to(out);
+        encode_seq(&self.active_validators, out);
+        encode_seq(&self.pending_validators, out);
+    }
+}
+
+impl Decode for State {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(State {
+            last_slot: u64::decode(input)?,
+            counter: u64::decode(input)?,
+            ticket_state: TicketState::decode(input)?,
+            last_block_hash: Option::decode(input)?,
+            last_state_root: Option::decode(input)?,
+            coretime: CoreTimeLedger::decode(input)?,
+            active_validators: decode_seq(input)?,
+            pending_validators: decode_seq(input)?,
+        })
+    }
+}
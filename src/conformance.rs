@@ -0,0 +1,505 @@
+//! Internal fuzzing harness modelled on the JAM fuzzer protocol (`TODO.md` 4.1).
+//!
+//! This is not wire-compatible with the JAM conformance fuzzer, and results from it are
+//! not conformance results:
+//! - headers and blocks in a [`Message`] use JamLiquor's internal codec
+//!   ([`crate::codec`]), not the GP encoding;
+//! - `Initialize.ancestry` is decoded but ignored;
+//! - state roots after the initial state come from JamLiquor's own state model (see
+//!   [`Genesis::from_key_values`]), so they never match a GP implementation's.
+//!
+//! A client connects to the target over a Unix domain socket and exchanges messages, each
+//! framed as `len: u32 LE | payload`. The session starts with a [`PeerInfo`] handshake;
+//! the client then initializes the state from raw key/values, imports blocks and reads
+//! back state roots and full states:
+//!
+//! | Request               | Response                    |
+//! |-----------------------|-----------------------------|
+//! | `PeerInfo`            | `PeerInfo`                  |
+//! | `Initialize`          | `StateRoot` of the state    |
+//! | `ImportBlock`         | `StateRoot` or `Error`      |
+//! | `GetState`            | `State` or `Error`          |
+//!
+//! [`Target`] holds the protocol state and is independent of the transport; [`Fuzzer`] is
+//! a minimal client used to drive a target locally.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+
+use crate::chain_spec::ChainSpec;
+use crate::codec::{decode_exact, decode_seq, encode_seq, take, Decode, Encode};
+use crate::codec::{decode_natural, encode_natural};
+use crate::genesis::Genesis;
+use crate::merkle::{KeyValues, StateKey};
+use crate::schema::{Block, BlockchainError, Header, OpaqueHash};
+use crate::Importer;
+
+/// Fuzzer protocol version spoken by the target.
+pub const FUZZ_PROTOCOL_VERSION: u8 = 1;
+/// Gray Paper version the state model follows.
+pub const JAM_VERSION: Version = Version {
+    major: 0,
+    minor: 7,
+    patch: 0,
+};
+/// Upper bound on a single message, so a corrupt length prefix cannot exhaust memory.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// Semantic version as carried in [`PeerInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+/// Handshake message identifying a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub fuzz_version: u8,
+    /// Optional protocol features, as a bit set.
+    pub features: u32,
+    pub jam_version: Version,
+    pub app_version: Version,
+    pub name: String,
+}
+
+impl PeerInfo {
+    /// This implementation's peer info.
+    pub fn jamliquor() -> Self {
+        let part = |s: &str| s.parse().unwrap_or(0);
+        PeerInfo {
+            fuzz_version: FUZZ_PROTOCOL_VERSION,
+            features: 0,
+            jam_version: JAM_VERSION,
+            app_version: Version {
+                major: part(env!("CARGO_PKG_VERSION_MAJOR")),
+                minor: part(env!("CARGO_PKG_VERSION_MINOR")),
+                patch: part(env!("CARGO_PKG_VERSION_PATCH")),
+            },
+            name: env!("CARGO_PKG_NAME").to_string(),
+        }
+    }
+}
+
+/// A fuzzer protocol message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    PeerInfo(PeerInfo),
+    /// Reset the target to `header` and the state given as raw key/values. `ancestry`
+    /// lists `(slot, hash)` of recent blocks preceding the header; the target ignores it.
+    Initialize {
+        header: Header,
+        key_values: KeyValues,
+        ancestry: Vec<(u32, OpaqueHash)>,
+    },
+    StateRoot([u8; 32]),
+    ImportBlock(Block),
+    /// Request the posterior state of the block with this header hash.
+    GetState(OpaqueHash),
+    State(KeyValues),
+    Error(String),
+}
+
+impl Message {
+    /// Name of the message kind, for logging.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::PeerInfo(_) => "PeerInfo",
+            Message::Initialize { .. } => "Initialize",
+            Message::StateRoot(_) => "StateRoot",
+            Message::ImportBlock(_) => "ImportBlock",
+            Message::GetState(_) => "GetState",
+            Message::State(_) => "State",
+            Message::Error(_) => "Error",
+        }
+    }
+}
+
+/// Read one framed message, or `None` if the peer closed the connection.
+pub fn read_message<R: Read>(reader: &mut R) -> Result<Option<Message>, BlockchainError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(BlockchainError::CodecError {
+            reason: format!("message of {len} bytes exceeds {MAX_MESSAGE_LEN}"),
+        });
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    decode_exact(&payload).map(Some)
+}
+
+/// Write one framed message.
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> Result<(), BlockchainError> {
+    let payload = message.encode();
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Protocol state of a conformance target.
+///
+/// Memory Usage:
+/// - Fixed: ~200 bytes (spec, peer info)
+/// - Dynamic: the importer's block tree and the initial key/values
+#[derive(Debug)]
+pub struct Target {
+    spec: ChainSpec,
+    importer: Option<Importer>,
+    /// Hash and key/values the target was initialized with. Decoding GP key/values keeps
+    /// only the modelled components, so the originals are served for the initial state.
+    initial: Option<(OpaqueHash, KeyValues)>,
+}
+
+impl Target {
+    pub fn new(spec: ChainSpec) -> Self {
+        Target {
+            spec,
+            importer: None,
+            initial: None,
+        }
+    }
+
+    /// The importer, once the target has been initialized.
+    pub fn importer(&self) -> Option<&Importer> {
+        self.importer.as_ref()
+    }
+
+    /// Answer a single request.
+    pub fn handle(&mut self, request: Message) -> Message {
+        match request {
+            Message::PeerInfo(peer) => {
+                info!(
+                    "Fuzzer `{}` connected (protocol v{}, JAM {}.{}.{})",
+                    peer.name,
+                    peer.fuzz_version,
+                    peer.jam_version.major,
+                    peer.jam_version.minor,
+                    peer.jam_version.patch
+                );
+                Message::PeerInfo(PeerInfo::jamliquor())
+            }
+            Message::Initialize {
+                header,
+                key_values,
+                ancestry,
+            } => self.initialize(header, key_values, &ancestry),
+            Message::ImportBlock(block) => self.import_block(block),
+            Message::GetState(hash) => self.get_state(&hash),
+            other => Message::Error(format!("unexpected {} request", other.kind())),
+        }
+    }
+
+    fn initialize(
+        &mut self,
+        header: Header,
+        key_values: KeyValues,
+        ancestry: &[(u32, OpaqueHash)],
+    ) -> Message {
+        let genesis = match Genesis::from_key_values(header, key_values, &self.spec) {
+            Ok(genesis) => genesis,
+            Err(e) => return Message::Error(format!("invalid initial state: {e}")),
+        };
+        debug!(
+            "Initializing at slot {} ({} ancestors ignored)",
+            genesis.header.slot,
+            ancestry.len()
+        );
+        self.importer = Some(Importer::from_genesis(self.spec.clone(), &genesis));
+        self.initial = Some((genesis.hash(), genesis.key_values));
+        Message::StateRoot(genesis.state_root)
+    }
+
+    fn import_block(&mut self, block: Block) -> Message {
+        let Some(importer) = self.importer.as_mut() else {
+            return Message::Error("target is not initialized".to_string());
+        };
+        let hash = block.header.hash();
+        if let Err(e) = importer.import_decoded(block) {
+            return Message::Error(format!("{e:#}"));
+        }
+        match importer
+            .tree()
+            .get(&hash)
+            .and_then(|node| node.state.last_state_root)
+        {
+            Some(root) => Message::StateRoot(root),
+            None => Message::Error("imported block has no state root".to_string()),
+        }
+    }
+
+    fn get_state(&self, hash: &OpaqueHash) -> Message {
+        if let Some((initial, key_values)) = &self.initial {
+            if initial == hash {
+                return Message::State(key_values.clone());
+            }
+        }
+        match self
+            .importer
+            .as_ref()
+            .and_then(|importer| importer.tree().get(hash))
+        {
+            Some(node) => Message::State(node.state.to_key_values()),
+            None => Message::Error(format!("unknown block {}", hex::encode(hash.as_bytes()))),
+        }
+    }
+
+    /// Serve one fuzzer session until the peer disconnects.
+    ///
+    /// The first message must be the peer-info handshake.
+    pub fn serve<S: Read + Write>(&mut self, stream: &mut S) -> Result<()> {
+        match read_message(stream)? {
+            Some(Message::PeerInfo(peer)) => {
+                let reply = self.handle(Message::PeerInfo(peer));
+                write_message(stream, &reply)?;
+            }
+            Some(other) => {
+                let error = format!("expected PeerInfo handshake, got {}", other.kind());
+                write_message(stream, &Message::Error(error.clone()))?;
+                bail!(error);
+            }
+            None => return Ok(()),
+        }
+
+        while let Some(request) = read_message(stream)? {
+            debug!("Received {}", request.kind());
+            let response = self.handle(request);
+            if let Message::Error(reason) = &response {
+                warn!("Request failed: {reason}");
+            }
+            write_message(stream, &response)?;
+        }
+        debug!("Fuzzer disconnected");
+        Ok(())
+    }
+}
+
+/// Listen on a Unix domain socket and serve fuzzer sessions one after another.
+///
+/// Each session starts from an uninitialized target. A stale socket file is replaced.
+#[cfg(unix)]
+pub fn listen<P: AsRef<Path>>(socket: P, spec: ChainSpec) -> Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    let socket = socket.as_ref();
+    if socket.exists() {
+        std::fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to bind {}", socket.display()))?;
+    info!("Fuzzing harness listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let mut stream = stream.context("Failed to accept fuzzer connection")?;
+        let mut target = Target::new(spec.clone());
+        if let Err(e) = target.serve(&mut stream) {
+            warn!("Fuzzer session ended with error: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+/// Minimal harness client, for driving a target locally.
+#[derive(Debug)]
+pub struct Fuzzer<S> {
+    stream: S,
+}
+
+impl<S: Read + Write> Fuzzer<S> {
+    pub fn new(stream: S) -> Self {
+        Fuzzer { stream }
+    }
+
+    /// Send a request and wait for the response.
+    pub fn request(&mut self, message: &Message) -> Result<Message> {
+        write_message(&mut self.stream, message)?;
+        read_message(&mut self.stream)?.context("target closed the connection")
+    }
+
+    /// Exchange peer info and return the target's.
+    pub fn handshake(&mut self, name: &str) -> Result<PeerInfo> {
+        let mut info = PeerInfo::jamliquor();
+        info.name = name.to_string();
+        match self.request(&Message::PeerInfo(info))? {
+            Message::PeerInfo(peer) => Ok(peer),
+            other => bail!("expected PeerInfo, got {other:?}"),
+        }
+    }
+
+    pub fn initialize(&mut self, header: &Header, key_values: &KeyValues) -> Result<[u8; 32]> {
+        self.expect_root(Message::Initialize {
+            header: header.clone(),
+            key_values: key_values.clone(),
+            ancestry: Vec::new(),
+        })
+    }
+
+    pub fn import_block(&mut self, block: &Block) -> Result<[u8; 32]> {
+        self.expect_root(Message::ImportBlock(block.clone()))
+    }
+
+    pub fn get_state(&mut self, hash: &OpaqueHash) -> Result<KeyValues> {
+        match self.request(&Message::GetState(*hash))? {
+            Message::State(key_values) => Ok(key_values),
+            Message::Error(reason) => bail!("target error: {reason}"),
+            other => bail!("expected State, got {}", other.kind()),
+        }
+    }
+
+    fn expect_root(&mut self, request: Message) -> Result<[u8; 32]> {
+        match self.request(&request)? {
+            Message::StateRoot(root) => Ok(root),
+            Message::Error(reason) => bail!("target error: {reason}"),
+            other => bail!("expected StateRoot, got {}", other.kind()),
+        }
+    }
+}
+
+impl Encode for Version {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&[self.major, self.minor, self.patch]);
+    }
+}
+
+impl Decode for Version {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let bytes = take(input, 3)?;
+        Ok(Version {
+            major: bytes[0],
+            minor: bytes[1],
+            patch: bytes[2],
+        })
+    }
+}
+
+impl Encode for PeerInfo {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.fuzz_version.encode_to(out);
+        self.features.encode_to(out);
+        self.jam_version.encode_to(out);
+        self.app_version.encode_to(out);
+        self.name.as_bytes().to_vec().encode_to(out);
+    }
+}
+
+impl Decode for PeerInfo {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(PeerInfo {
+            fuzz_version: u8::decode(input)?,
+            features: u32::decode(input)?,
+            jam_version: Version::decode(input)?,
+            app_version: Version::decode(input)?,
+            name: decode_string(input)?,
+        })
+    }
+}
+
+fn decode_string(input: &mut &[u8]) -> Result<String, BlockchainError> {
+    String::from_utf8(Vec::<u8>::decode(input)?).map_err(|e| BlockchainError::CodecError {
+        reason: format!("string is not UTF-8: {e}"),
+    })
+}
+
+fn encode_key_values(key_values: &KeyValues, out: &mut Vec<u8>) {
+    encode_natural(key_values.len() as u64, out);
+    for (key, value) in key_values {
+        key.encode_to(out);
+        value.encode_to(out);
+    }
+}
+
+fn decode_key_values(input: &mut &[u8]) -> Result<KeyValues, BlockchainError> {
+    let count = decode_natural(input)?;
+    let mut key_values = KeyValues::new();
+    for _ in 0..count {
+        let key = StateKey::decode(input)?;
+        key_values.insert(key, Vec::<u8>::decode(input)?);
+    }
+    Ok(key_values)
+}
+
+impl Encode for (u32, OpaqueHash) {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+        self.1.encode_to(out);
+    }
+}
+
+impl Decode for (u32, OpaqueHash) {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok((u32::decode(input)?, OpaqueHash::decode(input)?))
+    }
+}
+
+impl Encode for Message {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Message::PeerInfo(info) => {
+                out.push(0);
+                info.encode_to(out);
+            }
+            Message::Initialize {
+                header,
+                key_values,
+                ancestry,
+            } => {
+                out.push(1);
+                header.encode_to(out);
+                encode_key_values(key_values, out);
+                encode_seq(ancestry, out);
+            }
+            Message::StateRoot(root) => {
+                out.push(2);
+                root.encode_to(out);
+            }
+            Message::ImportBlock(block) => {
+                out.push(3);
+                block.encode_to(out);
+            }
+            Message::GetState(hash) => {
+                out.push(4);
+                hash.encode_to(out);
+            }
+            Message::State(key_values) => {
+                out.push(5);
+                encode_key_values(key_values, out);
+            }
+            Message::Error(reason) => {
+                out.push(255);
+                reason.as_bytes().to_vec().encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Message {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        match u8::decode(input)? {
+            0 => Ok(Message::PeerInfo(PeerInfo::decode(input)?)),
+            1 => Ok(Message::Initialize {
+                header: Header::decode(input)?,
+                key_values: decode_key_values(input)?,
+                ancestry: decode_seq(input)?,
+            }),
+            2 => Ok(Message::StateRoot(<[u8; 32]>::decode(input)?)),
+            3 => Ok(Message::ImportBlock(Block::decode(input)?)),
+            4 => Ok(Message::GetState(OpaqueHash::decode(input)?)),
+            5 => Ok(Message::State(decode_key_values(input)?)),
+            255 => Ok(Message::Error(decode_string(input)?)),
+            tag => Err(BlockchainError::CodecError {
+                reason: format!("unknown fuzzer message {tag}"),
+            }),
+        }
+    }
+}
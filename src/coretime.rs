@@ -1,3 +1,5 @@
+use crate::codec::{decode_len, encode_natural, Decode, Encode};
+use crate::error_code::{ErrorCode, Subsystem};
 use crate::schema::BlockchainError;
 use serde::Deserialize;
 use serde_json::Value;
@@ -14,7 +16,7 @@ pub const MAX_DISPUTE_AGE: u64 = 64;
 /// Maximum difference between the current block slot and a guarantee slot.
 pub const MAX_GUARANTEE_LOOKBACK: u64 = 8;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 struct CoreUsage {
     total_consumed: u64,
     last_block_slot: u64,
@@ -22,7 +24,7 @@ struct CoreUsage {
 }
 
 /// Tracks CoreTime allocations and consumption over time.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct CoreTimeLedger {
     total_allocated: u64,
     total_consumed: u64,
@@ -31,6 +33,14 @@ pub struct CoreTimeLedger {
 }
 
 impl CoreTimeLedger {
+    /// An empty ledger whose chain starts at `slot`, e.g. the genesis slot.
+    pub fn starting_at(slot: u64) -> Self {
+        CoreTimeLedger {
+            last_block_slot: Some(slot),
+            ..Self::default()
+        }
+    }
+
     /// Total CoreTime allocated across all processed blocks.
     pub fn total_allocated(&self) -> u64 {
         self.total_allocated
@@ -53,6 +63,17 @@ impl CoreTimeLedger {
             .map(|usage| usage.total_consumed)
     }
 
+    /// CoreTime consumed by each core in the block at `slot`, if that block was the last
+    /// one applied.
+    pub fn block_consumption(&self, slot: u64) -> impl Iterator<Item = (u16, u64)> + '_ {
+        self.per_core_usage
+            .iter()
+            .filter(move |(_, usage)| {
+                usage.last_block_slot == slot && usage.last_block_consumed > 0
+            })
+            .map(|(core_index, usage)| (*core_index, usage.last_block_consumed))
+    }
+
     /// Validate and apply the CoreTime information contained within a block.
     pub fn validate_and_apply(
         &mut self,
@@ -70,40 +91,47 @@ impl CoreTimeLedger {
         let mut total_block_usage: u64 = 0;
         let mut unique_allocations: HashSet<(u64, u16)> = HashSet::new();
 
-        for value in guarantees {
+        for (i, value) in guarantees.iter().enumerate() {
             let guarantee: CoreTimeGuarantee =
-                serde_json::from_value(value.clone()).map_err(|e| {
-                    BlockchainError::CoreTimeValidationError {
-                        reason: format!("Invalid guarantee format: {e}"),
-                    }
+                serde_json::from_value(value.clone()).map_err(|e| BlockchainError::Rejected {
+                    subsystem: Subsystem::Guarantees,
+                    code: ErrorCode::Malformed,
+                    index: Some(i),
+                    reason: format!("Invalid guarantee format: {e}"),
                 })?;
 
             if guarantee.slot > block_slot {
-                return Err(BlockchainError::CoreTimeValidationError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::FutureReportSlot,
+                    Some(i),
+                    format!(
                         "Guarantee references future slot {} (current block slot {})",
                         guarantee.slot, block_slot
                     ),
-                });
+                ));
             }
 
             if block_slot - guarantee.slot > MAX_GUARANTEE_LOOKBACK {
-                return Err(BlockchainError::CoreTimeValidationError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::ReportEpochBeforeLast,
+                    Some(i),
+                    format!(
                         "Guarantee slot {} exceeds lookback window {}",
                         guarantee.slot, MAX_GUARANTEE_LOOKBACK
                     ),
-                });
+                ));
             }
 
             let allocation_key = (guarantee.slot, guarantee.report.core_index);
             if !unique_allocations.insert(allocation_key) {
-                return Err(BlockchainError::CoreTimeValidationError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::CoreEngaged,
+                    Some(i),
+                    format!(
                         "Duplicate guarantee for core {} at slot {}",
                         guarantee.report.core_index, guarantee.slot
                     ),
-                });
+                ));
             }
 
             let mut core_consumption = 0u64;
@@ -137,12 +165,14 @@ impl CoreTimeLedger {
             })?;
 
             if *entry > MAX_CORETIME_PER_CORE {
-                return Err(BlockchainError::CoreTimeBalanceError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::WorkReportGasTooHigh,
+                    Some(i),
+                    format!(
                         "Core {} exceeds per-block CoreTime limit ({} > {})",
                         guarantee.report.core_index, *entry, MAX_CORETIME_PER_CORE
                     ),
-                });
+                ));
             }
 
             total_block_usage =
@@ -154,70 +184,86 @@ impl CoreTimeLedger {
         }
 
         if total_block_usage > MAX_CORETIME_PER_BLOCK {
-            return Err(BlockchainError::CoreTimeBalanceError {
-                reason: format!(
+            return Err(BlockchainError::rejected(
+                ErrorCode::WorkReportGasTooHigh,
+                None,
+                format!(
                     "Block exceeds CoreTime limit ({} > {})",
                     total_block_usage, MAX_CORETIME_PER_BLOCK
                 ),
-            });
+            ));
         }
 
         if assurances.len() > MAX_ASSURANCES_PER_BLOCK {
-            return Err(BlockchainError::CoreTimeValidationError {
-                reason: format!("Too many assurances in block: {}", assurances.len()),
-            });
+            return Err(BlockchainError::rejected(
+                ErrorCode::TooManyAssurances,
+                None,
+                format!("Too many assurances in block: {}", assurances.len()),
+            ));
         }
 
         let mut seen_validators = HashSet::new();
-        for value in assurances {
+        for (i, value) in assurances.iter().enumerate() {
             let assurance: CoreTimeAssurance =
-                serde_json::from_value(value.clone()).map_err(|e| {
-                    BlockchainError::CoreTimeValidationError {
-                        reason: format!("Invalid assurance format: {e}"),
-                    }
+                serde_json::from_value(value.clone()).map_err(|e| BlockchainError::Rejected {
+                    subsystem: Subsystem::Assurances,
+                    code: ErrorCode::Malformed,
+                    index: Some(i),
+                    reason: format!("Invalid assurance format: {e}"),
                 })?;
 
             if assurance.bitfield.trim().is_empty() {
-                return Err(BlockchainError::CoreTimeValidationError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::BadAttestation,
+                    Some(i),
+                    format!(
                         "Assurance for validator {} has empty bitfield",
                         assurance.validator_index
                     ),
-                });
+                ));
             }
 
             if !seen_validators.insert(assurance.validator_index) {
-                return Err(BlockchainError::CoreTimeValidationError {
-                    reason: format!(
+                return Err(BlockchainError::rejected(
+                    ErrorCode::NotSortedOrUniqueAssurers,
+                    Some(i),
+                    format!(
                         "Duplicate assurance for validator {}",
                         assurance.validator_index
                     ),
-                });
+                ));
             }
         }
 
         if !disputes.is_null() {
             let disputes: CoreTimeDisputes =
                 serde_json::from_value(disputes.clone()).map_err(|e| {
-                    BlockchainError::CoreTimeValidationError {
+                    BlockchainError::Rejected {
+                        subsystem: Subsystem::Disputes,
+                        code: ErrorCode::Malformed,
+                        index: None,
                         reason: format!("Invalid dispute format: {e}"),
                     }
                 })?;
 
-            for verdict in disputes.verdicts {
+            for (i, verdict) in disputes.verdicts.iter().enumerate() {
                 if verdict.age > MAX_DISPUTE_AGE {
-                    return Err(BlockchainError::CoreTimeValidationError {
-                        reason: format!(
+                    return Err(BlockchainError::rejected(
+                        ErrorCode::BadJudgementAge,
+                        Some(i),
+                        format!(
                             "Dispute verdict age {} exceeds limit {}",
                             verdict.age, MAX_DISPUTE_AGE
                         ),
-                    });
+                    ));
                 }
 
                 if verdict.votes.is_empty() {
-                    return Err(BlockchainError::CoreTimeValidationError {
-                        reason: format!("Dispute verdict {} has no votes", verdict.target),
-                    });
+                    return Err(BlockchainError::rejected(
+                        ErrorCode::BadVoteSplit,
+                        Some(i),
+                        format!("Dispute verdict {} has no votes", verdict.target),
+                    ));
                 }
             }
         }
@@ -253,6 +299,53 @@ impl CoreTimeLedger {
     }
 }
 
+/// Binary form used when persisting state: totals followed by per-core usage sorted by core.
+impl Encode for CoreTimeLedger {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.total_allocated.encode_to(out);
+        self.total_consumed.encode_to(out);
+        self.last_block_slot.encode_to(out);
+
+        let mut cores: Vec<_> = self.per_core_usage.iter().collect();
+        cores.sort_by_key(|(core_index, _)| **core_index);
+        encode_natural(cores.len() as u64, out);
+        for (core_index, usage) in cores {
+            core_index.encode_to(out);
+            usage.total_consumed.encode_to(out);
+            usage.last_block_slot.encode_to(out);
+            usage.last_block_consumed.encode_to(out);
+        }
+    }
+}
+
+impl Decode for CoreTimeLedger {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let total_allocated = u64::decode(input)?;
+        let total_consumed = u64::decode(input)?;
+        let last_block_slot = Option::decode(input)?;
+
+        let mut per_core_usage = HashMap::new();
+        for _ in 0..decode_len(input)? {
+            let core_index = u16::decode(input)?;
+            per_core_usage.insert(
+                core_index,
+                CoreUsage {
+                    total_consumed: u64::decode(input)?,
+                    last_block_slot: u64::decode(input)?,
+                    last_block_consumed: u64::decode(input)?,
+                },
+            );
+        }
+
+        Ok(CoreTimeLedger {
+            total_allocated,
+            total_consumed,
+            last_block_slot,
+            per_core_usage,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct CoreTimeGuarantee {
     slot: u64,
@@ -0,0 +1,405 @@
+//! Reed-Solomon erasure coding in GF(2^16) (GP Appendix H).
+//!
+//! Data is coded into one chunk per validator so that any `original` chunks recover it:
+//! 342 of 1023 for `full`, 2 of 6 for `tiny`. Field elements are octet pairs read
+//! little-endian as coordinates in the Cantor basis of GF(2^16) (reduction polynomial
+//! `x^16 + x^5 + x^3 + x^2 + 1`), and chunk `i` is the evaluation of the data
+//! polynomial at the element with coordinates `i`. The data is split into pieces of
+//! `W_E` octets, each coded on its own, and chunk `i` interleaves word `i` of every
+//! piece. The code is systematic: the first `original` chunks hold the data itself.
+//!
+//! Following the reference implementation, the data polynomial has degree below
+//! `N`, the next power of two of `original`: it takes the data words at points
+//! `0..original` and zero at `original..N`, and recovery chunk `j` is its value at
+//! `N + j`. Encoding and reconstruction use Lagrange interpolation over those points.
+//!
+//! Also here: the segment size and the paged proofs of exported segments (GP 14.10).
+
+use std::sync::OnceLock;
+
+use crate::chain_spec::ChainSpec;
+use crate::codec::encode_seq;
+use crate::schema::{blake2b_256, BlockchainError};
+
+/// Octets in an exported or imported segment `W_G`.
+pub const SEGMENT_SIZE: usize = 4104;
+/// Segments covered by one paged proof.
+pub const SEGMENTS_PER_PAGE: usize = 64;
+
+/// GF(2^16) reduction polynomial.
+const POLYNOMIAL: u32 = 0x1002d;
+/// Multiplicative group order.
+const MODULUS: usize = 0xffff;
+/// Cantor basis of GF(2^16) in the polynomial basis; `v_0 = 1` and
+/// `v_i^2 + v_i = v_(i-1)`.
+const CANTOR_BASIS: [u16; 16] = [
+    0x0001, 0xacca, 0x3c0e, 0x163e, 0xc582, 0xed2e, 0x914c, 0x4012, 0x6c98, 0x10d8, 0x6a72, 0xb900,
+    0xfdb8, 0xfb34, 0xff38, 0x991e,
+];
+
+/// Logarithm and exponent tables over Cantor basis coordinates.
+struct Tables {
+    log: Vec<u16>,
+    exp: Vec<u16>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        // Powers of the generator `x` in the polynomial basis.
+        let mut poly_log = vec![0u16; MODULUS + 1];
+        let mut poly_exp = vec![0u16; MODULUS];
+        let mut state = 1u32;
+        for (i, power) in poly_exp.iter_mut().enumerate() {
+            *power = state as u16;
+            poly_log[state as usize] = i as u16;
+            state <<= 1;
+            if state > 0xffff {
+                state ^= POLYNOMIAL;
+            }
+        }
+
+        // Cantor coordinates to polynomial basis, and back.
+        let mut to_poly = vec![0u16; MODULUS + 1];
+        for (bit, basis) in CANTOR_BASIS.iter().enumerate() {
+            let width = 1 << bit;
+            for j in 0..width {
+                to_poly[j + width] = to_poly[j] ^ basis;
+            }
+        }
+        let mut from_poly = vec![0u16; MODULUS + 1];
+        for (cantor, poly) in to_poly.iter().enumerate() {
+            from_poly[*poly as usize] = cantor as u16;
+        }
+
+        Tables {
+            log: to_poly
+                .iter()
+                .map(|&poly| poly_log[poly as usize])
+                .collect(),
+            exp: poly_exp
+                .iter()
+                .map(|&poly| from_poly[poly as usize])
+                .collect(),
+        }
+    })
+}
+
+/// Product of two field elements.
+pub fn gf_mul(a: u16, b: u16) -> u16 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let t = tables();
+    t.exp[(usize::from(t.log[a as usize]) + usize::from(t.log[b as usize])) % MODULUS]
+}
+
+/// `a / b` for non-zero `b`.
+fn gf_div(a: u16, b: u16) -> u16 {
+    if a == 0 {
+        return 0;
+    }
+    let t = tables();
+    t.exp[(usize::from(t.log[a as usize]) + MODULUS - usize::from(t.log[b as usize])) % MODULUS]
+}
+
+/// `prod (x - p)` over `points`, skipping `x` itself.
+fn vanishing(x: u16, points: impl Iterator<Item = u16>) -> u16 {
+    points
+        .filter(|&p| p != x)
+        .fold(1, |acc, p| gf_mul(acc, x ^ p))
+}
+
+/// Encodes data into chunks and reconstructs it for one validator count.
+///
+/// Memory Usage:
+/// - Fixed: 2 bytes per recovery chunk, plus 256 KiB of field tables shared by all coders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErasureCoder {
+    original: usize,
+    total: usize,
+    /// `N`: points `0..N` carry the data followed by zeros.
+    span: usize,
+    /// Per recovery chunk `j`: `W(N + j) / W'`, where `W` vanishes on `0..N` and `W'` is
+    /// its derivative, the same at every point of the subspace.
+    scale: Vec<u16>,
+}
+
+impl ErasureCoder {
+    /// A coder whose data is recovered from any `original` of `total` chunks.
+    pub fn new(original: usize, total: usize) -> Result<Self, BlockchainError> {
+        let span = original.next_power_of_two();
+        if original == 0 || total < original || span + (total - original) > MODULUS + 1 {
+            return Err(BlockchainError::ErasureCoding {
+                reason: format!("cannot code {original} original chunks into {total}"),
+            });
+        }
+        let derivative = vanishing(0, 1..span as u16);
+        let scale = (0..total - original)
+            .map(|j| {
+                let x = (span + j) as u16;
+                gf_div(vanishing(x, 0..span as u16), derivative)
+            })
+            .collect();
+        Ok(ErasureCoder {
+            original,
+            total,
+            span,
+            scale,
+        })
+    }
+
+    /// The coder for a chain spec: one chunk per validator, `W_E / 2` original chunks.
+    pub fn for_spec(spec: &ChainSpec) -> Result<Self, BlockchainError> {
+        Self::new(
+            usize::from(spec.erasure_piece_size / 2),
+            usize::from(spec.validators_count),
+        )
+    }
+
+    /// Chunks needed to reconstruct the data.
+    pub fn original(&self) -> usize {
+        self.original
+    }
+
+    /// Chunks produced per encoding.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Octets of data coded per word of every chunk, `W_E`.
+    pub fn piece_size(&self) -> usize {
+        2 * self.original
+    }
+
+    /// Code `data`, zero-padded to a multiple of the piece size, into `total` chunks of
+    /// equal length. Each piece contributes one word to every chunk, so chunk
+    /// `i < original` holds word `i` of each piece.
+    pub fn encode(&self, data: &[u8]) -> Vec<Vec<u8>> {
+        let words = data.len().div_ceil(self.piece_size()).max(1);
+        let mut padded = data.to_vec();
+        padded.resize(words * self.piece_size(), 0);
+
+        let mut chunks = vec![vec![0; 2 * words]; self.total];
+        let t = tables();
+        for (w, piece) in padded.chunks(self.piece_size()).enumerate() {
+            let message: Vec<u16> = (0..self.original).map(|i| word(piece, i)).collect();
+            for (i, &m) in message.iter().enumerate() {
+                put_word(&mut chunks[i], w, m);
+            }
+            for (j, scale) in self.scale.iter().enumerate() {
+                let x = self.span + j;
+                // Lagrange over 0..N, where all but the data points are zero.
+                let sum = message.iter().enumerate().fold(0u16, |acc, (i, &m)| {
+                    if m == 0 {
+                        return acc;
+                    }
+                    let log = usize::from(t.log[m as usize]) + MODULUS - usize::from(t.log[x ^ i]);
+                    acc ^ t.exp[log % MODULUS]
+                });
+                put_word(&mut chunks[self.original + j], w, gf_mul(*scale, sum));
+            }
+        }
+        chunks
+    }
+
+    /// Recover `len` octets of data from at least `original` chunks given with their
+    /// indices.
+    pub fn reconstruct(
+        &self,
+        chunks: &[(usize, Vec<u8>)],
+        len: usize,
+    ) -> Result<Vec<u8>, BlockchainError> {
+        let mut received: Vec<&(usize, Vec<u8>)> = Vec::with_capacity(self.original);
+        for chunk in chunks {
+            if chunk.0 >= self.total {
+                return Err(BlockchainError::ErasureCoding {
+                    reason: format!("chunk index {} out of {}", chunk.0, self.total),
+                });
+            }
+            if received.len() < self.original && received.iter().all(|r| r.0 != chunk.0) {
+                received.push(chunk);
+            }
+        }
+        let Some(first) = received.first() else {
+            return Err(self.too_few(0));
+        };
+        if received.len() < self.original {
+            return Err(self.too_few(received.len()));
+        }
+        let chunk_len = first.1.len();
+        if chunk_len % 2 != 0 || received.iter().any(|r| r.1.len() != chunk_len) {
+            return Err(BlockchainError::ErasureCoding {
+                reason: "chunks differ in length or are not whole words".to_string(),
+            });
+        }
+        let words = chunk_len / 2;
+        if len > words * self.piece_size() {
+            return Err(BlockchainError::ErasureCoding {
+                reason: format!("{len} octets do not fit in chunks of {chunk_len}"),
+            });
+        }
+
+        let point = |index: usize| {
+            if index < self.original {
+                index as u16
+            } else {
+                (self.span + index - self.original) as u16
+            }
+        };
+        // Interpolate through the received points and the zeros at `original..N`.
+        let points: Vec<u16> = received
+            .iter()
+            .map(|r| point(r.0))
+            .chain(self.original as u16..self.span as u16)
+            .collect();
+        let weights: Vec<u16> = received
+            .iter()
+            .map(|r| vanishing(point(r.0), points.iter().copied()))
+            .collect();
+
+        let mut data = vec![0u8; words * self.piece_size()];
+        for i in 0..self.original {
+            // Word `w` of chunk `i` is word `i` of piece `w`.
+            let mut put = |w: usize, value: u16| {
+                put_word(&mut data[w * self.piece_size()..], i, value);
+            };
+            if let Some(r) = received.iter().find(|r| r.0 == i) {
+                for w in 0..words {
+                    put(w, word(&r.1, w));
+                }
+                continue;
+            }
+            let x = i as u16;
+            let at_x = vanishing(x, points.iter().copied());
+            let factors: Vec<u16> = received
+                .iter()
+                .zip(&weights)
+                .map(|(r, &weight)| gf_div(at_x, gf_mul(weight, x ^ point(r.0))))
+                .collect();
+            for w in 0..words {
+                let value = received
+                    .iter()
+                    .zip(&factors)
+                    .fold(0, |acc, (r, &f)| acc ^ gf_mul(f, word(&r.1, w)));
+                put(w, value);
+            }
+        }
+        data.truncate(len);
+        Ok(data)
+    }
+
+    fn too_few(&self, received: usize) -> BlockchainError {
+        BlockchainError::ErasureCoding {
+            reason: format!(
+                "{received} distinct chunks received, {} needed",
+                self.original
+            ),
+        }
+    }
+}
+
+fn word(chunk: &[u8], w: usize) -> u16 {
+    u16::from_le_bytes([chunk[2 * w], chunk[2 * w + 1]])
+}
+
+fn put_word(chunk: &mut [u8], w: usize, value: u16) {
+    chunk[2 * w..2 * w + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Root of the constant-depth Merkle tree over `segments` (GP E.4).
+pub fn segments_root(segments: &[Vec<u8>]) -> [u8; 32] {
+    node(&leaves(segments))
+}
+
+/// Sibling hashes from the root down to segment `index`, proving it under
+/// [`segments_root`].
+pub fn segment_justification(segments: &[Vec<u8>], index: usize) -> Vec<[u8; 32]> {
+    trace(&leaves(segments), index)
+}
+
+/// Check that `segment` is segment `index` of the tree with root `root`.
+pub fn verify_segment(
+    root: &[u8; 32],
+    index: usize,
+    segment: &[u8],
+    justification: &[[u8; 32]],
+) -> bool {
+    if index >> justification.len() != 0 {
+        return false;
+    }
+    let leaf = blake2b_256(&[b"leaf", segment].concat());
+    let computed = justification
+        .iter()
+        .rev()
+        .enumerate()
+        .fold(leaf, |hash, (level, sibling)| {
+            let (left, right) = if (index >> level) & 1 == 0 {
+                (&hash, sibling)
+            } else {
+                (sibling, &hash)
+            };
+            blake2b_256(&[&b"node"[..], left, right].concat())
+        });
+    computed == *root
+}
+
+/// One proof page per 64 segments: the justification of the page's subtree followed by
+/// the leaf hashes of its segments, zero-padded to a segment (GP 14.10).
+pub fn paged_proofs(segments: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let leaves = leaves(segments);
+    let depth = leaves.len().trailing_zeros() as usize;
+    let page_depth = SEGMENTS_PER_PAGE.trailing_zeros() as usize;
+    (0..segments.len().div_ceil(SEGMENTS_PER_PAGE))
+        .map(|page| {
+            let start = page * SEGMENTS_PER_PAGE;
+            let mut justification = trace(&leaves, start);
+            justification.truncate(depth.saturating_sub(page_depth));
+            let end = segments.len().min(start + SEGMENTS_PER_PAGE);
+
+            let mut proof = Vec::with_capacity(SEGMENT_SIZE);
+            encode_seq(&justification, &mut proof);
+            encode_seq(&leaves[start..end], &mut proof);
+            proof.resize(proof.len().div_ceil(SEGMENT_SIZE) * SEGMENT_SIZE, 0);
+            proof
+        })
+        .collect()
+}
+
+/// Leaf hashes padded with zero hashes to a power of two.
+fn leaves(segments: &[Vec<u8>]) -> Vec<[u8; 32]> {
+    let mut leaves: Vec<[u8; 32]> = segments
+        .iter()
+        .map(|segment| blake2b_256(&[b"leaf", segment.as_slice()].concat()))
+        .collect();
+    leaves.resize(segments.len().max(1).next_power_of_two(), [0; 32]);
+    leaves
+}
+
+fn node(hashes: &[[u8; 32]]) -> [u8; 32] {
+    match hashes {
+        [] => [0; 32],
+        [hash] => *hash,
+        _ => {
+            let (left, right) = hashes.split_at(hashes.len().div_ceil(2));
+            blake2b_256(&[&b"node"[..], &node(left), &node(right)].concat())
+        }
+    }
+}
+
+/// Sibling subtree roots on the path from the root to leaf `index`, top first.
+fn trace(hashes: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    if hashes.len() <= 1 {
+        return Vec::new();
+    }
+    let mid = hashes.len().div_ceil(2);
+    let (left, right) = hashes.split_at(mid);
+    let (sibling, rest) = if index < mid {
+        (node(right), trace(left, index))
+    } else {
+        (node(left), trace(right, index - mid))
+    };
+    let mut path = vec![sibling];
+    path.extend(rest);
+    path
+}
@@ -0,0 +1,146 @@
+//! Machine-readable block rejection codes.
+//!
+//! Every block the STF rejects carries an [`ErrorCode`], named after the `err` outputs of
+//! the jam-test-vectors where one exists (`bad_slot`, `bad_ticket_attempt`,
+//! `core_engaged`, ...), the [`Subsystem`] that rejected it and, for extrinsic items, the
+//! index of the offending item. Runners and tests compare codes instead of matching on
+//! message text.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::BlockchainError;
+
+/// Part of the state transition that rejected a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Block structure and decoding.
+    Block,
+    Header,
+    /// Tickets extrinsic and Safrole state.
+    Safrole,
+    Preimages,
+    /// Guarantees extrinsic (work reports) and CoreTime accounting.
+    Guarantees,
+    Assurances,
+    Disputes,
+}
+
+impl Subsystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Subsystem::Block => "block",
+            Subsystem::Header => "header",
+            Subsystem::Safrole => "safrole",
+            Subsystem::Preimages => "preimages",
+            Subsystem::Guarantees => "guarantees",
+            Subsystem::Assurances => "assurances",
+            Subsystem::Disputes => "disputes",
+        }
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+macro_rules! error_codes {
+    ($($(#[$doc:meta])* $variant:ident => $name:literal, $subsystem:ident;)*) => {
+        /// Reason a block was rejected.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum ErrorCode {
+            $($(#[$doc])* $variant,)*
+        }
+
+        impl ErrorCode {
+            /// Every code, in declaration order.
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant,)*];
+
+            /// The code as written in test vectors.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $name,)*
+                }
+            }
+
+            /// Subsystem the code belongs to.
+            pub fn subsystem(self) -> Subsystem {
+                match self {
+                    $(ErrorCode::$variant => Subsystem::$subsystem,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    /// A required block section is malformed.
+    Malformed => "malformed", Block;
+    /// Slot does not advance past the parent's, or is zero.
+    BadSlot => "bad_slot", Header;
+    BadParent => "bad_parent", Header;
+    BadParentStateRoot => "bad_parent_state_root", Header;
+    UnknownParent => "unknown_parent", Header;
+    /// Author index outside the validator set.
+    UnexpectedAuthor => "unexpected_author", Header;
+    BadEntropy => "bad_entropy", Header;
+    /// Tickets submitted where none are expected, or not matching the tickets mark.
+    UnexpectedTicket => "unexpected_ticket", Safrole;
+    /// Tickets extrinsic entries are not in strictly ascending identifier order.
+    BadTicketOrder => "bad_ticket_order", Safrole;
+    BadTicketAttempt => "bad_ticket_attempt", Safrole;
+    BadTicketProof => "bad_ticket_proof", Safrole;
+    /// Preimage nobody requested, or an empty one.
+    PreimageUnneeded => "preimage_unneeded", Preimages;
+    BadCoreIndex => "bad_core_index", Guarantees;
+    FutureReportSlot => "future_report_slot", Guarantees;
+    /// Guarantee older than the lookback window.
+    ReportEpochBeforeLast => "report_epoch_before_last", Guarantees;
+    /// A core already has a report for this slot.
+    CoreEngaged => "core_engaged", Guarantees;
+    /// Gas exceeds the per-core or per-block CoreTime limit.
+    WorkReportGasTooHigh => "work_report_gas_too_high", Guarantees;
+    /// Guarantee without 2 or 3 guarantor credentials.
+    InsufficientGuarantees => "insufficient_guarantees", Guarantees;
+    TooManyAssurances => "too_many_assurances", Assurances;
+    NotSortedOrUniqueAssurers => "not_sorted_or_unique_assurers", Assurances;
+    BadAttestation => "bad_attestation", Assurances;
+    BadJudgementAge => "bad_judgement_age", Disputes;
+    BadVoteSplit => "bad_vote_split", Disputes;
+    BadSignature => "bad_signature", Block;
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|code| code.as_str() == s)
+            .ok_or_else(|| BlockchainError::CodecError {
+                reason: format!("unknown error code `{s}`"),
+            })
+    }
+}
+
+/// The rejection code of the first [`BlockchainError`] in an error chain, if the error is
+/// a block rejection.
+pub fn error_code(error: &anyhow::Error) -> Option<ErrorCode> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+        .and_then(BlockchainError::code)
+}
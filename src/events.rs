@@ -0,0 +1,105 @@
+//! Import events for indexers, metrics and alerting.
+//!
+//! Observers registered with [`Importer::observe`](crate::Importer::observe) are told about
+//! each event once the importer has committed the change it describes. Events only hold
+//! copies and shared references, so an observer cannot influence what gets imported.
+
+use std::fmt;
+
+use crate::schema::OpaqueHash;
+use crate::stf::ReportedPackage;
+
+/// Share of a CoreTime limit, in percent, above which
+/// [`ImportEvent::CoreTimeLimitApproached`] is raised.
+pub const CORETIME_WARNING_PERCENT: u64 = 90;
+
+/// Something that happened while importing or finalizing blocks.
+#[derive(Debug)]
+pub enum ImportEvent<'a> {
+    BlockImported {
+        hash: OpaqueHash,
+        slot: u32,
+        /// Root of the block's posterior state.
+        state_root: Option<[u8; 32]>,
+        /// Whether the block became the best head.
+        best: bool,
+    },
+    /// A block failed validation. Internal errors such as store failures are not
+    /// reported here.
+    BlockRejected {
+        hash: OpaqueHash,
+        slot: u32,
+        error: &'a anyhow::Error,
+    },
+    /// An imported block opened a new epoch.
+    EpochChanged { epoch: u64, slot: u32 },
+    /// An imported block reported offending validators in its header.
+    Offenders {
+        hash: OpaqueHash,
+        offenders: &'a [OpaqueHash],
+    },
+    /// A work package was reported on a core by an imported block.
+    WorkReported {
+        hash: OpaqueHash,
+        package: &'a ReportedPackage,
+    },
+    /// CoreTime consumed by a block reached [`CORETIME_WARNING_PERCENT`] of a limit.
+    CoreTimeLimitApproached {
+        slot: u32,
+        /// The core, or `None` for the per-block limit.
+        core: Option<u16>,
+        consumed: u64,
+        limit: u64,
+    },
+    /// A block was finalized and competing branches pruned.
+    Finalized {
+        hash: OpaqueHash,
+        slot: u64,
+        /// Blocks on the discarded branches, not the finalized block's ancestors.
+        pruned: &'a [OpaqueHash],
+    },
+}
+
+/// Receiver of [`ImportEvent`]s.
+///
+/// Implemented for any `FnMut(&ImportEvent)` closure.
+pub trait ImportObserver: Send {
+    fn on_event(&mut self, event: &ImportEvent<'_>);
+}
+
+impl<F: FnMut(&ImportEvent<'_>) + Send> ImportObserver for F {
+    fn on_event(&mut self, event: &ImportEvent<'_>) {
+        self(event)
+    }
+}
+
+/// The observers registered on an importer, notified in registration order.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn ImportObserver>>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Box<dyn ImportObserver>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn notify(&mut self, event: &ImportEvent<'_>) {
+        for observer in &mut self.0 {
+            observer.on_event(event);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+/// Whether `consumed` is within [`CORETIME_WARNING_PERCENT`] of `limit`.
+pub(crate) fn approaches_limit(consumed: u64, limit: u64) -> bool {
+    consumed.saturating_mul(100) >= limit.saturating_mul(CORETIME_WARNING_PERCENT)
+}
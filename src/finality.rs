@@ -0,0 +1,149 @@
+//! GRANDPA-style finality justifications.
+//!
+//! A block is final once a supermajority (`floor(2V/3) + 1`) of the active validators
+//! has signed a precommit for it. Finalizing re-roots the [`BlockTree`](crate::block_tree::BlockTree)
+//! at that block; an importer with a [`Store`](crate::store::Store) records it there as
+//! a [`FinalizedRecord`](crate::store::FinalizedRecord), so a restarted node resumes from it.
+
+use std::collections::HashSet;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use log::{debug, warn};
+
+use crate::codec::{decode_seq, encode_seq, Decode, Encode};
+use crate::schema::{BlockchainError, OpaqueHash};
+
+/// Context string prefixed to every finality precommit before signing.
+pub const FINALITY_CONTEXT: &[u8] = b"jam_finality_precommit";
+
+/// A single validator's precommit signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalityVote {
+    pub validator_index: u16,
+    pub signature: Vec<u8>,
+}
+
+/// Proof that a block was finalized by the active validator set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Justification {
+    pub block_hash: OpaqueHash,
+    pub slot: u32,
+    pub votes: Vec<FinalityVote>,
+}
+
+/// Minimum number of votes out of `validators` for a supermajority.
+pub fn supermajority(validators: usize) -> usize {
+    validators * 2 / 3 + 1
+}
+
+/// Message validators sign to precommit `block_hash` at `slot`.
+pub fn precommit_payload(block_hash: &OpaqueHash, slot: u32) -> Vec<u8> {
+    let mut payload = FINALITY_CONTEXT.to_vec();
+    payload.extend_from_slice(block_hash.as_bytes());
+    payload.extend_from_slice(&slot.to_le_bytes());
+    payload
+}
+
+impl Justification {
+    /// Verify the justification against the active Ed25519 validator keys.
+    ///
+    /// Votes must come from distinct, in-range validators, carry valid signatures, and
+    /// together reach a supermajority of `validators`.
+    pub fn verify(&self, validators: &[OpaqueHash]) -> Result<(), BlockchainError> {
+        if validators.is_empty() {
+            return Err(BlockchainError::InvalidJustification {
+                reason: "No active validator set to verify against".to_string(),
+            });
+        }
+
+        let payload = precommit_payload(&self.block_hash, self.slot);
+        let mut signers = HashSet::new();
+        for (i, vote) in self.votes.iter().enumerate() {
+            let key = validators
+                .get(vote.validator_index as usize)
+                .ok_or_else(|| BlockchainError::InvalidJustification {
+                    reason: format!(
+                        "Vote {} from validator {} outside set of {}",
+                        i,
+                        vote.validator_index,
+                        validators.len()
+                    ),
+                })?;
+            if !signers.insert(vote.validator_index) {
+                return Err(BlockchainError::InvalidJustification {
+                    reason: format!("Duplicate vote from validator {}", vote.validator_index),
+                });
+            }
+            verify_ed25519(key, &payload, &vote.signature).map_err(|reason| {
+                BlockchainError::InvalidSignature {
+                    reason: format!(
+                        "Vote {} from validator {}: {reason}",
+                        i, vote.validator_index
+                    ),
+                }
+            })?;
+        }
+
+        let required = supermajority(validators.len());
+        if signers.len() < required {
+            warn!(
+                "Justification for {} has {} of {} required votes",
+                hex::encode(self.block_hash.as_bytes()),
+                signers.len(),
+                required
+            );
+            return Err(BlockchainError::InvalidJustification {
+                reason: format!("{} votes, {} required", signers.len(), required),
+            });
+        }
+
+        debug!(
+            "Justification for {} verified with {} votes",
+            hex::encode(self.block_hash.as_bytes()),
+            signers.len()
+        );
+        Ok(())
+    }
+}
+
+/// Verify an Ed25519 signature, reporting malformed keys and signatures as errors.
+pub fn verify_ed25519(key: &OpaqueHash, message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let key = VerifyingKey::from_bytes(key.as_bytes()).map_err(|e| format!("bad key: {e}"))?;
+    let signature = Signature::from_slice(signature).map_err(|e| format!("bad signature: {e}"))?;
+    key.verify(message, &signature)
+        .map_err(|_| "signature does not verify".to_string())
+}
+
+impl Encode for FinalityVote {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.validator_index.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl Decode for FinalityVote {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(FinalityVote {
+            validator_index: u16::decode(input)?,
+            signature: Vec::<u8>::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Justification {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.block_hash.encode_to(out);
+        self.slot.encode_to(out);
+        encode_seq(&self.votes, out);
+    }
+}
+
+impl Decode for Justification {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Justification {
+            block_hash: OpaqueHash::decode(input)?,
+            slot: u32::decode(input)?,
+            votes: decode_seq(input)?,
+        })
+    }
+}
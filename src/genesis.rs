@@ -0,0 +1,330 @@
+//! Genesis loading and chain bootstrapping.
+//!
+//! A genesis is a header plus the state it commits to. The state may be given as raw
+//! state key/values, as the conformance and trace vectors provide, or as typed JSON using
+//! the GP component names. Raw key/values in the GP serialization (Appendix D.1) are
+//! decoded into the components JamLiquor models; key/values exported by JamLiquor itself
+//! (see [`crate::snapshot`]) are read back directly.
+//!
+//! The genesis state root is the Merkle root of the key/values as given, so the first
+//! block must name exactly the root the vectors were generated with.
+
+use std::fs;
+use std::path::Path;
+
+use log::{debug, info};
+use serde_json::Value;
+
+use crate::chain_spec::ChainSpec;
+use crate::coretime::CoreTimeLedger;
+use crate::merkle::{merklize, KeyValues, StateKey, STATE_KEY_LEN};
+use crate::schema::{BlockchainError, Header, OpaqueHash, State, TicketBody};
+use crate::state_keys::{
+    component_key, ACTIVE_VALIDATORS, ENTROPY, JAMLIQUOR_EXTENSION, SAFROLE, TIMESLOT,
+};
+
+/// Size of a GP validator key record: Bandersnatch, Ed25519, BLS and metadata.
+pub const VALIDATOR_RECORD_LEN: usize = 336;
+/// Offset of the Ed25519 key within a validator key record.
+const ED25519_OFFSET: usize = 32;
+/// Size of the Bandersnatch ring root following `gamma_k` in the Safrole component.
+const RING_ROOT_LEN: usize = 144;
+/// Size of a GP ticket body: identifier and attempt.
+const TICKET_BODY_LEN: usize = 33;
+
+/// Genesis header and state.
+///
+/// Memory Usage:
+/// - Fixed: ~300 bytes (header, root)
+/// - Dynamic: the state key/values and decoded validator sets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Genesis {
+    pub header: Header,
+    pub state: State,
+    pub state_root: [u8; 32],
+    pub key_values: KeyValues,
+}
+
+impl Genesis {
+    /// Build a genesis from raw state key/values.
+    ///
+    /// The genesis root is merklized over `key_values` as given, usually the GP
+    /// serialization, so the first block must name the root the vectors were generated
+    /// with. Every later root is computed from [`State::to_key_values`], JamLiquor's own
+    /// serialization of the components it models, so from the second block on the roots
+    /// no longer match a GP chain. Only genesis given in JamLiquor's serialization keeps
+    /// a single model throughout.
+    pub fn from_key_values(
+        header: Header,
+        key_values: KeyValues,
+        spec: &ChainSpec,
+    ) -> Result<Self, BlockchainError> {
+        Ok(Genesis {
+            header,
+            state: Self::decode_state(&key_values, spec)?,
+            state_root: merklize(&key_values),
+            key_values,
+        })
+    }
+
+    /// Decode the modelled state components from key/values in either serialization.
+    pub fn decode_state(
+        key_values: &KeyValues,
+        spec: &ChainSpec,
+    ) -> Result<State, BlockchainError> {
+        if key_values.contains_key(&component_key(JAMLIQUOR_EXTENSION)) {
+            State::from_key_values(key_values)
+        } else {
+            decode_gp_state(key_values, spec)
+        }
+    }
+
+    /// Build a genesis from an already typed state.
+    pub fn from_state(header: Header, state: State) -> Self {
+        let key_values = state.to_key_values();
+        Genesis {
+            header,
+            state,
+            state_root: merklize(&key_values),
+            key_values,
+        }
+    }
+
+    /// Parse a genesis document: `{"header": ..., "state": ...}`.
+    ///
+    /// `state` is either `{"state_root": ..., "keyvals": [...]}` or a typed state object
+    /// with the GP fields `tau`, `kappa` and `gamma` (or `gamma_k`). A `state_root`, if
+    /// present, must match the merklized key/values.
+    pub fn from_json(value: &Value, spec: &ChainSpec) -> Result<Self, BlockchainError> {
+        let header: Header = serde_json::from_value(field(value, "header")?.clone())?;
+        let state = field(value, "state")?;
+
+        let genesis = match state.get("keyvals") {
+            Some(keyvals) => Self::from_key_values(header, parse_keyvals(keyvals)?, spec)?,
+            None => Self::from_state(header, parse_typed_state(state)?),
+        };
+
+        if let Some(expected) = state.get("state_root") {
+            let expected = parse_hex(expected, "state_root")?;
+            if expected != genesis.state_root {
+                return Err(BlockchainError::StateTransitionError {
+                    reason: format!(
+                        "Genesis state root mismatch: document says 0x{}, key/values give 0x{}",
+                        hex::encode(&expected),
+                        hex::encode(genesis.state_root)
+                    ),
+                });
+            }
+        }
+
+        debug!(
+            "Parsed genesis with {} state keys and {} validators",
+            genesis.key_values.len(),
+            genesis.state.active_validators.len()
+        );
+        Ok(genesis)
+    }
+
+    /// Load a genesis JSON file.
+    pub fn load<P: AsRef<Path>>(path: P, spec: &ChainSpec) -> Result<Self, BlockchainError> {
+        let value: Value = serde_json::from_slice(&fs::read(path.as_ref())?)?;
+        let genesis = Self::from_json(&value, spec)?;
+        info!(
+            "Loaded genesis {} from {} (state root {})",
+            hex::encode(genesis.hash().as_bytes()),
+            path.as_ref().display(),
+            hex::encode(genesis.state_root)
+        );
+        Ok(genesis)
+    }
+
+    /// Hash of the genesis header.
+    pub fn hash(&self) -> OpaqueHash {
+        self.header.hash()
+    }
+
+    /// State the importer starts from: the genesis state, anchored at the genesis hash and
+    /// root, with a CoreTime ledger starting at the genesis slot.
+    pub fn initial_state(&self) -> State {
+        let mut state = self.state.clone();
+        state.last_block_hash = Some(*self.hash().as_bytes());
+        state.last_state_root = Some(self.state_root);
+        if state.coretime.last_block_slot().is_none() {
+            state.coretime = CoreTimeLedger::starting_at(state.last_slot);
+        }
+        state
+    }
+}
+
+pub(crate) fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, BlockchainError> {
+    value
+        .get(name)
+        .ok_or_else(|| BlockchainError::InvalidBlockStructure {
+            reason: format!("JSON document has no `{name}`"),
+        })
+}
+
+pub(crate) fn parse_hex(value: &Value, what: &str) -> Result<Vec<u8>, BlockchainError> {
+    let s = value
+        .as_str()
+        .ok_or_else(|| BlockchainError::InvalidBlockStructure {
+            reason: format!("`{what}` is not a hex string"),
+        })?;
+    Ok(hex::decode(s.trim_start_matches("0x"))?)
+}
+
+/// Parse `[{"key": ..., "value": ...}]` or `[[key, value, ...]]` entries.
+pub fn parse_keyvals(keyvals: &Value) -> Result<KeyValues, BlockchainError> {
+    let entries = keyvals
+        .as_array()
+        .ok_or_else(|| BlockchainError::InvalidBlockStructure {
+            reason: "State `keyvals` is not an array".to_string(),
+        })?;
+
+    let mut kvs = KeyValues::new();
+    for entry in entries {
+        let (key, value) = match entry {
+            Value::Array(items) if items.len() >= 2 => (&items[0], &items[1]),
+            _ => (field(entry, "key")?, field(entry, "value")?),
+        };
+        let key = parse_hex(key, "key")?;
+        let key: StateKey =
+            key.try_into()
+                .map_err(|key: Vec<u8>| BlockchainError::InvalidBlockStructure {
+                    reason: format!("State key is {} bytes, expected {STATE_KEY_LEN}", key.len()),
+                })?;
+        kvs.insert(key, parse_hex(value, "value")?);
+    }
+    Ok(kvs)
+}
+
+/// A validator given as a hex key or as a GP key record object.
+fn parse_validator(value: &Value) -> Result<OpaqueHash, BlockchainError> {
+    let key = match value.get("ed25519") {
+        Some(ed25519) => ed25519,
+        None => value,
+    };
+    OpaqueHash::from_hex(key.as_str().unwrap_or_default())
+}
+
+fn parse_validators(value: Option<&Value>) -> Result<Vec<OpaqueHash>, BlockchainError> {
+    match value {
+        Some(Value::Array(validators)) => validators.iter().map(parse_validator).collect(),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn parse_typed_state(value: &Value) -> Result<State, BlockchainError> {
+    let gamma = value.get("gamma");
+    let gamma_k = gamma
+        .and_then(|gamma| gamma.get("gamma_k"))
+        .or_else(|| value.get("gamma_k"));
+
+    let mut state = State::new();
+    state.last_slot = value.get("tau").and_then(Value::as_u64).unwrap_or(0);
+    state.active_validators = parse_validators(value.get("kappa"))?;
+    state.pending_validators = parse_validators(gamma_k)?;
+    // `gamma_s` is either `{"tickets": [...]}` or `{"keys": [...]}`.
+    state.ticket_state.sealed_by_tickets = gamma
+        .and_then(|gamma| gamma.get("gamma_s"))
+        .is_some_and(|gamma_s| gamma_s.get("tickets").is_some());
+    Ok(state)
+}
+
+/// Extract the Ed25519 keys from a run of GP validator key records.
+fn validator_keys(records: &[u8]) -> Result<Vec<OpaqueHash>, BlockchainError> {
+    if !records.len().is_multiple_of(VALIDATOR_RECORD_LEN) {
+        return Err(BlockchainError::CodecError {
+            reason: format!(
+                "{} bytes of validator keys is not a multiple of {VALIDATOR_RECORD_LEN}",
+                records.len()
+            ),
+        });
+    }
+    let (records, _) = records.as_chunks::<VALIDATOR_RECORD_LEN>();
+    Ok(records
+        .iter()
+        .map(|record| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&record[ED25519_OFFSET..ED25519_OFFSET + 32]);
+            OpaqueHash::new(key)
+        })
+        .collect())
+}
+
+/// Decode the modelled components from GP-serialized key/values.
+/// The `E` ticket bodies at the start of a ticket-sealed `gamma_s`.
+fn sealing_tickets(bytes: &[u8], spec: &ChainSpec) -> Result<Vec<TicketBody>, BlockchainError> {
+    let count = spec.epoch_length as usize;
+    let bytes =
+        bytes
+            .get(..count * TICKET_BODY_LEN)
+            .ok_or_else(|| BlockchainError::CodecError {
+                reason: format!("C({SAFROLE}) is too short for {count} sealing tickets"),
+            })?;
+    let (tickets, _) = bytes.as_chunks::<TICKET_BODY_LEN>();
+    Ok(tickets
+        .iter()
+        .map(|ticket| {
+            let mut id = [0u8; 32];
+            id.copy_from_slice(&ticket[..32]);
+            TicketBody {
+                id: OpaqueHash::new(id),
+                attempt: ticket[32],
+            }
+        })
+        .collect())
+}
+
+fn decode_gp_state(kvs: &KeyValues, spec: &ChainSpec) -> Result<State, BlockchainError> {
+    let component = |index: u8| {
+        kvs.get(&component_key(index))
+            .ok_or_else(|| BlockchainError::StateTransitionError {
+                reason: format!("Genesis state component C({index}) missing"),
+            })
+    };
+
+    let tau = component(TIMESLOT)?;
+    let tau: [u8; 4] = tau
+        .as_slice()
+        .try_into()
+        .map_err(|_| BlockchainError::CodecError {
+            reason: format!("C({TIMESLOT}) is {} bytes, expected 4", tau.len()),
+        })?;
+
+    let mut state = State::new();
+    state.last_slot = u64::from(u32::from_le_bytes(tau));
+    state.active_validators = validator_keys(component(ACTIVE_VALIDATORS)?)?;
+
+    if let Some(gamma) = kvs.get(&component_key(SAFROLE)) {
+        let keys_len = spec.validators_count as usize * VALIDATOR_RECORD_LEN;
+        if gamma.len() < keys_len + RING_ROOT_LEN + 1 {
+            return Err(BlockchainError::CodecError {
+                reason: format!(
+                    "C({SAFROLE}) is {} bytes, too short for {} validators",
+                    gamma.len(),
+                    spec.validators_count
+                ),
+            });
+        }
+        state.pending_validators = validator_keys(&gamma[..keys_len])?;
+        // gamma_s discriminator: 0 = tickets, 1 = fallback keys.
+        let sealing = &gamma[keys_len + RING_ROOT_LEN..];
+        state.ticket_state.sealed_by_tickets = sealing[0] == 0;
+        if state.ticket_state.sealed_by_tickets {
+            state.ticket_state.sealing_tickets = sealing_tickets(&sealing[1..], spec)?;
+        }
+    }
+    // eta_3, the entropy sealing the current epoch.
+    if let Some(eta) = kvs.get(&component_key(ENTROPY)) {
+        let eta_3 = eta
+            .get(96..128)
+            .ok_or_else(|| BlockchainError::CodecError {
+                reason: format!("C({ENTROPY}) is {} bytes, expected 128", eta.len()),
+            })?;
+        let mut entropy = [0u8; 32];
+        entropy.copy_from_slice(eta_3);
+        state.ticket_state.entropy = OpaqueHash::new(entropy);
+    }
+    Ok(state)
+}
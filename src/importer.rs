@@ -1,42 +1,235 @@
-use crate::coretime::CoreTimeLedger;
-use crate::schema::{Block, BlockchainError, Extrinsic, Header, State};
+use crate::block_tree::BlockTree;
+use crate::chain_import::{
+    is_invalid_block, ChainImportReport, ChainSource, InvalidBlockPolicy, RejectedBlock,
+    IMPORT_FINALITY_LAG,
+};
+use crate::chain_spec::ChainSpec;
+use crate::codec::{Codec, Encode};
+use crate::coretime::{CoreTimeLedger, MAX_CORETIME_PER_BLOCK, MAX_CORETIME_PER_CORE};
+use crate::events::{approaches_limit, ImportEvent, ImportObserver, Observers};
+use crate::finality::Justification;
+use crate::genesis::Genesis;
+use crate::schema::{Block, BlockchainError, Header, OpaqueHash, State};
+use crate::snapshot::{Snapshot, SnapshotAnchor};
+use crate::stf::{self, PhaseTimings, StfOutputs, ValidationReport};
+use crate::store::{self, FileStore, FinalizedRecord, Store};
+use crate::verification::{collect_checks, SignatureVerifier, VerificationConfig};
+use crate::wal::WriteBatch;
 use anyhow::{Context, Result};
-use log::{debug, info, trace, warn};
+use log::{debug, info, warn};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
+/// How often [`Importer::import_chain`] logs its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
 pub struct Importer {
-    state: State,
-    last_block_hash: Option<[u8; 32]>,
-    last_state_root: Option<[u8; 32]>,
-    coretime: CoreTimeLedger,
+    tree: BlockTree,
+    spec: ChainSpec,
+    store: Option<Box<dyn Store>>,
+    observers: Observers,
+    /// Signature pre-verification, if enabled.
+    verifier: Option<SignatureVerifier>,
 }
 
 impl Importer {
     pub fn new() -> Self {
+        Self::with_spec(ChainSpec::default())
+    }
+
+    /// Create an importer validating against the given chain configuration.
+    ///
+    /// The importer starts from an unanchored root: the first imported block may name any
+    /// parent, and every later block must extend a block already in the tree.
+    pub fn with_spec(spec: ChainSpec) -> Self {
         Importer {
-            state: State::new(),
-            last_block_hash: None,
-            last_state_root: None,
-            coretime: CoreTimeLedger::default(),
+            tree: BlockTree::new(OpaqueHash::default(), None, State::new()),
+            spec,
+            store: None,
+            observers: Observers::default(),
+            verifier: None,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn set_initial_state(&mut self, last_hash: [u8; 32], last_root: [u8; 32]) {
-        self.last_block_hash = Some(last_hash);
-        self.last_state_root = Some(last_root);
+    /// Open (or create) a file store in `dir` and resume from it.
+    pub fn open<P: AsRef<Path>>(spec: ChainSpec, dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let store = FileStore::open(dir)
+            .with_context(|| format!("Failed to open data directory {}", dir.display()))?;
+        Self::with_store(spec, Box::new(store))
+    }
+
+    /// Open (or create) a file store in `dir`, starting a new one from `genesis`.
+    pub fn open_with_genesis<P: AsRef<Path>>(
+        spec: ChainSpec,
+        dir: P,
+        genesis: &Genesis,
+    ) -> Result<Self> {
+        let dir = dir.as_ref();
+        let store = FileStore::open(dir)
+            .with_context(|| format!("Failed to open data directory {}", dir.display()))?;
+        Self::resume(spec, Box::new(store), Some(genesis))
+    }
+
+    /// Create an importer rooted at a genesis block.
+    ///
+    /// The genesis state, including its validator sets and CoreTime ledger, becomes the
+    /// state of the tree root; the first block must name the genesis hash and state root.
+    pub fn from_genesis(spec: ChainSpec, genesis: &Genesis) -> Self {
+        let mut importer = Self::with_spec(spec);
+        importer.tree = BlockTree::new(
+            genesis.hash(),
+            Some(genesis.header.clone()),
+            genesis.initial_state(),
+        );
+        info!(
+            "Initialized from genesis {} at slot {}",
+            hex::encode(genesis.hash().as_bytes()),
+            genesis.header.slot
+        );
+        importer
+    }
+
+    /// Create an importer rooted at a known block and its posterior state.
+    ///
+    /// `state` must record the hash and state root the next block will name.
+    pub fn from_state(
+        spec: ChainSpec,
+        hash: OpaqueHash,
+        header: Option<Header>,
+        state: State,
+    ) -> Self {
+        let mut importer = Self::with_spec(spec);
+        importer.tree = BlockTree::new(hash, header, state);
+        importer
+    }
+
+    /// Create an importer that persists blocks and states to `store`.
+    ///
+    /// The tree is rebuilt from the store: it is rooted at the recorded finalized block and
+    /// every stored descendant is re-inserted in slot order, so the best head is the one the
+    /// previous run left off at. An empty store starts from an unanchored root.
+    pub fn with_store(spec: ChainSpec, store: Box<dyn Store>) -> Result<Self> {
+        Self::resume(spec, store, None)
+    }
+
+    /// Rebuild the tree from `store`, or start an empty store from `genesis` if given.
+    fn resume(spec: ChainSpec, store: Box<dyn Store>, genesis: Option<&Genesis>) -> Result<Self> {
+        let mut importer = match genesis {
+            Some(genesis) => Self::from_genesis(spec, genesis),
+            None => Self::with_spec(spec),
+        };
+        importer.store = Some(store);
+        let store = importer.store.as_deref().expect("store just set");
+
+        let Some(record) = store::finalized(store).context("Failed to read finalized head")? else {
+            importer.persist_root(&[])?;
+            return Ok(importer);
+        };
+        let mut state = store::load_state(store, &record.state_root)
+            .context("Failed to load finalized state")?;
+        state.last_state_root = record.last_state_root;
+        importer.tree = BlockTree::new(record.hash, record.header, state);
+        let root_slot = importer.tree.best_node().slot();
+
+        for hash in store
+            .block_hashes()
+            .context("Failed to list stored blocks")?
+        {
+            if importer.tree.contains(&hash) {
+                continue;
+            }
+            let stored = store.get_block(&hash)?.with_context(|| {
+                format!("Stored block {} vanished", hex::encode(hash.as_bytes()))
+            })?;
+            // Blocks at or below the finalized slot are its ancestors or pruned forks.
+            if u64::from(stored.block.header.slot) <= root_slot {
+                continue;
+            }
+            let Ok(parent) = importer.resolve_parent(&stored.block.header) else {
+                debug!(
+                    "Skipping stored block {} off the finalized chain",
+                    hex::encode(hash.as_bytes())
+                );
+                continue;
+            };
+            let state = store::load_state(store, &stored.state_root).with_context(|| {
+                format!(
+                    "Failed to load state of block {}",
+                    hex::encode(hash.as_bytes())
+                )
+            })?;
+            let ticket_sealed = state.ticket_state.sealed_by_tickets;
+            importer
+                .tree
+                .insert(parent, stored.block.header, state, ticket_sealed);
+        }
+
+        info!(
+            "Resumed from store: finalized={} best={} ({} blocks)",
+            hex::encode(importer.tree.root().as_bytes()),
+            hex::encode(importer.tree.best().as_bytes()),
+            importer.tree.len()
+        );
+        Ok(importer)
+    }
+
+    /// Start from a state snapshot, verified against a trusted `anchor`, instead of
+    /// replaying the chain.
+    pub fn from_snapshot(
+        spec: ChainSpec,
+        snapshot: &Snapshot,
+        anchor: &SnapshotAnchor,
+    ) -> Result<Self> {
+        let state = snapshot
+            .verify(anchor)
+            .context("Snapshot failed verification")?;
+        let mut importer = Self::with_spec(spec);
+        importer.tree = BlockTree::new(snapshot.hash(), Some(snapshot.header.clone()), state);
+        Ok(importer)
     }
 
     pub fn coretime(&self) -> &CoreTimeLedger {
-        &self.coretime
+        &self.state().coretime
+    }
+
+    /// Chain configuration used for validation.
+    pub fn spec(&self) -> &ChainSpec {
+        &self.spec
+    }
+
+    /// Tree of imported, non-finalized blocks.
+    pub fn tree(&self) -> &BlockTree {
+        &self.tree
+    }
+
+    /// Hash of the best head selected by the fork-choice rule.
+    pub fn best_head(&self) -> OpaqueHash {
+        self.tree.best()
+    }
+
+    /// Hash of the last finalized block (the root of the block tree).
+    pub fn finalized_head(&self) -> OpaqueHash {
+        self.tree.root()
+    }
+
+    /// Hash recorded for the best head, if any.
+    pub fn last_block_hash(&self) -> Option<[u8; 32]> {
+        self.state().last_block_hash
+    }
+
+    /// State root recorded for the best head, if any.
+    pub fn last_state_root(&self) -> Option<[u8; 32]> {
+        self.state().last_state_root
     }
 
     /// Import and validate a block from a given path
     ///
-    /// This function performs streaming JSON parsing for memory efficiency with large blocks.
-    /// It validates the block structure, header, and transactions before applying state changes.
+    /// The codec is chosen from the file extension (`.jlb` is binary, anything else JSON).
+    /// JSON is parsed in streaming fashion for memory efficiency with large blocks.
     pub fn import_block<P: AsRef<Path>>(&mut self, path: P) -> Result<Block> {
         let path = path.as_ref();
         info!("Importing block from path: {}", path.display());
@@ -46,302 +239,469 @@ impl Importer {
             .map_err(BlockchainError::IoError)
             .with_context(|| format!("Failed to open block file: {}", path.display()))?;
 
-        // Use buffered reader for better performance
-        let reader = BufReader::new(file);
-
-        // Parse JSON with size limits for security
-        let block: Block = serde_json::from_reader(reader)
-            .map_err(BlockchainError::JsonError)
-            .with_context(|| format!("Failed to parse block JSON from {}", path.display()))?;
-
-        // Validate and apply block
-        self.validate_and_apply_block(&block)
-            .with_context(|| "Block validation failed")?;
+        let block = Codec::from_path(path)
+            .read_block(BufReader::new(file))
+            .with_context(|| format!("Failed to decode block from {}", path.display()))?;
 
-        // Update last block hash and state root
-        let extrinsic_hash = hex::encode(block.header.extrinsic_hash.as_bytes());
-
-        info!("Block imported successfully: hash={extrinsic_hash}");
+        self.import_decoded(block)
+    }
 
-        self.last_block_hash = Some(*block.header.extrinsic_hash.as_bytes());
-        self.last_state_root = Some(*block.header.parent_state_root.as_bytes());
+    /// Import and validate a block held in memory, encoded with the given codec.
+    pub fn import_block_bytes(&mut self, bytes: &[u8], codec: Codec) -> Result<Block> {
+        debug!("Importing {} byte block ({codec:?})", bytes.len());
+        let block = codec
+            .decode_block(bytes)
+            .with_context(|| format!("Failed to decode {codec:?} block"))?;
+        self.import_decoded(block)
+    }
 
-        Ok(block)
+    /// Import and validate a block read from an arbitrary reader, such as a socket.
+    pub fn import_block_reader<R: Read>(&mut self, reader: R, codec: Codec) -> Result<Block> {
+        let block = codec
+            .read_block(reader)
+            .with_context(|| format!("Failed to decode {codec:?} block from reader"))?;
+        self.import_decoded(block)
     }
 
-    /// Validates and applies a block to the current state
+    /// Import and validate an already decoded block.
     ///
-    /// This performs all necessary validations in the correct order:
-    /// 1. Structural validation
-    /// 2. Header validation
-    // 3. Transaction validation
-    /// 4. State transition validation
-    fn validate_and_apply_block(&mut self, block: &Block) -> Result<()> {
-        debug!(
-            "Starting validation for block at slot {}",
-            block.header.slot
-        );
-
-        // 1. Validate block structure
-        self.validate_block_structure(block)?;
+    /// Import is transactional: the posterior state is computed by the pure [`stf::apply`]
+    /// and only enters the block tree once every sub-STF has succeeded. A rejected block
+    /// leaves the importer exactly as it was.
+    pub fn import_decoded(&mut self, block: Block) -> Result<Block> {
+        self.import_with_outputs(block).map(|(block, _)| block)
+    }
 
-        // 2. Validate header (includes parent hash, slot, etc.)
-        self.validate_header(&block.header)?;
+    /// Import an already decoded block and also return the per-subsystem STF outputs.
+    ///
+    /// The block may extend any block in the tree; the best head is re-selected afterwards.
+    pub fn import_with_outputs(&mut self, block: Block) -> Result<(Block, StfOutputs)> {
+        self.import_timed(block, &mut PhaseTimings::default())
+    }
 
-        // 3. Validate all transactions and their proofs
-        self.validate_extrinsic(&block.header, &block.extrinsic)?;
+    /// Import an ordered sequence of blocks: a directory of block files, a JSON block file
+    /// or a stream of concatenated binary blocks (see [`ChainSource`]).
+    ///
+    /// Every block after the first must build on the block imported just before it.
+    /// Invalid blocks stop the import or are skipped according to `policy`; internal
+    /// errors such as unreadable files or store failures always abort it. Blocks more
+    /// than [`IMPORT_FINALITY_LAG`] below the best head are finalized along the way.
+    pub fn import_chain<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        policy: InvalidBlockPolicy,
+    ) -> Result<ChainImportReport> {
+        let source = ChainSource::open(path)?;
+        self.import_chain_from(&source, policy)
+    }
 
-        // 3b. Validate CoreTime accounting and guarantees
-        self.coretime.validate_and_apply(
-            block.header.slot as u64,
-            &block.extrinsic.guarantees,
-            &block.extrinsic.assurances,
-            &block.extrinsic.disputes,
-        )?;
+    /// [`Importer::import_chain`] over an already opened source.
+    pub fn import_chain_from(
+        &mut self,
+        source: &ChainSource,
+        policy: InvalidBlockPolicy,
+    ) -> Result<ChainImportReport> {
+        let start = Instant::now();
+        let mut last_progress = start;
+        let mut report = ChainImportReport::default();
+        let mut previous: Option<OpaqueHash> = None;
+
+        for (label, block) in source.blocks() {
+            let slot = block.as_ref().ok().map(|block| block.header.slot);
+            let result = block.and_then(|block| {
+                if let Some(expected) = previous {
+                    if block.header.parent != expected {
+                        return Err(BlockchainError::ParentHashMismatch {
+                            expected: hex::encode(expected.as_bytes()),
+                            actual: hex::encode(block.header.parent.as_bytes()),
+                        }
+                        .into());
+                    }
+                }
+                self.import_timed(block, &mut report.phases)
+            });
+
+            match result {
+                Ok((block, _)) => {
+                    previous = Some(block.header.hash());
+                    report.imported += 1;
+                    report.last_slot = Some(block.header.slot);
+                    // Finalize in steps of the lag rather than on every block.
+                    if self.tree.best_node().depth >= 2 * IMPORT_FINALITY_LAG {
+                        let finalized = self.tree.ancestor(self.tree.best(), IMPORT_FINALITY_LAG);
+                        self.finalize(finalized, None)?;
+                    }
+                }
+                Err(error) if is_invalid_block(&error) => {
+                    warn!("Rejected block {label}: {error:#}");
+                    report.rejected.push(RejectedBlock {
+                        source: label,
+                        slot,
+                        error,
+                    });
+                    if policy == InvalidBlockPolicy::Stop {
+                        break;
+                    }
+                }
+                Err(error) => return Err(error.context(format!("Failed to import {label}"))),
+            }
 
-        // 4. Apply state transition
-        trace!(
-            "Applying state transition for block at slot {}",
-            block.header.slot
-        );
-        self.state.apply_block(block)?;
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                report.elapsed = start.elapsed();
+                info!(
+                    "Imported {} blocks, slot {}, {:.1} blocks/s",
+                    report.imported,
+                    report.last_slot.unwrap_or_default(),
+                    report.blocks_per_second()
+                );
+            }
+        }
 
-        debug!(
-            "Successfully validated and applied block at slot {}",
-            block.header.slot
+        report.elapsed = start.elapsed();
+        info!(
+            "Chain import done: {} blocks imported, {} rejected, {:.1} blocks/s",
+            report.imported,
+            report.rejected.len(),
+            report.blocks_per_second()
         );
-        Ok(())
+        Ok(report)
     }
 
-    /// Validates the structural integrity of the block
-    fn validate_block_structure(&self, block: &Block) -> Result<()> {
-        // Check header has a valid slot number
-        if block.header.slot == 0 {
-            return Err(BlockchainError::InvalidSlot {
-                last_slot: 0,
-                current_slot: 0,
+    fn import_timed(
+        &mut self,
+        block: Block,
+        timings: &mut PhaseTimings,
+    ) -> Result<(Block, StfOutputs)> {
+        let (hash, slot) = (block.header.hash(), block.header.slot);
+        let result = self.import_unobserved(block, timings);
+        if let Err(error) = &result {
+            if is_invalid_block(error) {
+                self.observers
+                    .notify(&ImportEvent::BlockRejected { hash, slot, error });
             }
-            .into());
         }
+        result
+    }
 
-        // Validate extrinsic structure
-        // In v0.8: tickets_mark = null means no epoch transition, but tickets may still exist
-        // Only validate ticket count when tickets_mark is Some (epoch transition)
-        if let Some(tickets_mark) = &block.header.tickets_mark {
-            if tickets_mark.len() != block.extrinsic.tickets.len() {
-                return Err(BlockchainError::InvalidBlockStructure {
-                    reason: format!(
-                        "Ticket count mismatch: header marks {} tickets but found {}",
-                        tickets_mark.len(),
-                        block.extrinsic.tickets.len()
-                    ),
-                }
-                .into());
-            }
+    fn import_unobserved(
+        &mut self,
+        block: Block,
+        timings: &mut PhaseTimings,
+    ) -> Result<(Block, StfOutputs)> {
+        let hash = block.header.hash();
+        let parent = self.parent_of_new(&block)?;
+        let prior = &self.tree.get(&parent).expect("resolved parent").state;
+        let prior_slot = prior.get_last_slot();
+        if let Some(verifier) = self.verifier.as_mut() {
+            let start = Instant::now();
+            let validators = stf::signing_validators(prior, &block.header);
+            let verified = collect_checks(&block.extrinsic, &validators)
+                .and_then(|checks| verifier.verify(&checks));
+            timings.extrinsic += start.elapsed();
+            verified.with_context(|| "Block validation failed")?;
         }
+        let (posterior, outputs) = stf::apply_timed(prior, &block, &self.spec, timings)
+            .with_context(|| "Block validation failed")?;
+
+        // The block and its state delta are durable before the tree (and head) moves.
+        if let Some(store) = self.store.as_deref_mut() {
+            let mut batch = WriteBatch::new();
+            let state_root = store::stage_state(store, &mut batch, &posterior)?;
+            batch.put_block(&block, state_root);
+            store.commit(batch).with_context(|| {
+                format!("Failed to persist block {}", hex::encode(hash.as_bytes()))
+            })?;
+        }
+
+        let state_root = posterior.last_state_root;
+        let reorg = self.tree.insert(
+            parent,
+            block.header.clone(),
+            posterior,
+            outputs.ticket_sealed,
+        );
 
-        // Validate preimages if any
-        for (i, preimage) in block.extrinsic.preimages.iter().enumerate() {
-            if preimage.blob.is_empty() {
-                return Err(BlockchainError::InvalidPreimage {
-                    reason: format!("Preimage at index {} has empty blob", i),
+        info!(
+            "Block imported successfully: hash={} best={}{}",
+            hex::encode(hash.as_bytes()),
+            hex::encode(self.tree.best().as_bytes()),
+            if reorg { " (new best)" } else { "" }
+        );
+
+        // The head pointer is advisory: reopening re-runs fork choice over stored blocks.
+        if reorg {
+            if let Some(store) = self.store.as_deref_mut() {
+                let mut batch = WriteBatch::new();
+                batch.put_meta(store::META_BEST_HEAD, &self.tree.best().encode());
+                if let Err(e) = store.commit(batch) {
+                    warn!("Failed to persist best head: {e}");
                 }
-                .into());
             }
         }
 
-        Ok(())
+        self.notify_imported(&block, &outputs, prior_slot, state_root, reorg);
+        Ok((block, outputs))
     }
 
-    /// Validates block header with comprehensive checks
+    /// Verify every signature and proof in a block's extrinsic before applying it.
     ///
-    /// This includes:
-    /// - Slot progression validation
-    /// - Parent hash validation
-    /// - State root validation
-    /// - Entropy validation
-    /// - Epoch mark validation (if present)
-    fn validate_header(&self, header: &Header) -> Result<()> {
-        let current_slot = u64::from(header.slot);
-        let last_slot = self.state.get_last_slot();
-
-        trace!(
-            "Validating header for slot {} (last slot: {})",
-            current_slot,
-            last_slot
-        );
+    /// Checks are collected up front and verified as a batch according to `config`;
+    /// results are cached across blocks, so forks sharing extrinsic items verify them once.
+    pub fn enable_verification(&mut self, config: VerificationConfig) {
+        self.verifier = Some(SignatureVerifier::new(config));
+    }
 
-        // Slot validation
-        if current_slot <= last_slot {
-            warn!("Invalid slot progression: current {current_slot} <= last {last_slot}");
-            return Err(BlockchainError::InvalidSlot {
-                last_slot,
-                current_slot,
-            }
-            .into());
-        }
+    /// Register an observer for import, rejection and finality events.
+    pub fn observe(&mut self, observer: impl ImportObserver + 'static) {
+        self.observers.push(Box::new(observer));
+    }
 
-        // Parent hash validation
-        if let Some(last_hash) = self.last_block_hash {
-            let parent_hash = header.parent.as_bytes();
-            if parent_hash != &last_hash {
-                warn!(
-                    "Parent hash mismatch: expected {}, got {}",
-                    hex::encode(last_hash),
-                    hex::encode(parent_hash)
-                );
-                return Err(BlockchainError::ParentHashMismatch {
-                    expected: hex::encode(last_hash),
-                    actual: hex::encode(parent_hash),
-                }
-                .into());
-            }
+    /// Tell the observers about a block just inserted into the tree.
+    fn notify_imported(
+        &mut self,
+        block: &Block,
+        outputs: &StfOutputs,
+        prior_slot: u64,
+        state_root: Option<[u8; 32]>,
+        best: bool,
+    ) {
+        if self.observers.is_empty() {
+            return;
         }
+        let hash = block.header.hash();
+        let slot = block.header.slot;
+        let mut events = vec![ImportEvent::BlockImported {
+            hash,
+            slot,
+            state_root,
+            best,
+        }];
+
+        let epoch = self.spec.epoch_of(u64::from(slot));
+        if epoch != self.spec.epoch_of(prior_slot) {
+            events.push(ImportEvent::EpochChanged { epoch, slot });
+        }
+        if !outputs.offenders.is_empty() {
+            events.push(ImportEvent::Offenders {
+                hash,
+                offenders: &outputs.offenders,
+            });
+        }
+        events.extend(
+            outputs
+                .reported
+                .iter()
+                .map(|package| ImportEvent::WorkReported { hash, package }),
+        );
 
-        // Parent state root validation
-        if let Some(last_root) = self.last_state_root {
-            if header.parent_state_root.as_bytes() != &last_root {
-                warn!(
-                    "Parent state root mismatch: expected {}, got {}",
-                    hex::encode(last_root),
-                    hex::encode(header.parent_state_root.as_bytes())
-                );
-                return Err(BlockchainError::ParentStateRootMismatch {
-                    expected: hex::encode(last_root),
-                    actual: hex::encode(header.parent_state_root.as_bytes()),
-                }
-                .into());
+        let coretime = &self.tree.get(&hash).expect("imported block").state.coretime;
+        let mut busy_cores: Vec<_> = coretime
+            .block_consumption(u64::from(slot))
+            .filter(|(_, consumed)| approaches_limit(*consumed, MAX_CORETIME_PER_CORE))
+            .collect();
+        busy_cores.sort_unstable();
+        events.extend(busy_cores.into_iter().map(|(core, consumed)| {
+            ImportEvent::CoreTimeLimitApproached {
+                slot,
+                core: Some(core),
+                consumed,
+                limit: MAX_CORETIME_PER_CORE,
             }
+        }));
+        if approaches_limit(outputs.coretime_consumed, MAX_CORETIME_PER_BLOCK) {
+            events.push(ImportEvent::CoreTimeLimitApproached {
+                slot,
+                core: None,
+                consumed: outputs.coretime_consumed,
+                limit: MAX_CORETIME_PER_BLOCK,
+            });
         }
 
-        // Entropy validation
-        // Validate entropy source (result is ignored as we only care about the error case)
-        let _ = header
-            .validate_entropy()
-            .map_err(|e| BlockchainError::InvalidEntropy {
-                reason: format!("Invalid entropy source: {}", e),
-            })?;
-
-        trace!("Entropy validation passed for slot {}", current_slot);
-
-        info!("Header validation passed for slot {current_slot}");
+        for event in &events {
+            self.observers.notify(event);
+        }
+    }
 
-        // Optional additional validations
-        if let Some(epoch_mark) = &header.epoch_mark {
-            if header.author_index as usize >= epoch_mark.validators.len() {
-                warn!(
-                    "Author index {} out of bounds (max {})",
-                    header.author_index,
-                    epoch_mark.validators.len() - 1
+    /// Run every import check on `block` without importing it.
+    ///
+    /// The block is validated against its parent's state exactly as
+    /// [`Importer::import_decoded`] would, but the tree, the store and the best head are
+    /// left untouched.
+    pub fn check_block(&self, block: &Block) -> ValidationReport {
+        let mut report = ValidationReport {
+            hash: block.header.hash(),
+            slot: block.header.slot,
+            error: None,
+            state_root: None,
+            gas_used: 0,
+            coretime_consumed: 0,
+            phases: PhaseTimings::default(),
+        };
+
+        let result = self.parent_of_new(block).and_then(|parent| {
+            let prior = &self.tree.get(&parent).expect("resolved parent").state;
+            if let Some(verifier) = &self.verifier {
+                let validators = stf::signing_validators(prior, &block.header);
+                collect_checks(&block.extrinsic, &validators)
+                    .and_then(|checks| verifier.check(&checks))
+                    .with_context(|| "Block validation failed")?;
+            }
+            stf::apply_timed(prior, block, &self.spec, &mut report.phases)
+                .with_context(|| "Block validation failed")
+        });
+        match result {
+            Ok((posterior, outputs)) => {
+                report.state_root = posterior.last_state_root;
+                report.gas_used = outputs.gas_used;
+                report.coretime_consumed = outputs.coretime_consumed;
+            }
+            Err(error) => {
+                debug!(
+                    "Block {} would be rejected: {error:#}",
+                    hex::encode(report.hash.as_bytes())
                 );
-                return Err(BlockchainError::InvalidAuthorIndex {
-                    author_index: header.author_index as u64,
-                    max_validators: epoch_mark.validators.len(),
-                }
-                .into());
+                report.error = Some(error);
             }
         }
-
-        Ok(())
+        report
     }
 
-    /// Validates all transactions in the extrinsic
+    /// Mark a block as finalized.
     ///
-    /// This includes:
-    /// - Ticket validation against marks
-    /// - Signature verification
-    /// - Transaction inclusion proofs
-    fn validate_extrinsic(&self, header: &Header, extrinsic: &Extrinsic) -> Result<()> {
-        debug!(
-            "Validating extrinsic with {} tickets",
-            extrinsic.tickets.len()
-        );
-
-        // Validate tickets against marks if marks exist
-        if let Some(tickets_mark) = &header.tickets_mark {
-            // This check is redundant with validate_block_structure but kept for defense in depth
-            if tickets_mark.len() != extrinsic.tickets.len() {
-                return Err(BlockchainError::TicketValidationError {
+    /// If a justification is given it must name the block and be signed by a supermajority
+    /// of the validators active in that block's state. Competing branches are discarded
+    /// along with their states. With a store, the new finalized block is recorded there,
+    /// pruned blocks are deleted and the trie nodes only their states or the finalized
+    /// block's ancestors used are swept; the finalized chain's blocks are kept. Returns
+    /// the hashes of the blocks pruned from competing branches.
+    pub fn finalize(
+        &mut self,
+        hash: OpaqueHash,
+        justification: Option<&Justification>,
+    ) -> Result<Vec<OpaqueHash>> {
+        let node = self
+            .tree
+            .get(&hash)
+            .ok_or_else(|| BlockchainError::UnknownParent {
+                parent: hex::encode(hash.as_bytes()),
+            })
+            .context("Cannot finalize unknown block")?;
+
+        if let Some(justification) = justification {
+            if justification.block_hash != hash {
+                return Err(BlockchainError::InvalidJustification {
                     reason: format!(
-                        "Ticket count mismatch: expected {}, got {}",
-                        tickets_mark.len(),
-                        extrinsic.tickets.len()
+                        "Justification targets {} not {}",
+                        hex::encode(justification.block_hash.as_bytes()),
+                        hex::encode(hash.as_bytes())
                     ),
                 }
                 .into());
             }
+            justification.verify(&node.state.active_validators)?;
+        }
 
-            // Validate each ticket against its mark
-            for (i, (ticket, mark)) in extrinsic
-                .tickets
-                .iter()
-                .zip(tickets_mark.iter())
-                .enumerate()
-            {
-                if ticket.attempt != mark.attempt {
-                    return Err(BlockchainError::TicketValidationError {
-                        reason: format!(
-                            "Ticket {} attempt mismatch: expected {}, got {}",
-                            i, mark.attempt, ticket.attempt
-                        ),
-                    }
-                    .into());
-                }
+        let pruned = self.tree.prune_to(hash);
+        info!(
+            "Finalized block {} ({} fork blocks pruned)",
+            hex::encode(hash.as_bytes()),
+            pruned.len()
+        );
 
-                // Additional ticket validation
-                if let Err(e) = ticket.validate() {
-                    return Err(BlockchainError::TicketValidationError {
-                        reason: format!("Invalid ticket at index {}: {}", i, e),
-                    }
-                    .into());
-                }
-            }
-        } else {
-            // In v0.8: tickets_mark = null means no epoch transition
-            // Tickets may still be present for other purposes, so don't reject them
-            // Just validate individual ticket signatures
-            for (i, ticket) in extrinsic.tickets.iter().enumerate() {
-                if let Err(e) = ticket.validate() {
-                    return Err(BlockchainError::TicketValidationError {
-                        reason: format!("Invalid ticket at index {}: {}", i, e),
-                    }
-                    .into());
-                }
+        self.persist_root(&pruned)?;
+
+        let slot = self
+            .tree
+            .get(&hash)
+            .expect("finalized block is the root")
+            .slot();
+        self.observers.notify(&ImportEvent::Finalized {
+            hash,
+            slot,
+            pruned: &pruned,
+        });
+        Ok(pruned)
+    }
+
+    /// Record the tree root, its state, the best head and the removal of `pruned` blocks
+    /// in the store, if there is one, as a single commit.
+    ///
+    /// Trie nodes reachable from neither the root state nor a state in the tree are swept
+    /// in the same commit.
+    fn persist_root(&mut self, pruned: &[OpaqueHash]) -> Result<()> {
+        let Some(store) = self.store.as_deref_mut() else {
+            return Ok(());
+        };
+        let root = self.tree.get(&self.tree.root()).expect("tree root");
+        let mut batch = WriteBatch::new();
+        let state_root = store::stage_state(store, &mut batch, &root.state)?;
+        let record = FinalizedRecord {
+            hash: self.tree.root(),
+            header: root.header.clone(),
+            state_root,
+            last_state_root: root.state.last_state_root,
+        };
+        batch.put_meta(store::META_FINALIZED, &record.encode());
+        for hash in pruned {
+            batch.delete_block(*hash);
+        }
+        let mut live = vec![state_root];
+        for hash in self.tree.hashes() {
+            if let Some(stored) = store.get_block(hash)? {
+                live.push(stored.state_root);
             }
         }
+        let swept = store::stage_prune(store, &mut batch, &live)?;
+        debug!("Sweeping {swept} trie nodes of pruned states");
+        batch.put_meta(store::META_BEST_HEAD, &self.tree.best().encode());
+        store
+            .commit(batch)
+            .context("Failed to persist finalized head")
+    }
 
-        // Validate preimages
-        for (i, preimage) in extrinsic.preimages.iter().enumerate() {
-            if preimage.requester == 0 {
-                return Err(BlockchainError::InvalidPreimage {
-                    reason: format!("Preimage at index {} has invalid requester ID 0", i),
-                }
-                .into());
+    /// Find the tree node a block not yet in the tree builds on.
+    fn parent_of_new(&self, block: &Block) -> Result<OpaqueHash> {
+        let hash = block.header.hash();
+        if self.tree.contains(&hash) {
+            return Err(BlockchainError::InvalidBlockStructure {
+                reason: format!("Block {} already imported", hex::encode(hash.as_bytes())),
             }
+            .into());
         }
+        self.resolve_parent(&block.header)
+    }
 
-        // Validate extrinsic hash commitment in header
-        self.validate_extrinsic_hash(header, extrinsic)?;
-
-        debug!(
-            "Extrinsic validation passed with {} tickets and {} preimages",
-            extrinsic.tickets.len(),
-            extrinsic.preimages.len()
-        );
+    /// Find the tree node a header builds on.
+    fn resolve_parent(&self, header: &Header) -> Result<OpaqueHash> {
+        if self.tree.contains(&header.parent) {
+            return Ok(header.parent);
+        }
 
-        Ok(())
-    }
+        // An unanchored root accepts whichever block comes first as its child.
+        let root = self.tree.get(&self.tree.root()).expect("tree root");
+        if root.state.last_block_hash.is_none() && root.children.is_empty() {
+            debug!(
+                "Anchoring chain at parent {}",
+                hex::encode(header.parent.as_bytes())
+            );
+            return Ok(self.tree.root());
+        }
 
-    fn validate_extrinsic_hash(&self, header: &Header, extrinsic: &Extrinsic) -> Result<()> {
-        // TODO: Fix extrinsic hash computation - current JSON serialization doesn't match
-        // official test vectors which likely use SCALE-like encoding
-        // For now, skip this validation to focus on basic import functionality
-        trace!("Skipping extrinsic hash validation - codec needs v0.8 alignment");
-        Ok(())
+        warn!(
+            "Unknown parent {} for block at slot {}",
+            hex::encode(header.parent.as_bytes()),
+            header.slot
+        );
+        Err(BlockchainError::UnknownParent {
+            parent: hex::encode(header.parent.as_bytes()),
+        }
+        .into())
     }
 
+    /// Posterior state of the best head.
     pub fn state(&self) -> &State {
-        &self.state
+        &self.tree.best_node().state
     }
 }
 
@@ -2,11 +2,41 @@
 //!
 //! This platform focuses on lightweight design, decentralization, and post-quantum cryptography.
 
+pub mod audit;
+pub mod authorer;
+pub mod availability;
+pub mod block_tree;
+pub mod chain_import;
+pub mod chain_spec;
+pub mod codec;
+pub mod conformance;
 pub mod coretime;
+pub mod erasure;
+pub mod error_code;
+pub mod events;
+pub mod finality;
+pub mod genesis;
 pub mod importer;
+pub mod light;
+pub mod merkle;
+pub mod proof;
+pub mod refine;
 pub mod schema;
+pub mod snapshot;
 pub mod state;
+pub mod state_diff;
+pub mod state_keys;
+pub mod stf;
+pub mod store;
+pub mod trace;
+pub mod vectors;
+pub mod verification;
+pub mod wal;
+pub mod work_package;
 
+pub use chain_spec::ChainSpec;
+pub use codec::Codec;
+pub use genesis::Genesis;
 pub use importer::Importer;
 
 #[cfg(test)]
@@ -0,0 +1,231 @@
+//! Header-only light client for devices that cannot hold the full state.
+//!
+//! A [`LightClient`] follows a single chain of headers. It checks slot progression, parent
+//! linkage, the author, seal and entropy source against the validator set it tracks, and
+//! hands the set over at `epoch_mark` transitions the same way the full STF does. Only
+//! the most recent headers and the current and next validator keys are kept; state
+//! values are checked on demand against a header's `parent_state_root` with a
+//! [`StateProof`] served by a full node.
+
+use std::collections::VecDeque;
+
+use log::{debug, info, warn};
+
+use crate::authorer::{verify_author, EpochSealing};
+use crate::chain_spec::ChainSpec;
+use crate::genesis::Genesis;
+use crate::merkle::StateKey;
+use crate::proof::{verify_proof, StateProof};
+use crate::schema::{BlockchainError, Header, OpaqueHash, TicketBody};
+use crate::stf::rotate_validators;
+
+/// Recent headers a light client keeps by default.
+pub const DEFAULT_RETAINED_HEADERS: usize = 64;
+
+/// Follows the header chain without state.
+///
+/// Memory Usage:
+/// - Headers: up to `retain` headers (~300 bytes each without marks)
+/// - Validators: 32 bytes per key for the current and next sets
+/// - Tickets: 33 bytes per sealing ticket of the current and next epochs
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    spec: ChainSpec,
+    /// Recent headers with their hashes, oldest first.
+    headers: VecDeque<(OpaqueHash, Header)>,
+    retain: usize,
+    head: OpaqueHash,
+    head_slot: u64,
+    active_validators: Vec<OpaqueHash>,
+    pending_validators: Vec<OpaqueHash>,
+    /// Entropy and tickets sealing the current epoch.
+    sealing: EpochSealing,
+    /// Tickets announced by a `tickets_mark` for the next epoch.
+    next_tickets: Vec<TicketBody>,
+}
+
+impl LightClient {
+    /// Start following the chain at block `head`, at `slot`, with the given current and
+    /// next validator sets.
+    pub fn new(
+        spec: ChainSpec,
+        head: OpaqueHash,
+        slot: u64,
+        active_validators: Vec<OpaqueHash>,
+        pending_validators: Vec<OpaqueHash>,
+    ) -> Self {
+        LightClient {
+            spec,
+            headers: VecDeque::new(),
+            retain: DEFAULT_RETAINED_HEADERS,
+            head,
+            head_slot: slot,
+            active_validators,
+            pending_validators,
+            sealing: EpochSealing::default(),
+            next_tickets: Vec::new(),
+        }
+    }
+
+    /// Start following the chain at its genesis block.
+    pub fn from_genesis(spec: ChainSpec, genesis: &Genesis) -> Self {
+        let state = genesis.initial_state();
+        let sealing = EpochSealing::of_state(&state);
+        let mut client = Self::new(
+            spec,
+            genesis.hash(),
+            u64::from(genesis.header.slot),
+            state.active_validators,
+            state.pending_validators,
+        );
+        client.sealing = sealing;
+        client.next_tickets = state.ticket_state.next_tickets;
+        client.remember(genesis.hash(), genesis.header.clone());
+        client
+    }
+
+    /// Keep at most `retain` recent headers (at least one).
+    pub fn with_retention(mut self, retain: usize) -> Self {
+        self.retain = retain.max(1);
+        while self.headers.len() > self.retain {
+            self.headers.pop_front();
+        }
+        self
+    }
+
+    /// Check seals of the current epoch against `sealing` instead of zero entropy and
+    /// fallback keys.
+    pub fn with_sealing(mut self, sealing: EpochSealing) -> Self {
+        self.sealing = sealing;
+        self
+    }
+
+    pub fn head(&self) -> OpaqueHash {
+        self.head
+    }
+
+    pub fn head_slot(&self) -> u64 {
+        self.head_slot
+    }
+
+    /// Validator keys of the current epoch.
+    pub fn validators(&self) -> &[OpaqueHash] {
+        &self.active_validators
+    }
+
+    /// Validator keys announced for the next epoch.
+    pub fn pending_validators(&self) -> &[OpaqueHash] {
+        &self.pending_validators
+    }
+
+    /// A retained header by hash.
+    pub fn header(&self, hash: &OpaqueHash) -> Option<&Header> {
+        self.headers
+            .iter()
+            .find(|(retained, _)| retained == hash)
+            .map(|(_, header)| header)
+    }
+
+    /// Number of retained headers.
+    pub fn retained(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Validate `header` as the next header of the chain and make it the head.
+    pub fn import_header(&mut self, header: Header) -> Result<OpaqueHash, BlockchainError> {
+        let slot = u64::from(header.slot);
+        if slot <= self.head_slot {
+            return Err(BlockchainError::InvalidSlot {
+                last_slot: self.head_slot,
+                current_slot: slot,
+            });
+        }
+
+        if header.parent != self.head {
+            warn!(
+                "Header at slot {slot} does not extend head {}",
+                hex::encode(self.head.as_bytes())
+            );
+            return Err(BlockchainError::ParentHashMismatch {
+                expected: hex::encode(self.head.as_bytes()),
+                actual: hex::encode(header.parent.as_bytes()),
+            });
+        }
+
+        // The first header of an epoch is sealed by the keys and entropy it announces.
+        let epoch = self.spec.epoch_of(slot);
+        if header.epoch_mark.is_some() && epoch == self.spec.epoch_of(self.head_slot) {
+            return Err(BlockchainError::InvalidBlockStructure {
+                reason: format!("Epoch mark at slot {slot} within epoch {epoch}"),
+            });
+        }
+        let sealing = self.sealing.advance(
+            &self.spec,
+            self.head_slot,
+            &self.next_tickets,
+            header.slot,
+            header.epoch_mark.as_ref(),
+        );
+        let rotated = header.epoch_mark.as_ref().map(|epoch_mark| {
+            let mut active = self.active_validators.clone();
+            let mut pending = self.pending_validators.clone();
+            rotate_validators(&mut active, &mut pending, epoch_mark);
+            (active, pending)
+        });
+        let validators = rotated
+            .as_ref()
+            .map_or(&self.active_validators, |(active, _)| active);
+        verify_author(&self.spec, &header, validators, &sealing)?;
+
+        if epoch != self.spec.epoch_of(self.head_slot) {
+            self.sealing = sealing;
+            self.next_tickets.clear();
+        }
+        if let Some((active, pending)) = rotated {
+            self.active_validators = active;
+            self.pending_validators = pending;
+            info!(
+                "Light client entered epoch {epoch} with {} validators",
+                self.active_validators.len()
+            );
+        }
+        if let Some(tickets_mark) = &header.tickets_mark {
+            self.next_tickets = tickets_mark.clone();
+        }
+
+        let hash = header.hash();
+        self.head = hash;
+        self.head_slot = slot;
+        self.remember(hash, header);
+        debug!(
+            "Light client head {} at slot {slot}",
+            hex::encode(hash.as_bytes())
+        );
+        Ok(hash)
+    }
+
+    fn remember(&mut self, hash: OpaqueHash, header: Header) {
+        self.headers.push_back((hash, header));
+        while self.headers.len() > self.retain {
+            self.headers.pop_front();
+        }
+    }
+
+    /// Check that `key` held `value` (`None`: was absent) in the state named by the
+    /// `parent_state_root` of the retained header `hash`, i.e. the posterior state of its
+    /// parent.
+    pub fn verify_state(
+        &self,
+        hash: &OpaqueHash,
+        key: &StateKey,
+        value: Option<&[u8]>,
+        proof: &StateProof,
+    ) -> Result<(), BlockchainError> {
+        let header = self
+            .header(hash)
+            .ok_or_else(|| BlockchainError::InvalidStateProof {
+                reason: format!("Header {} is not retained", hex::encode(hash.as_bytes())),
+            })?;
+        verify_proof(header.parent_state_root.as_bytes(), proof, key, value)
+    }
+}
@@ -1,28 +1,654 @@
-use anyhow::Result;
-use jamliquor::Importer;
-use std::path::PathBuf;
-
-fn main() -> Result<()> {
-    let mut importer = Importer::new();
-    let block_path = PathBuf::from("tests/vectors/codec/tiny/block.json");
-    let block = importer.import_block(&block_path)?;
-    println!("Block: {block:?}");
-    println!("State: {:?}", importer.state());
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use jamliquor::chain_import::InvalidBlockPolicy;
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::codec::{decode_exact, Decode, Encode};
+use jamliquor::conformance;
+use jamliquor::error_code::error_code;
+use jamliquor::genesis::{parse_keyvals, Genesis};
+use jamliquor::merkle::{merklize, KeyValues};
+use jamliquor::schema::{Block, BlockchainError, Header, OpaqueHash};
+use jamliquor::snapshot::{Snapshot, SnapshotAnchor, SNAPSHOT_MAGIC};
+use jamliquor::state_diff::diff_key_values;
+use jamliquor::state_keys::describe_key;
+use jamliquor::store::{self, FileStore, Store};
+use jamliquor::trace::StepVerdict;
+use jamliquor::{trace, vectors, Codec, Importer};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Exit code for internal errors: I/O, storage and anything else not caused by the input.
+const EXIT_INTERNAL_ERROR: u8 = 1;
+/// Exit code for a block (or other input) that fails to decode or validate.
+const EXIT_INVALID_BLOCK: u8 = 3;
+/// Exit code for vectors or traces whose results differ from the expected ones.
+const EXIT_MISMATCH: u8 = 4;
+
+#[derive(Parser)]
+#[command(
+    name = "jamliquor",
+    version,
+    about,
+    after_help = "Exit codes: 0 success, 1 internal error, 2 usage error, 3 invalid block, \
+                  4 conformance mismatch"
+)]
+struct Cli {
+    /// Chain spec to run with (tiny or full)
+    #[arg(long, global = true, default_value = "tiny", value_parser = parse_chain_spec)]
+    chain_spec: ChainSpec,
+    /// Data directory of the persistent block and state store
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Log output format; the level is taken from `RUST_LOG`
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Wire types `decode` and `encode` convert.
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Block,
+    Header,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Import blocks from files or directories, in order
+    Import {
+        /// Block files (`.json`, or `.jlb` streams of concatenated blocks) or directories
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Genesis file to start from when the data directory is empty
+        #[arg(long)]
+        genesis: Option<PathBuf>,
+        /// Skip invalid blocks instead of stopping at the first one
+        #[arg(long)]
+        skip_invalid: bool,
+    },
+    /// Convert a value in the internal binary format (`.jlb`, not the GP codec) to JSON
+    Decode {
+        input: PathBuf,
+        #[arg(long, value_enum, default_value_t = Kind::Block)]
+        kind: Kind,
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Convert a JSON value to the internal binary format (`.jlb`, not the GP codec)
+    Encode {
+        input: PathBuf,
+        #[arg(long, value_enum, default_value_t = Kind::Block)]
+        kind: Kind,
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Summarize a header, block or state file
+    Inspect {
+        #[command(subcommand)]
+        what: InspectCommand,
+    },
+    /// Print the Merkle root of a state file (snapshot, genesis or raw key/values)
+    StateRoot { file: PathBuf },
+    /// Compare two state files key by key and print decoded differences
+    Diff {
+        /// State the comparison is against (snapshot, genesis or raw key/values)
+        expected: PathBuf,
+        actual: PathBuf,
+    },
+    /// Run a category of jam-test-vectors (`codec`, `traces`, `erasure` or a subdirectory)
+    ///
+    /// `codec` only supports self-generated JSON/binary pairs (from `encode`): the binary
+    /// format is internal, so upstream GP-encoded `.bin` files do not match.
+    RunVectors {
+        category: String,
+        /// Root of the jam-test-vectors checkout
+        #[arg(long, default_value = "tests/vectors")]
+        vectors_dir: PathBuf,
+    },
+    /// Replay a multi-block trace directory and compare every posterior state
+    ReplayTrace {
+        /// Directory with an optional genesis.json and numbered step files
+        dir: PathBuf,
+    },
+    /// Serve the internal fuzzing harness (not the JAM conformance fuzzer wire format) on a
+    /// Unix domain socket
+    Target {
+        /// Socket path to listen on
+        socket: PathBuf,
+    },
+    /// Export, import and verify state snapshots
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum InspectCommand {
+    /// A header file (`.json` or `.jlb`)
+    Header { file: PathBuf },
+    /// A block file (`.json` or `.jlb`)
+    Block { file: PathBuf },
+    /// A snapshot, genesis or raw key/value state file
+    State { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Write the state at a block (default: the finalized head) to a snapshot file
+    Export {
+        /// Snapshot file to write
+        out: PathBuf,
+        /// Hex hash of the block to export
+        #[arg(long)]
+        block: Option<String>,
+    },
+    /// Verify a snapshot and import it into an empty data directory
+    Import {
+        snapshot: PathBuf,
+        #[command(flatten)]
+        anchor: AnchorArgs,
+    },
+    /// Check a snapshot against a trusted anchor and its key/values against its state root
+    Verify {
+        snapshot: PathBuf,
+        #[command(flatten)]
+        anchor: AnchorArgs,
+    },
+}
+
+/// Trusted block a snapshot is checked against: a finalized hash and state root, or a
+/// child header.
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct AnchorArgs {
+    /// Hex hash of the finalized block the snapshot must be taken at
+    #[arg(long, requires = "state_root", conflicts_with = "child")]
+    finalized: Option<String>,
+    /// Hex root of the finalized block's posterior state
+    #[arg(long, requires = "finalized")]
+    state_root: Option<String>,
+    /// Header file (`.json` or `.jlb`) of a child of the snapshot's block
+    #[arg(long)]
+    child: Option<PathBuf>,
+}
+
+impl AnchorArgs {
+    fn anchor(&self) -> Result<SnapshotAnchor> {
+        match (&self.finalized, &self.state_root, &self.child) {
+            (Some(hash), Some(state_root), None) => Ok(SnapshotAnchor::Finalized {
+                hash: OpaqueHash::from_hex(hash)?,
+                state_root: *OpaqueHash::from_hex(state_root)?.as_bytes(),
+            }),
+            (None, None, Some(child)) => Ok(SnapshotAnchor::Child(Box::new(read_value(child)?))),
+            _ => bail!("pass `--finalized` with `--state-root`, or `--child`"),
+        }
+    }
+}
+
+/// Results differ from the expected ones.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct Mismatch(String);
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(cli.log_format);
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            if let Some(code) = error_code(&e) {
+                eprintln!("Error code: {code}");
+            }
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(error: &anyhow::Error) -> u8 {
+    if error.is::<Mismatch>() {
+        return EXIT_MISMATCH;
+    }
+    match error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+    {
+        Some(cause) if cause.is_invalid_block() => EXIT_INVALID_BLOCK,
+        _ => EXIT_INTERNAL_ERROR,
+    }
+}
+
+fn parse_chain_spec(name: &str) -> Result<ChainSpec, String> {
+    ChainSpec::by_name(name).ok_or_else(|| format!("unknown chain spec `{name}` (tiny or full)"))
+}
+
+fn init_logging(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let LogFormat::Json = format {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    builder.init();
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let spec = cli.chain_spec;
+    let data_dir = cli.data_dir.as_deref();
+    match cli.command {
+        Command::Import {
+            paths,
+            genesis,
+            skip_invalid,
+        } => {
+            let policy = if skip_invalid {
+                InvalidBlockPolicy::Skip
+            } else {
+                InvalidBlockPolicy::Stop
+            };
+            import(spec, data_dir, &paths, genesis.as_deref(), policy)
+        }
+        Command::Decode { input, kind, out } => match kind {
+            Kind::Block => decode::<Block>(&input, out.as_deref()),
+            Kind::Header => decode::<Header>(&input, out.as_deref()),
+        },
+        Command::Encode { input, kind, out } => match kind {
+            Kind::Block => encode::<Block>(&input, out.as_deref()),
+            Kind::Header => encode::<Header>(&input, out.as_deref()),
+        },
+        Command::Inspect { what } => inspect(what, &spec),
+        Command::StateRoot { file } => {
+            println!("0x{}", hex::encode(merklize(&load_key_values(&file)?)));
+            Ok(())
+        }
+        Command::Diff { expected, actual } => {
+            let diff = diff_key_values(&load_key_values(&expected)?, &load_key_values(&actual)?);
+            println!("{diff}");
+            if diff.is_empty() {
+                return Ok(());
+            }
+            Err(Mismatch(format!(
+                "{} and {} differ in {} keys",
+                expected.display(),
+                actual.display(),
+                diff.len()
+            ))
+            .into())
+        }
+        Command::RunVectors {
+            category,
+            vectors_dir,
+        } => run_vectors(&vectors_dir, &category, &spec),
+        Command::ReplayTrace { dir } => replay_trace(&dir, &spec),
+        Command::Target { socket } => conformance::listen(&socket, spec),
+        Command::Snapshot { action } => snapshot(action, data_dir),
+    }
+}
+
+fn import(
+    spec: ChainSpec,
+    data_dir: Option<&Path>,
+    paths: &[PathBuf],
+    genesis: Option<&Path>,
+    policy: InvalidBlockPolicy,
+) -> Result<()> {
+    let genesis = genesis
+        .map(|path| {
+            Genesis::load(path, &spec)
+                .with_context(|| format!("Failed to load genesis {}", path.display()))
+        })
+        .transpose()?;
+    let mut importer = match (data_dir, &genesis) {
+        (Some(dir), Some(genesis)) => Importer::open_with_genesis(spec, dir, genesis)?,
+        (Some(dir), None) => Importer::open(spec, dir)?,
+        (None, Some(genesis)) => Importer::from_genesis(spec, genesis),
+        (None, None) => Importer::with_spec(spec),
+    };
+
+    let mut rejected = Vec::new();
+    for path in paths {
+        let report = importer.import_chain(path, policy)?;
+        println!(
+            "{}: {} blocks imported up to slot {} ({:.1} blocks/s)",
+            path.display(),
+            report.imported,
+            report
+                .last_slot
+                .map_or("-".to_string(), |slot| slot.to_string()),
+            report.blocks_per_second()
+        );
+        for (phase, time) in report.phases.phases() {
+            println!("  {phase:<12} {time:?}");
+        }
+        for block in &report.rejected {
+            println!("  rejected {}: {:#}", block.source, block.error);
+        }
+        rejected.extend(report.rejected);
+        if policy == InvalidBlockPolicy::Stop && !rejected.is_empty() {
+            break;
+        }
+    }
+    println!(
+        "Best head 0x{} at slot {}",
+        hex::encode(importer.best_head().as_bytes()),
+        importer.state().get_last_slot()
+    );
+    match rejected.into_iter().next() {
+        Some(first) => Err(first
+            .error
+            .context(format!("Invalid block {}", first.source))),
+        None => Ok(()),
+    }
+}
+
+/// Read a value from a `.json` or `.jlb` file.
+fn read_value<T: DeserializeOwned + Decode>(path: &Path) -> Result<T> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = match Codec::from_path(path) {
+        Codec::Json => serde_json::from_slice(&bytes).map_err(BlockchainError::from),
+        Codec::Binary => decode_exact(&bytes),
+    };
+    value.with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+fn write_output(out: Option<&Path>, bytes: &[u8]) -> Result<()> {
+    match out {
+        Some(path) => std::fs::write(path, bytes)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => Ok(std::io::stdout().write_all(bytes)?),
+    }
+}
+
+fn decode<T: Decode + Serialize>(input: &Path, out: Option<&Path>) -> Result<()> {
+    let bytes =
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let value: T =
+        decode_exact(&bytes).with_context(|| format!("Failed to decode {}", input.display()))?;
+    let mut json = serde_json::to_vec_pretty(&value)?;
+    json.push(b'\n');
+    write_output(out, &json)
+}
+
+fn encode<T: DeserializeOwned + Encode>(input: &Path, out: Option<&Path>) -> Result<()> {
+    let bytes =
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let value: T = serde_json::from_slice(&bytes)
+        .map_err(BlockchainError::from)
+        .with_context(|| format!("Failed to parse {}", input.display()))?;
+    write_output(out, &value.encode())
+}
+
+fn inspect(what: InspectCommand, spec: &ChainSpec) -> Result<()> {
+    match what {
+        InspectCommand::Header { file } => print_header(&read_value(&file)?, spec),
+        InspectCommand::Block { file } => {
+            let block: Block = read_value(&file)?;
+            print_header(&block.header, spec);
+            let extrinsic = &block.extrinsic;
+            let commitment = extrinsic.compute_hash()?;
+            println!(
+                "extrinsic:         {} tickets, {} preimages, {} guarantees, {} assurances",
+                extrinsic.tickets.len(),
+                extrinsic.preimages.len(),
+                extrinsic.guarantees.len(),
+                extrinsic.assurances.len()
+            );
+            println!(
+                "extrinsic matches: {}",
+                commitment == *block.header.extrinsic_hash.as_bytes()
+            );
+        }
+        InspectCommand::State { file } => {
+            let key_values = load_key_values(&file)?;
+            println!("state root: 0x{}", hex::encode(merklize(&key_values)));
+            println!("keys:       {}", key_values.len());
+            for (key, value) in &key_values {
+                println!(
+                    "0x{} {:>8} bytes  {}",
+                    hex::encode(key),
+                    value.len(),
+                    describe_key(key)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_header(header: &Header, spec: &ChainSpec) {
+    println!(
+        "hash:              0x{}",
+        hex::encode(header.hash().as_bytes())
+    );
+    println!(
+        "slot:              {} (epoch {})",
+        header.slot,
+        spec.epoch_of(u64::from(header.slot))
+    );
+    println!(
+        "parent:            0x{}",
+        hex::encode(header.parent.as_bytes())
+    );
+    println!(
+        "parent state root: 0x{}",
+        hex::encode(header.parent_state_root.as_bytes())
+    );
+    println!(
+        "extrinsic hash:    0x{}",
+        hex::encode(header.extrinsic_hash.as_bytes())
+    );
+    println!("author index:      {}", header.author_index);
+    println!("epoch mark:        {}", header.epoch_mark.is_some());
+    println!(
+        "tickets mark:      {}",
+        header
+            .tickets_mark
+            .as_ref()
+            .map_or("none".to_string(), |tickets| format!(
+                "{} tickets",
+                tickets.len()
+            ))
+    );
+    println!("offenders mark:    {}", header.offenders_mark.len());
+}
+
+/// Key/values of a snapshot file, or of a JSON document holding `keyvals` at the top level,
+/// under `state` (genesis) or under `post_state` (trace steps).
+fn load_key_values(path: &Path) -> Result<KeyValues> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if bytes.starts_with(SNAPSHOT_MAGIC) {
+        let snapshot: Snapshot = decode_exact(&bytes)
+            .with_context(|| format!("Failed to decode snapshot {}", path.display()))?;
+        return Ok(snapshot.key_values);
+    }
+
+    let value: Value = serde_json::from_slice(&bytes)
+        .map_err(BlockchainError::from)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let keyvals = [&value, &value["state"], &value["post_state"]]
+        .into_iter()
+        .find_map(|value| value.get("keyvals"));
+    match keyvals {
+        Some(keyvals) => Ok(parse_keyvals(keyvals)?),
+        None => bail!("{} holds no state key/values", path.display()),
+    }
+}
+
+fn run_vectors(vectors_dir: &Path, category: &str, spec: &ChainSpec) -> Result<()> {
+    let outcomes = vectors::run_category(vectors_dir, category, spec)?;
+    let (mut failed, mut partial) = (0, 0);
+    for outcome in &outcomes {
+        match (&outcome.failure, &outcome.partial) {
+            (Some(reason), _) => {
+                failed += 1;
+                println!("{}: FAILED: {reason}", outcome.path.display());
+            }
+            (None, Some(reason)) => {
+                partial += 1;
+                println!("{}: PARTIAL: {reason}", outcome.path.display());
+            }
+            (None, None) => println!("{}: ok", outcome.path.display()),
+        }
+    }
+    println!(
+        "{} of {} vectors passed, {partial} partially verified",
+        outcomes.len() - failed - partial,
+        outcomes.len()
+    );
+    if failed > 0 {
+        return Err(Mismatch(format!("{failed} {category} vectors failed")).into());
+    }
+    if partial > 0 {
+        return Err(Mismatch(format!(
+            "{partial} {category} vectors were only partially verified"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn replay_trace(dir: &Path, spec: &ChainSpec) -> Result<()> {
+    let report = trace::replay_trace(dir, spec)?;
+    for step in &report.steps {
+        println!(
+            "{} slot {}: {}",
+            step.file.display(),
+            step.slot,
+            match step.verdict() {
+                StepVerdict::Passed => "ok",
+                StepVerdict::Partial => "PARTIAL",
+                StepVerdict::Failed => "FAILED",
+            }
+        );
+    }
+
+    if let Some(step) = report.partial().next() {
+        println!(
+            "  not compared (unmodelled): {} and the state root",
+            step.unmodelled.join(", ")
+        );
+    }
+
+    let Some(failure) = report.failure() else {
+        let partial = report.partial().count();
+        if partial > 0 {
+            return Err(Mismatch(format!(
+                "trace {}: {partial} of {} steps only partially verified",
+                dir.display(),
+                report.steps.len()
+            ))
+            .into());
+        }
+        println!("{} steps passed", report.steps.len());
+        return Ok(());
+    };
+    if let Some(error) = &failure.error {
+        println!("  import error: {error}");
+    }
+    println!("  expected root: {}", hex::encode(failure.expected_root));
+    println!("  actual root:   {}", hex::encode(failure.actual_root));
+    if let Some(diff) = &failure.first_difference {
+        println!("  first difference: {diff}");
+    }
+    Err(Mismatch(format!(
+        "trace {} diverged at {}",
+        dir.display(),
+        failure.file.display()
+    ))
+    .into())
+}
+
+fn snapshot(action: SnapshotCommand, data_dir: Option<&Path>) -> Result<()> {
+    let data_dir = || data_dir.context("`--data-dir` is required");
+    match action {
+        SnapshotCommand::Export { out, block } => {
+            let data_dir = data_dir()?;
+            let store = FileStore::open(data_dir)?;
+            let hash = match block {
+                Some(hex_hash) => OpaqueHash::from_hex(&hex_hash)?,
+                None => match store::finalized(&store)? {
+                    Some(record) => record.hash,
+                    None => bail!("{} holds no chain", data_dir.display()),
+                },
+            };
+            let snapshot = Snapshot::export(&store, &hash)?;
+            snapshot.save(&out)?;
+            println!(
+                "Exported {} keys at slot {} to {}",
+                snapshot.key_values.len(),
+                snapshot.header.slot,
+                out.display()
+            );
+        }
+        SnapshotCommand::Import { snapshot, anchor } => {
+            let snapshot = Snapshot::load(&snapshot)
+                .with_context(|| format!("Failed to read snapshot {}", snapshot.display()))?;
+            let anchor = anchor.anchor()?;
+            let data_dir = data_dir()?;
+            let mut store = FileStore::open(data_dir)?;
+            snapshot.import_into(&mut store as &mut dyn Store, &anchor)?;
+            println!(
+                "Imported snapshot of {} into {}",
+                hex::encode(snapshot.hash().as_bytes()),
+                data_dir.display()
+            );
+        }
+        SnapshotCommand::Verify { snapshot, anchor } => {
+            verify_snapshot(&snapshot, &anchor.anchor()?)?
+        }
+    }
+    Ok(())
+}
+
+fn verify_snapshot(path: &Path, anchor: &SnapshotAnchor) -> Result<()> {
+    let snapshot = Snapshot::load(path)
+        .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+    snapshot.verify(anchor)?;
+    println!(
+        "Snapshot of {} at slot {} verified: state root {}",
+        hex::encode(snapshot.hash().as_bytes()),
+        snapshot.header.slot,
+        hex::encode(snapshot.state_root)
+    );
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::CommandFactory;
+    use ed25519_dalek::SigningKey;
+    use jamliquor::authorer::{Authorer, SealKind};
     use jamliquor::schema::{
-        Block, Extrinsic, Header, OpaqueHash, Preimage, TicketBody, TicketEnvelope,
+        Block, BlockchainError, Extrinsic, Header, OpaqueHash, Preimage, State, TicketBody,
+        TicketEnvelope,
     };
+    use jamliquor::Codec;
     use serde_json::{to_value, Value};
-    use std::fs::File;
-    use std::path::PathBuf;
-    use tempfile::tempdir;
 
-    fn build_sample_block() -> (Block, Value) {
+    fn build_sample_block(authorer: &Authorer) -> (Block, Value) {
         let mut block = Block {
             header: Header {
                 parent: OpaqueHash::new([0u8; 32]),
@@ -59,32 +685,25 @@ mod tests {
             .compute_hash()
             .expect("failed to compute extrinsic hash");
         block.header.extrinsic_hash = OpaqueHash::new(extrinsic_hash);
+        block.header = authorer.seal(block.header, &OpaqueHash::default(), &SealKind::Fallback);
 
         let block_json = to_value(&block).expect("failed to serialize block to JSON");
 
         (block, block_json)
     }
 
-    fn write_block_json(value: &Value) -> Result<PathBuf> {
-        let dir = tempdir()?;
-        let file_path = dir.path().join("block.json");
-        serde_json::to_writer(File::create(&file_path)?, value)?;
-        std::mem::forget(dir);
-        Ok(file_path)
-    }
-
     #[test]
     fn test_block_import() -> Result<()> {
-        let mut importer = Importer::new();
-        let (block, block_json) = build_sample_block();
-
-        importer.set_initial_state(
-            *block.header.parent.as_bytes(),
-            *block.header.parent_state_root.as_bytes(),
-        );
+        // A single validator holds every fallback slot.
+        let authorer = Authorer::new(SigningKey::from_bytes(&[1; 32]), ChainSpec::default());
+        let mut state = State::new();
+        state.active_validators = vec![*authorer.public_key()];
+        let mut importer =
+            Importer::from_state(ChainSpec::default(), OpaqueHash::default(), None, state);
+        let (block, block_json) = build_sample_block(&authorer);
 
-        let block_path = write_block_json(&block_json)?;
-        let imported_block = importer.import_block(&block_path)?;
+        let block_bytes = serde_json::to_vec(&block_json)?;
+        let imported_block = importer.import_block_bytes(&block_bytes, Codec::Json)?;
 
         assert_eq!(
             imported_block.header.extrinsic_hash.as_bytes(),
@@ -111,4 +730,41 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let invalid = anyhow::Error::from(BlockchainError::InvalidSlot {
+            last_slot: 2,
+            current_slot: 1,
+        })
+        .context("Block validation failed");
+        assert_eq!(exit_code(&invalid), EXIT_INVALID_BLOCK);
+
+        let io = anyhow::Error::from(BlockchainError::IoError(std::io::Error::other("disk")))
+            .context("Failed to persist block");
+        assert_eq!(exit_code(&io), EXIT_INTERNAL_ERROR);
+
+        // Decode and state errors from the node's own data are not block verdicts.
+        for error in [
+            BlockchainError::CodecError {
+                reason: "truncated WAL record".to_string(),
+            },
+            BlockchainError::HexError(hex::FromHexError::OddLength),
+            BlockchainError::StateTransitionError {
+                reason: "missing service".to_string(),
+            },
+        ] {
+            assert_eq!(exit_code(&error.into()), EXIT_INTERNAL_ERROR);
+        }
+        let undecodable = Codec::Json.decode_block(b"{").unwrap_err();
+        assert_eq!(exit_code(&undecodable.into()), EXIT_INVALID_BLOCK);
+
+        let mismatch = anyhow::Error::from(Mismatch("trace diverged".to_string()));
+        assert_eq!(exit_code(&mismatch), EXIT_MISMATCH);
+    }
 }
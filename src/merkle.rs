@@ -0,0 +1,237 @@
+//! Binary Patricia Merkle trie over state key/values (GP Appendix D.2).
+//!
+//! Nodes are 64 bytes. A branch holds its left child hash with the first bit cleared
+//! followed by the right child hash; a leaf holds a 31-byte key and either the value
+//! itself (up to 32 bytes, "embedded") or the hash of the value. Key bits are read most
+//! significant bit first.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::schema::{blake2b_256, BlockchainError};
+
+/// Length of a state key in bytes.
+pub const STATE_KEY_LEN: usize = 31;
+/// Size of an encoded trie node.
+pub const NODE_LEN: usize = 64;
+/// Largest value stored inline in a leaf.
+pub const MAX_EMBEDDED_VALUE: usize = 32;
+
+/// A 31-byte state key.
+pub type StateKey = [u8; STATE_KEY_LEN];
+
+/// Ordered state key/value set, the input to merklization.
+pub type KeyValues = BTreeMap<StateKey, Vec<u8>>;
+
+/// Fetches a trie node or value by hash from a content-addressed store.
+pub type NodeLookup<'a> = dyn Fn(&[u8; 32]) -> Result<Option<Vec<u8>>, BlockchainError> + 'a;
+
+/// Root of the empty trie.
+pub const EMPTY_ROOT: [u8; 32] = [0u8; 32];
+
+/// Bit `i` of `key`, most significant bit first.
+pub fn key_bit(key: &StateKey, i: usize) -> bool {
+    key[i / 8] & (0x80 >> (i % 8)) != 0
+}
+
+/// Encode a leaf node for `key` and `value`.
+pub fn encode_leaf(key: &StateKey, value: &[u8]) -> [u8; NODE_LEN] {
+    let mut node = [0u8; NODE_LEN];
+    node[1..32].copy_from_slice(key);
+    if value.len() <= MAX_EMBEDDED_VALUE {
+        node[0] = 0b1000_0000 | value.len() as u8;
+        node[32..32 + value.len()].copy_from_slice(value);
+    } else {
+        node[0] = 0b1100_0000;
+        node[32..].copy_from_slice(&blake2b_256(value));
+    }
+    node
+}
+
+/// Encode a branch node from its child hashes.
+pub fn encode_branch(left: &[u8; 32], right: &[u8; 32]) -> [u8; NODE_LEN] {
+    let mut node = [0u8; NODE_LEN];
+    node[..32].copy_from_slice(left);
+    node[0] &= 0b0111_1111;
+    node[32..].copy_from_slice(right);
+    node
+}
+
+/// A decoded trie node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// Children hashes; the left hash is missing its first bit.
+    Branch { left: [u8; 32], right: [u8; 32] },
+    /// Leaf with the value inline.
+    Embedded { key: StateKey, value: Vec<u8> },
+    /// Leaf referencing the value by hash.
+    Regular { key: StateKey, value_hash: [u8; 32] },
+}
+
+impl Node {
+    /// Decode a 64-byte node.
+    pub fn decode(node: &[u8]) -> Result<Self, BlockchainError> {
+        if node.len() != NODE_LEN {
+            return Err(BlockchainError::CodecError {
+                reason: format!("trie node is {} bytes, expected {NODE_LEN}", node.len()),
+            });
+        }
+        let mut key = [0u8; STATE_KEY_LEN];
+        key.copy_from_slice(&node[1..32]);
+        let mut tail = [0u8; 32];
+        tail.copy_from_slice(&node[32..]);
+
+        match node[0] >> 6 {
+            0b00 | 0b01 => {
+                let mut left = [0u8; 32];
+                left.copy_from_slice(&node[..32]);
+                Ok(Node::Branch { left, right: tail })
+            }
+            0b10 => {
+                let len = (node[0] & 0b0011_1111) as usize;
+                if len > MAX_EMBEDDED_VALUE {
+                    return Err(BlockchainError::CodecError {
+                        reason: format!("embedded leaf length {len} exceeds {MAX_EMBEDDED_VALUE}"),
+                    });
+                }
+                Ok(Node::Embedded {
+                    key,
+                    value: node[32..32 + len].to_vec(),
+                })
+            }
+            _ => Ok(Node::Regular {
+                key,
+                value_hash: tail,
+            }),
+        }
+    }
+}
+
+/// Compute the Merkle root of a key/value set.
+pub fn merklize(kvs: &KeyValues) -> [u8; 32] {
+    let entries: Vec<_> = kvs.iter().collect();
+    build(&entries, 0, &mut |_, _| {})
+}
+
+/// Compute the root and hand every node (and every non-embedded value) to `sink`.
+///
+/// `sink` receives `(hash, bytes)` pairs suitable for a content-addressed store.
+pub fn merklize_with_nodes(kvs: &KeyValues, sink: &mut dyn FnMut([u8; 32], &[u8])) -> [u8; 32] {
+    let entries: Vec<_> = kvs.iter().collect();
+    build(&entries, 0, sink)
+}
+
+fn build(
+    entries: &[(&StateKey, &Vec<u8>)],
+    depth: usize,
+    sink: &mut dyn FnMut([u8; 32], &[u8]),
+) -> [u8; 32] {
+    match entries {
+        [] => EMPTY_ROOT,
+        [(key, value)] => {
+            if value.len() > MAX_EMBEDDED_VALUE {
+                sink(blake2b_256(value), value);
+            }
+            let node = encode_leaf(key, value);
+            let hash = blake2b_256(&node);
+            sink(hash, &node);
+            hash
+        }
+        _ => {
+            // Entries are sorted, so the split point is the first key with the bit set.
+            let split = entries.partition_point(|(key, _)| !key_bit(key, depth));
+            let left = build(&entries[..split], depth + 1, sink);
+            let right = build(&entries[split..], depth + 1, sink);
+            let node = encode_branch(&left, &right);
+            let hash = blake2b_256(&node);
+            sink(hash, &node);
+            hash
+        }
+    }
+}
+
+/// Rebuild the full key/value set below `root` from a node lookup.
+///
+/// Branches only carry 255 bits of the left child hash, so the left child is looked up
+/// under both possible first bits.
+pub fn collect(root: &[u8; 32], lookup: &NodeLookup) -> Result<KeyValues, BlockchainError> {
+    let mut kvs = KeyValues::new();
+    if *root != EMPTY_ROOT {
+        collect_node(root, lookup, &mut kvs)?;
+    }
+    Ok(kvs)
+}
+
+/// Add the hash of every node and value of the trie with root `root` to `hashes`.
+///
+/// Subtrees already in `hashes` are not walked again, so the tries of related states can
+/// be marked one after the other at the cost of their differences.
+pub fn reachable(
+    root: &[u8; 32],
+    lookup: &NodeLookup,
+    hashes: &mut HashSet<[u8; 32]>,
+) -> Result<(), BlockchainError> {
+    if *root == EMPTY_ROOT || !hashes.insert(*root) {
+        return Ok(());
+    }
+    let bytes = lookup(root)?.ok_or_else(|| missing(root))?;
+    match Node::decode(&bytes)? {
+        Node::Branch { left, right } => {
+            if left != EMPTY_ROOT {
+                let left = resolve_left(&left, lookup)?.ok_or_else(|| missing(&left))?;
+                reachable(&left, lookup, hashes)?;
+            }
+            reachable(&right, lookup, hashes)?;
+        }
+        Node::Embedded { .. } => {}
+        Node::Regular { value_hash, .. } => {
+            hashes.insert(value_hash);
+        }
+    }
+    Ok(())
+}
+
+fn missing(hash: &[u8; 32]) -> BlockchainError {
+    BlockchainError::StoreError {
+        reason: format!("missing trie entry {}", hex::encode(hash)),
+    }
+}
+
+/// Resolve a left child hash whose first bit was cleared by [`encode_branch`].
+pub fn resolve_left(
+    left: &[u8; 32],
+    lookup: &NodeLookup,
+) -> Result<Option<[u8; 32]>, BlockchainError> {
+    if lookup(left)?.is_some() {
+        return Ok(Some(*left));
+    }
+    let mut flipped = *left;
+    flipped[0] |= 0b1000_0000;
+    Ok(lookup(&flipped)?.map(|_| flipped))
+}
+
+fn collect_node(
+    hash: &[u8; 32],
+    lookup: &NodeLookup,
+    kvs: &mut KeyValues,
+) -> Result<(), BlockchainError> {
+    let bytes = lookup(hash)?.ok_or_else(|| missing(hash))?;
+    match Node::decode(&bytes)? {
+        Node::Branch { left, right } => {
+            if left != EMPTY_ROOT {
+                let left = resolve_left(&left, lookup)?.ok_or_else(|| missing(&left))?;
+                collect_node(&left, lookup, kvs)?;
+            }
+            if right != EMPTY_ROOT {
+                collect_node(&right, lookup, kvs)?;
+            }
+        }
+        Node::Embedded { key, value } => {
+            kvs.insert(key, value);
+        }
+        Node::Regular { key, value_hash } => {
+            let value = lookup(&value_hash)?.ok_or_else(|| missing(&value_hash))?;
+            kvs.insert(key, value);
+        }
+    }
+    Ok(())
+}
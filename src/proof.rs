@@ -0,0 +1,186 @@
+//! Inclusion and exclusion proofs for state keys.
+//!
+//! A [`StateProof`] carries the trie nodes on the paths from the state root to a set of
+//! keys. With it, [`verify_proof`] checks the value of any of those keys (or that the key
+//! is absent) against nothing but the state root: no store or full state is needed, so
+//! light clients and external verifiers can use it directly. Values longer than 32 bytes
+//! are not part of the proof; the verifier supplies the claimed value and the proof binds
+//! it by hash.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::codec::{decode_seq, encode_seq, Decode, Encode};
+use crate::merkle::{
+    key_bit, merklize_with_nodes, KeyValues, Node, StateKey, EMPTY_ROOT, NODE_LEN, STATE_KEY_LEN,
+};
+use crate::schema::{blake2b_256, BlockchainError, State};
+
+/// Trie nodes proving the values of one or more state keys.
+///
+/// Memory Usage:
+/// - 64 bytes per distinct node on the proven paths (about `log2(keys in state)` per key)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateProof {
+    /// Path nodes, each included once, in the order they are first visited.
+    pub nodes: Vec<[u8; NODE_LEN]>,
+}
+
+/// Where the path to a key ends.
+enum Terminal {
+    Absent,
+    Embedded(Vec<u8>),
+    /// Leaf referencing a value by hash.
+    Hashed([u8; 32]),
+}
+
+impl StateProof {
+    /// Prove the values (or absence) of `keys` in the trie of `kvs`.
+    pub fn generate(kvs: &KeyValues, keys: &[StateKey]) -> Self {
+        let mut nodes = HashMap::new();
+        let root = merklize_with_nodes(kvs, &mut |hash, bytes| {
+            if let Ok(node) = <[u8; NODE_LEN]>::try_from(bytes) {
+                nodes.insert(hash, node);
+            }
+        });
+
+        let mut proof = StateProof::default();
+        let mut included = HashSet::new();
+        for key in keys {
+            let mut lookup = |hash: &[u8; 32]| {
+                let node = nodes.get(hash).copied();
+                if let Some(node) = node {
+                    if included.insert(*hash) {
+                        proof.nodes.push(node);
+                    }
+                }
+                node
+            };
+            walk(&root, key, &mut lookup).expect("every node of the trie is available");
+        }
+        proof
+    }
+
+    /// Number of nodes in the proof.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl State {
+    /// Prove the values (or absence) of `keys` in this state, under
+    /// [`State::state_root`].
+    pub fn prove(&self, keys: &[StateKey]) -> StateProof {
+        StateProof::generate(&self.to_key_values(), keys)
+    }
+}
+
+/// Check that `key` has `value` (`None`: is absent) in the state with root `root`.
+pub fn verify_proof(
+    root: &[u8; 32],
+    proof: &StateProof,
+    key: &StateKey,
+    value: Option<&[u8]>,
+) -> Result<(), BlockchainError> {
+    let nodes: HashMap<[u8; 32], [u8; NODE_LEN]> = proof
+        .nodes
+        .iter()
+        .map(|node| (blake2b_256(node), *node))
+        .collect();
+    let proven = walk(root, key, &mut |hash| nodes.get(hash).copied())?;
+
+    let matches = match (&proven, value) {
+        (Terminal::Absent, None) => true,
+        (Terminal::Embedded(proven), Some(value)) => proven.as_slice() == value,
+        (Terminal::Hashed(hash), Some(value)) => *hash == blake2b_256(value),
+        _ => false,
+    };
+    if !matches {
+        let proven = match proven {
+            Terminal::Absent => "absent".to_string(),
+            Terminal::Embedded(value) => hex::encode(value),
+            Terminal::Hashed(hash) => format!("value with hash {}", hex::encode(hash)),
+        };
+        return Err(BlockchainError::InvalidStateProof {
+            reason: format!(
+                "Key {} proves {proven} under {}",
+                hex::encode(key),
+                hex::encode(root)
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Follow the path to `key` from `root`, fetching nodes through `lookup`.
+///
+/// A path has at most one branch per key bit; a forged proof branching deeper is rejected.
+fn walk(
+    root: &[u8; 32],
+    key: &StateKey,
+    lookup: &mut dyn FnMut(&[u8; 32]) -> Option<[u8; NODE_LEN]>,
+) -> Result<Terminal, BlockchainError> {
+    let mut hash = *root;
+    for depth in 0..STATE_KEY_LEN * 8 {
+        if hash == EMPTY_ROOT {
+            return Ok(Terminal::Absent);
+        }
+        // Branches store the left child without its first bit.
+        let node = match lookup(&hash) {
+            Some(node) => node,
+            None if depth > 0 && !key_bit(key, depth - 1) => {
+                hash[0] |= 0b1000_0000;
+                lookup(&hash).ok_or_else(|| missing(key, &hash))?
+            }
+            None => return Err(missing(key, &hash)),
+        };
+        match Node::decode(&node)? {
+            Node::Branch { left, right } => {
+                hash = if key_bit(key, depth) { right } else { left };
+            }
+            Node::Embedded {
+                key: leaf_key,
+                value,
+            } if leaf_key == *key => return Ok(Terminal::Embedded(value)),
+            Node::Regular {
+                key: leaf_key,
+                value_hash,
+            } if leaf_key == *key => return Ok(Terminal::Hashed(value_hash)),
+            Node::Embedded { .. } | Node::Regular { .. } => return Ok(Terminal::Absent),
+        }
+    }
+    Err(BlockchainError::InvalidStateProof {
+        reason: format!(
+            "Proof for key {} branches past its {} bits",
+            hex::encode(key),
+            STATE_KEY_LEN * 8
+        ),
+    })
+}
+
+fn missing(key: &StateKey, hash: &[u8; 32]) -> BlockchainError {
+    BlockchainError::InvalidStateProof {
+        reason: format!(
+            "Proof for key {} lacks node {}",
+            hex::encode(key),
+            hex::encode(hash)
+        ),
+    }
+}
+
+impl Encode for StateProof {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_seq(&self.nodes, out);
+    }
+}
+
+impl Decode for StateProof {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(StateProof {
+            nodes: decode_seq(input)?,
+        })
+    }
+}
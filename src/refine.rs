@@ -0,0 +1,307 @@
+//! In-core refinement of work packages (GP 14.3).
+//!
+//! [`refine`] checks a [`WorkPackageBundle`] against its package, runs the authorizer's
+//! Is-Authorized code, then each item's Refine code, collects the exported segments and
+//! erasure-codes the bundle and segments for availability. The result is the
+//! [`WorkReport`] a guarantor signs, with the data assurers and auditors are sent.
+//!
+//! The tree has no PVM interpreter yet: programs run behind the [`Pvm`] trait, which an
+//! interpreter (or a recompiler on capable devices) implements. Closures implement it too,
+//! for tests and native service stand-ins.
+
+use log::{debug, warn};
+
+use crate::availability::verify_import;
+use crate::codec::Encode;
+use crate::erasure::{paged_proofs, segments_root, ErasureCoder, SEGMENT_SIZE};
+use crate::schema::{blake2b_256, BlockchainError, OpaqueHash};
+use crate::work_package::{
+    ExtrinsicSpec, PackageSpec, WorkError, WorkPackageBundle, WorkReport, WorkResult,
+    MAX_PACKAGE_SEGMENTS, MAX_WORK_ITEMS,
+};
+
+/// Gas available to Is-Authorized `G_I`.
+pub const IS_AUTHORIZED_GAS: u64 = 50_000_000;
+/// Maximum total refine gas of a package `G_R`.
+pub const MAX_REFINE_GAS: u64 = 5_000_000_000;
+/// Maximum size of the authorizer output and item outputs together `W_R`.
+pub const MAX_REPORT_OUTPUT: usize = 48 * 1024;
+
+/// PVM entry point of an invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    IsAuthorized,
+    Refine,
+}
+
+/// A program run requested by the refine pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation<'a> {
+    pub entry: Entry,
+    /// Service whose code runs: the authorizer's host, or the item's service.
+    pub service: u32,
+    pub code_hash: OpaqueHash,
+    /// Encoded arguments: the core for Is-Authorized; core, item index, service,
+    /// payload and package hash for Refine.
+    pub args: Vec<u8>,
+    pub gas: u64,
+    /// Imported segments and extrinsic blobs of the item; empty for Is-Authorized.
+    pub imports: &'a [Vec<u8>],
+    pub extrinsics: &'a [Vec<u8>],
+}
+
+/// What a program run produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvocationResult {
+    pub output: Result<Vec<u8>, WorkError>,
+    pub gas_used: u64,
+    /// Segments exported by Refine, each at most `SEGMENT_SIZE` octets.
+    pub exports: Vec<Vec<u8>>,
+}
+
+/// Runs PVM programs for the refine pipeline.
+pub trait Pvm {
+    fn invoke(&mut self, invocation: &Invocation) -> InvocationResult;
+}
+
+impl<F> Pvm for F
+where
+    F: FnMut(&Invocation) -> InvocationResult,
+{
+    fn invoke(&mut self, invocation: &Invocation) -> InvocationResult {
+        self(invocation)
+    }
+}
+
+/// A refined package: its report and the data that backs it.
+///
+/// Memory Usage:
+/// - 4104 bytes per exported segment
+/// - Chunks: about three times the encoded bundle, exports and proof pages together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefinedPackage {
+    pub report: WorkReport,
+    /// Exported segments, zero-padded to `SEGMENT_SIZE`, in item order.
+    pub exports: Vec<Vec<u8>>,
+    /// Availability chunk of each validator: its bundle chunk followed by its chunks of
+    /// every exported segment and proof page.
+    pub chunks: Vec<Vec<u8>>,
+}
+
+/// Refine `bundle` on `core_index`, running programs through `pvm`.
+pub fn refine(
+    pvm: &mut dyn Pvm,
+    coder: &ErasureCoder,
+    bundle: &WorkPackageBundle,
+    core_index: u16,
+) -> Result<RefinedPackage, BlockchainError> {
+    validate_bundle(bundle)?;
+    let package = &bundle.package;
+    let package_hash = package.hash();
+
+    let authorized = pvm.invoke(&Invocation {
+        entry: Entry::IsAuthorized,
+        service: package.auth_code_host,
+        code_hash: package.authorizer.code_hash,
+        args: core_index.encode(),
+        gas: IS_AUTHORIZED_GAS,
+        imports: &[],
+        extrinsics: &[],
+    });
+    let auth_output = authorized.output.map_err(|error| {
+        invalid(format!(
+            "package {} is not authorized on core {core_index}: {}",
+            hex::encode(package_hash.as_bytes()),
+            error.name()
+        ))
+    })?;
+    let mut output_size = auth_output.len();
+
+    let mut results = Vec::with_capacity(package.items.len());
+    let mut exports = Vec::new();
+    let (mut next_import, mut next_extrinsic) = (0, 0);
+    for (i, item) in package.items.iter().enumerate() {
+        let imports = &bundle.imports[next_import..next_import + item.import_segments.len()];
+        let extrinsics = &bundle.extrinsics[next_extrinsic..next_extrinsic + item.extrinsic.len()];
+        next_import += imports.len();
+        next_extrinsic += extrinsics.len();
+
+        let mut args = core_index.encode();
+        (i as u16).encode_to(&mut args);
+        item.service.encode_to(&mut args);
+        item.payload.encode_to(&mut args);
+        package_hash.encode_to(&mut args);
+        let refined = pvm.invoke(&Invocation {
+            entry: Entry::Refine,
+            service: item.service,
+            code_hash: item.code_hash,
+            args,
+            gas: item.refine_gas_limit,
+            imports,
+            extrinsics,
+        });
+
+        let declared = usize::from(item.export_count);
+        let mut output = refined.output;
+        if output.is_ok()
+            && (refined.exports.len() != declared
+                || refined.exports.iter().any(|s| s.len() > SEGMENT_SIZE))
+        {
+            output = Err(WorkError::BadExports);
+        }
+        if let Ok(bytes) = &output {
+            output_size += bytes.len();
+            if output_size > MAX_REPORT_OUTPUT {
+                output = Err(WorkError::Oversize);
+            }
+        }
+        // Failed items still export as many segments as they declared, all zero.
+        match &output {
+            Ok(_) => exports.extend(refined.exports.into_iter().map(|mut segment| {
+                segment.resize(SEGMENT_SIZE, 0);
+                segment
+            })),
+            Err(error) => {
+                warn!(
+                    "Work item {i} of service {} failed: {}",
+                    item.service,
+                    error.name()
+                );
+                exports.extend((0..declared).map(|_| vec![0; SEGMENT_SIZE]));
+            }
+        }
+
+        results.push(WorkResult {
+            service_id: item.service,
+            code_hash: item.code_hash,
+            payload_hash: OpaqueHash::new(blake2b_256(&item.payload)),
+            accumulate_gas: item.accumulate_gas_limit,
+            result: output,
+            refine_gas_used: refined.gas_used.min(item.refine_gas_limit),
+            imports: imports.len() as u16,
+            extrinsic_count: extrinsics.len() as u16,
+            extrinsic_size: extrinsics.iter().map(|blob| blob.len() as u32).sum(),
+            exports: item.export_count,
+        });
+    }
+
+    let encoded = bundle.encode();
+    let chunks = availability_chunks(coder, &encoded, &exports);
+    let report = WorkReport {
+        package_spec: PackageSpec {
+            hash: package_hash,
+            length: encoded.len() as u32,
+            erasure_root: OpaqueHash::new(segments_root(&chunks)),
+            exports_root: OpaqueHash::new(segments_root(&exports)),
+            exports_count: exports.len() as u16,
+        },
+        context: package.context.clone(),
+        core_index,
+        authorizer_hash: package.authorizer.hash(),
+        auth_output,
+        results,
+        auth_gas_used: authorized.gas_used.min(IS_AUTHORIZED_GAS),
+        segment_root_lookup: bundle.segment_root_lookup.clone(),
+    };
+    debug!(
+        "Refined package {} on core {core_index}: {} items, {} exports",
+        hex::encode(package_hash.as_bytes()),
+        report.results.len(),
+        exports.len()
+    );
+    Ok(RefinedPackage {
+        report,
+        exports,
+        chunks,
+    })
+}
+
+/// The bundle must carry exactly the data its items declare, within package limits, and
+/// every imported segment must verify under the segments root its import names.
+fn validate_bundle(bundle: &WorkPackageBundle) -> Result<(), BlockchainError> {
+    let items = &bundle.package.items;
+    if items.is_empty() || items.len() > MAX_WORK_ITEMS {
+        return Err(invalid(format!(
+            "{} work items, expected 1 to {MAX_WORK_ITEMS}",
+            items.len()
+        )));
+    }
+    let refine_gas = items.iter().fold(0u64, |total, item| {
+        total.saturating_add(item.refine_gas_limit)
+    });
+    if refine_gas > MAX_REFINE_GAS {
+        return Err(invalid(format!(
+            "refine gas {refine_gas} exceeds {MAX_REFINE_GAS}"
+        )));
+    }
+
+    let imports: usize = items.iter().map(|item| item.import_segments.len()).sum();
+    let exports: usize = items
+        .iter()
+        .map(|item| usize::from(item.export_count))
+        .sum();
+    if imports > MAX_PACKAGE_SEGMENTS || exports > MAX_PACKAGE_SEGMENTS {
+        return Err(invalid(format!(
+            "{imports} imports and {exports} exports, at most {MAX_PACKAGE_SEGMENTS} each"
+        )));
+    }
+    if bundle.imports.len() != imports || bundle.justifications.len() != imports {
+        return Err(invalid(format!(
+            "bundle has {} segments and {} justifications for {imports} imports",
+            bundle.imports.len(),
+            bundle.justifications.len()
+        )));
+    }
+    if let Some(i) = bundle.imports.iter().position(|s| s.len() != SEGMENT_SIZE) {
+        return Err(invalid(format!(
+            "imported segment {i} is not {SEGMENT_SIZE} bytes"
+        )));
+    }
+    let import_specs = items.iter().flat_map(|item| &item.import_segments);
+    for (i, import) in import_specs.enumerate() {
+        let root = bundle
+            .segment_root_lookup
+            .iter()
+            .find(|lookup| lookup.work_package_hash == import.tree_root)
+            .map_or(import.tree_root, |lookup| lookup.segment_tree_root);
+        verify_import(
+            &root,
+            import.index,
+            &bundle.imports[i],
+            &bundle.justifications[i],
+        )?;
+    }
+
+    let specs: Vec<&ExtrinsicSpec> = items.iter().flat_map(|item| &item.extrinsic).collect();
+    if specs.len() != bundle.extrinsics.len() {
+        return Err(invalid(format!(
+            "bundle has {} extrinsic blobs for {} specs",
+            bundle.extrinsics.len(),
+            specs.len()
+        )));
+    }
+    if let Some(i) =
+        (0..specs.len()).find(|&i| ExtrinsicSpec::of(&bundle.extrinsics[i]) != *specs[i])
+    {
+        return Err(invalid(format!(
+            "extrinsic blob {i} does not match its hash and length"
+        )));
+    }
+    Ok(())
+}
+
+/// Per validator: the chunk of the bundle followed by the chunks of every segment and
+/// proof page.
+fn availability_chunks(coder: &ErasureCoder, bundle: &[u8], exports: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut chunks = coder.encode(bundle);
+    for segment in exports.iter().chain(&paged_proofs(exports)) {
+        for (chunk, piece) in chunks.iter_mut().zip(coder.encode(segment)) {
+            chunk.extend(piece);
+        }
+    }
+    chunks
+}
+
+fn invalid(reason: String) -> BlockchainError {
+    BlockchainError::InvalidWorkPackage { reason }
+}
@@ -1,3 +1,6 @@
+use crate::codec::Encode;
+use crate::coretime::CoreTimeLedger;
+use crate::error_code::{ErrorCode, Subsystem};
 use ::hex::FromHexError;
 use blake2b_simd::Params as Blake2bParams;
 use serde::{Deserialize, Serialize};
@@ -18,6 +21,10 @@ pub enum BlockchainError {
     #[error("Parent hash mismatch: expected {expected}, got {actual}")]
     ParentHashMismatch { expected: String, actual: String },
 
+    /// Block builds on a parent that is not in the block tree
+    #[error("Unknown parent block: {parent}")]
+    UnknownParent { parent: String },
+
     /// Ticket validation failed
     #[error("Ticket validation error: {reason}")]
     TicketValidationError { reason: String },
@@ -49,6 +56,10 @@ pub enum BlockchainError {
     #[error("Invalid signature: {reason}")]
     InvalidSignature { reason: String },
 
+    /// Finality justification rejected
+    #[error("Invalid finality justification: {reason}")]
+    InvalidJustification { reason: String },
+
     /// Invalid entropy source
     #[error("Invalid entropy source: {reason}")]
     InvalidEntropy { reason: String },
@@ -76,6 +87,106 @@ pub enum BlockchainError {
     /// Hex decoding error
     #[error("Hex decoding error: {0}")]
     HexError(#[from] FromHexError),
+
+    /// Binary codec decoding error
+    #[error("Codec error: {reason}")]
+    CodecError { reason: String },
+
+    /// Block or state store failure
+    #[error("Store error: {reason}")]
+    StoreError { reason: String },
+
+    /// State snapshot does not match its header or state root
+    #[error("Invalid snapshot: {reason}")]
+    InvalidSnapshot { reason: String },
+
+    /// State proof does not prove the claimed value under the given state root
+    #[error("Invalid state proof: {reason}")]
+    InvalidStateProof { reason: String },
+
+    /// Erasure-coded chunks cannot be encoded or reconstructed
+    #[error("Erasure coding error: {reason}")]
+    ErasureCoding { reason: String },
+
+    /// Work package is malformed, unauthorized or does not match its bundle
+    #[error("Invalid work package: {reason}")]
+    InvalidWorkPackage { reason: String },
+
+    /// Imported segment is not held by the availability store
+    #[error("Segment {index} of {root} is unavailable")]
+    SegmentUnavailable { root: String, index: u16 },
+
+    /// Block rejected by a sub-STF; `index` names the offending extrinsic item
+    #[error("{subsystem} {code}{}: {reason}", .index.map(|i| format!(" (item {i})")).unwrap_or_default())]
+    Rejected {
+        subsystem: Subsystem,
+        code: ErrorCode,
+        index: Option<usize>,
+        reason: String,
+    },
+}
+
+impl BlockchainError {
+    /// A rejection in the code's own subsystem.
+    pub fn rejected(code: ErrorCode, index: Option<usize>, reason: impl Into<String>) -> Self {
+        BlockchainError::Rejected {
+            subsystem: code.subsystem(),
+            code,
+            index,
+            reason: reason.into(),
+        }
+    }
+
+    /// Machine-readable rejection code, if the error rejects a block.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            BlockchainError::Rejected { code, .. } => Some(*code),
+            BlockchainError::InvalidSlot { .. } => Some(ErrorCode::BadSlot),
+            BlockchainError::ParentHashMismatch { .. } => Some(ErrorCode::BadParent),
+            BlockchainError::ParentStateRootMismatch { .. } => Some(ErrorCode::BadParentStateRoot),
+            BlockchainError::UnknownParent { .. } => Some(ErrorCode::UnknownParent),
+            BlockchainError::InvalidAuthorIndex { .. } => Some(ErrorCode::UnexpectedAuthor),
+            BlockchainError::InvalidEntropy { .. } => Some(ErrorCode::BadEntropy),
+            BlockchainError::InvalidSignature { .. } => Some(ErrorCode::BadSignature),
+            _ => None,
+        }
+    }
+
+    /// Subsystem that rejected the block.
+    pub fn subsystem(&self) -> Option<Subsystem> {
+        match self {
+            BlockchainError::Rejected { subsystem, .. } => Some(*subsystem),
+            other => other.code().map(ErrorCode::subsystem),
+        }
+    }
+
+    /// Index of the offending extrinsic item.
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            BlockchainError::Rejected { index, .. } => *index,
+            _ => None,
+        }
+    }
+
+    /// Whether the error is a verdict on the block (one that fails to decode or
+    /// validate) rather than a failure of the node itself.
+    ///
+    /// Decoding errors count only once the block codec has turned them into a
+    /// [`ErrorCode::Malformed`] rejection; a bare `CodecError` or `JsonError` may come
+    /// from the store or a snapshot.
+    pub fn is_invalid_block(&self) -> bool {
+        matches!(
+            self,
+            BlockchainError::Rejected { .. }
+                | BlockchainError::InvalidSlot { .. }
+                | BlockchainError::ParentHashMismatch { .. }
+                | BlockchainError::UnknownParent { .. }
+                | BlockchainError::ParentStateRootMismatch { .. }
+                | BlockchainError::InvalidAuthorIndex { .. }
+                | BlockchainError::InvalidEntropy { .. }
+                | BlockchainError::InvalidSignature { .. }
+        )
+    }
 }
 
 /// ValidationResult captures the outcome of block or state validation with detailed context.
@@ -90,11 +201,13 @@ pub enum ValidationResult {
     Failure { code: u64, message: Option<String> },
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 /// State structure for tracking JAM protocol state.
 ///
 /// Memory Usage:
-/// - Fixed: ~64 bytes (u64s + TicketState)
+/// - Fixed: ~200 bytes (u64s + TicketState + head hashes + CoreTime totals)
+/// - Per active core: ~24 bytes of CoreTime usage
+/// - Per validator: 32 bytes in each of the active and pending sets
 /// - Per ticket: ~40 bytes
 /// - Per preimage: ~32 bytes + blob size
 ///
@@ -104,18 +217,30 @@ pub struct State {
     pub last_slot: u64, // Tracks last processed slot
     pub counter: u64,   // Total valid tickets and preimages
     pub ticket_state: TicketState,
+    pub last_block_hash: Option<[u8; 32]>, // Hash the next block must name as parent
+    pub last_state_root: Option<[u8; 32]>, // Root the next block must name as parent state
+    pub coretime: CoreTimeLedger,
+    pub active_validators: Vec<OpaqueHash>, // Keys of the current epoch's validators (kappa)
+    pub pending_validators: Vec<OpaqueHash>, // Keys announced for the next epoch (gamma_k)
 }
 
-#[derive(Debug, Default)]
-/// TicketState tracks ticket statistics and last ticket ID.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// TicketState tracks ticket statistics, the last ticket ID and how the current and next
+/// epochs are sealed.
 ///
 /// Memory Usage:
-/// - Fixed: ~56 bytes (3 x u64 + Option<[u8;32]>)
+/// - Fixed: ~90 bytes (3 x u64 + Option<[u8;32]> + 2 x bool + entropy)
+/// - Dynamic: 33 bytes per sealing ticket of the current and next epochs
 pub struct TicketState {
     pub total_tickets: u64,
     pub valid_tickets: u64,
     pub invalid_tickets: u64,
     pub last_ticket_id: Option<OpaqueHash>,
+    pub sealed_by_tickets: bool, // Current epoch is sealed by tickets rather than fallback keys
+    pub next_sealed_by_tickets: bool, // A tickets mark was issued for the next epoch
+    pub entropy: OpaqueHash, // Epoch entropy seals and fallback keys are drawn from
+    pub sealing_tickets: Vec<TicketBody>, // Tickets sealing the current epoch, in slot order
+    pub next_tickets: Vec<TicketBody>, // Tickets announced for the next epoch
 }
 
 impl TicketState {
@@ -213,7 +338,7 @@ impl State {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
 /// OpaqueHash is a fixed-size 32-byte array used for hashes and IDs.
 ///
 /// Memory Usage:
@@ -249,9 +374,21 @@ impl OpaqueHash {
     pub fn new(bytes: [u8; 32]) -> Self {
         OpaqueHash(bytes)
     }
+
+    /// Parse a hex string, with or without a `0x` prefix.
+    pub fn from_hex(s: &str) -> Result<Self, BlockchainError> {
+        let bytes = ::hex::decode(s.trim_start_matches("0x"))?;
+        let bytes: [u8; 32] =
+            bytes
+                .try_into()
+                .map_err(|bytes: Vec<u8>| BlockchainError::CodecError {
+                    reason: format!("expected a 32-byte hash, got {} bytes", bytes.len()),
+                })?;
+        Ok(OpaqueHash(bytes))
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// EpochMark represents epoch metadata and entropy.
 ///
 /// Memory Usage:
@@ -265,7 +402,7 @@ pub struct EpochMark {
     pub validators: Vec<OpaqueHash>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// Header contains block metadata and consensus information.
 ///
 /// Memory Usage:
@@ -296,7 +433,7 @@ pub struct Header {
     pub seal: Vec<u8>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// TicketEnvelope represents a ticket submitted by a validator.
 ///
 /// Memory Usage:
@@ -325,7 +462,7 @@ impl TicketEnvelope {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// TicketBody represents the actual content of a ticket.
 ///
 /// Memory Usage:
@@ -344,7 +481,7 @@ impl TicketBody {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// Preimage represents a preimage for state transition proofs.
 ///
 /// Memory Usage:
@@ -358,7 +495,7 @@ pub struct Preimage {
     pub blob: Vec<u8>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// Extrinsic contains tickets and preimages for a block.
 ///
 /// Memory Usage:
@@ -375,18 +512,23 @@ impl Extrinsic {
     /// Compute the canonical Blake2b-256 hash of the extrinsic payload.
     pub fn compute_hash(&self) -> Result<[u8; 32], serde_json::Error> {
         let payload = serde_json::to_vec(self)?;
-        let hash = Blake2bParams::new()
-            .hash_length(32)
-            .to_state()
-            .update(&payload)
-            .finalize();
-        let mut result = [0u8; 32];
-        result.copy_from_slice(hash.as_bytes());
-        Ok(result)
+        Ok(blake2b_256(&payload))
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Blake2b-256, the Gray Paper's general-purpose hash function `H`.
+pub fn blake2b_256(data: &[u8]) -> [u8; 32] {
+    let hash = Blake2bParams::new()
+        .hash_length(32)
+        .to_state()
+        .update(data)
+        .finalize();
+    let mut result = [0u8; 32];
+    result.copy_from_slice(hash.as_bytes());
+    result
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// Block is the top-level structure for JAM blocks.
 ///
 /// Memory Usage:
@@ -583,6 +725,18 @@ impl EntropySource {
 }
 
 impl Header {
+    /// Header hash: Blake2b-256 of the binary header encoding.
+    ///
+    /// That encoding is JamLiquor's internal codec ([`crate::codec`]), not the Gray Paper
+    /// header serialization, and the header model cannot be serialized the GP way (epoch
+    /// marks hold one key per validator instead of a Bandersnatch and Ed25519 pair). Hashes
+    /// therefore never match those of GP vectors or traces, and a header taken from GP data
+    /// does not link to its parent by hash; trace replay re-anchors each step on its
+    /// pre-state instead.
+    pub fn hash(&self) -> OpaqueHash {
+        OpaqueHash(blake2b_256(&self.encode()))
+    }
+
     pub fn validate_entropy(&self) -> Result<EntropySource, anyhow::Error> {
         // Parse and validate entropy source format
         let entropy_source = EntropySource::from_bytes(&self.entropy_source)?;
@@ -0,0 +1,257 @@
+//! State snapshots: every state key/value at a block, with its header and state root.
+//!
+//! A snapshot lets a node start from a recent block instead of replaying from genesis,
+//! which matters for the low-RAM devices targeted in `docs/src/EDGE.md`. A snapshot file
+//! vouches only for itself, so it is only trusted after [`Snapshot::verify`] has checked it
+//! against a [`SnapshotAnchor`] obtained out of band, e.g. from a node the operator trusts.
+
+use std::fs;
+use std::path::Path;
+
+use log::{debug, info};
+
+use crate::codec::{decode_exact, take, Decode, Encode};
+use crate::merkle::{self, KeyValues, StateKey};
+use crate::schema::{BlockchainError, Header, OpaqueHash, State};
+use crate::store::{self, FinalizedRecord, Store};
+use crate::wal::WriteBatch;
+
+/// Leading bytes of a snapshot file.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"JAMSNAP1";
+
+/// Trusted commitment to the block and state a snapshot must hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotAnchor {
+    /// A finalized block and the root of its posterior state.
+    Finalized {
+        hash: OpaqueHash,
+        state_root: [u8; 32],
+    },
+    /// Header of a child of the snapshot's block, which names its posterior state root.
+    Child(Box<Header>),
+}
+
+impl SnapshotAnchor {
+    /// Block hash and posterior state root the anchor commits to.
+    fn commitment(&self) -> (OpaqueHash, [u8; 32]) {
+        match self {
+            SnapshotAnchor::Finalized { hash, state_root } => (*hash, *state_root),
+            SnapshotAnchor::Child(header) => (header.parent, *header.parent_state_root.as_bytes()),
+        }
+    }
+}
+
+/// Full state at a block.
+///
+/// Memory Usage:
+/// - Fixed: ~350 bytes (header and root)
+/// - Dynamic: every state key/value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub header: Header,
+    pub state_root: [u8; 32],
+    pub key_values: KeyValues,
+}
+
+impl Snapshot {
+    /// Build a snapshot of `state`, the posterior state of `header`.
+    pub fn new(header: Header, state: &State) -> Self {
+        let key_values = state.to_key_values();
+        Snapshot {
+            header,
+            state_root: merkle::merklize(&key_values),
+            key_values,
+        }
+    }
+
+    /// Export the state at `hash` from a store.
+    ///
+    /// `hash` may name any stored block or the finalized root, as long as its header is
+    /// known.
+    pub fn export(store: &dyn Store, hash: &OpaqueHash) -> Result<Self, BlockchainError> {
+        let (header, state_root) = match store.get_block(hash)? {
+            Some(stored) => (stored.block.header, stored.state_root),
+            None => match store::finalized(store)? {
+                Some(FinalizedRecord {
+                    hash: root,
+                    header: Some(header),
+                    state_root,
+                    ..
+                }) if root == *hash => (header, state_root),
+                _ => {
+                    return Err(BlockchainError::StoreError {
+                        reason: format!(
+                            "no block with a header stored for {}",
+                            hex::encode(hash.as_bytes())
+                        ),
+                    })
+                }
+            },
+        };
+
+        let key_values = merkle::collect(&state_root, &|node| store.get_node(node))?;
+        debug!(
+            "Exported snapshot of {} with {} keys",
+            hex::encode(hash.as_bytes()),
+            key_values.len()
+        );
+        Ok(Snapshot {
+            header,
+            state_root,
+            key_values,
+        })
+    }
+
+    /// Hash of the block the snapshot was taken at.
+    pub fn hash(&self) -> OpaqueHash {
+        self.header.hash()
+    }
+
+    /// Check the snapshot against `anchor` and return the state it holds.
+    ///
+    /// The snapshot must be taken at the anchored block and record the anchored state root,
+    /// the key/values must merklize to that root, and the state must be the posterior of
+    /// the header: it records the header's hash and slot.
+    pub fn verify(&self, anchor: &SnapshotAnchor) -> Result<State, BlockchainError> {
+        let hash = self.hash();
+        let (anchor_hash, anchor_root) = anchor.commitment();
+        if hash != anchor_hash {
+            return Err(BlockchainError::InvalidSnapshot {
+                reason: format!(
+                    "snapshot is of {}, anchor names {}",
+                    hex::encode(hash.as_bytes()),
+                    hex::encode(anchor_hash.as_bytes())
+                ),
+            });
+        }
+        if self.state_root != anchor_root {
+            return Err(BlockchainError::InvalidSnapshot {
+                reason: format!(
+                    "snapshot records state root {}, anchor names {}",
+                    hex::encode(self.state_root),
+                    hex::encode(anchor_root)
+                ),
+            });
+        }
+
+        let root = merkle::merklize(&self.key_values);
+        if root != self.state_root {
+            return Err(BlockchainError::InvalidSnapshot {
+                reason: format!(
+                    "key/values merklize to {}, snapshot records {}",
+                    hex::encode(root),
+                    hex::encode(self.state_root)
+                ),
+            });
+        }
+
+        let state = State::from_key_values(&self.key_values)?;
+        if state.last_block_hash != Some(*hash.as_bytes()) {
+            return Err(BlockchainError::InvalidSnapshot {
+                reason: format!(
+                    "state is not the posterior of header {}",
+                    hex::encode(hash.as_bytes())
+                ),
+            });
+        }
+        if state.last_slot != u64::from(self.header.slot) {
+            return Err(BlockchainError::InvalidSnapshot {
+                reason: format!(
+                    "state is at slot {}, header at slot {}",
+                    state.last_slot, self.header.slot
+                ),
+            });
+        }
+        Ok(state)
+    }
+
+    /// Verify the snapshot against `anchor` and write it into an empty store as its
+    /// finalized root.
+    pub fn import_into(
+        &self,
+        store: &mut dyn Store,
+        anchor: &SnapshotAnchor,
+    ) -> Result<State, BlockchainError> {
+        if store::finalized(store)?.is_some() || !store.block_hashes()?.is_empty() {
+            return Err(BlockchainError::StoreError {
+                reason: "snapshots can only be imported into an empty store".to_string(),
+            });
+        }
+        let state = self.verify(anchor)?;
+
+        let mut batch = WriteBatch::new();
+        merkle::merklize_with_nodes(&self.key_values, &mut |hash, bytes| {
+            batch.put_node(hash, bytes)
+        });
+        let hash = self.hash();
+        let record = FinalizedRecord {
+            hash,
+            header: Some(self.header.clone()),
+            state_root: self.state_root,
+            last_state_root: Some(self.state_root),
+        };
+        batch.put_meta(store::META_FINALIZED, &record.encode());
+        batch.put_meta(store::META_BEST_HEAD, &hash.encode());
+        store.commit(batch)?;
+
+        info!(
+            "Imported snapshot of {} at slot {}",
+            hex::encode(hash.as_bytes()),
+            self.header.slot
+        );
+        Ok(state)
+    }
+
+    /// Write the snapshot to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BlockchainError> {
+        fs::write(path, self.encode())?;
+        Ok(())
+    }
+
+    /// Read a snapshot file. The snapshot still has to be verified against an anchor before
+    /// use.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        decode_exact(&fs::read(path)?)
+    }
+}
+
+impl Encode for Snapshot {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        self.header.encode_to(out);
+        self.state_root.encode_to(out);
+        (self.key_values.len() as u64).encode_to(out);
+        for (key, value) in &self.key_values {
+            key.encode_to(out);
+            value.encode_to(out);
+        }
+    }
+}
+
+impl Decode for Snapshot {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        if take(input, SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(BlockchainError::CodecError {
+                reason: "not a JamLiquor snapshot".to_string(),
+            });
+        }
+        let header = Header::decode(input)?;
+        let state_root = <[u8; 32]>::decode(input)?;
+        let count = u64::decode(input)?;
+        let mut key_values = KeyValues::new();
+        for _ in 0..count {
+            let key = StateKey::decode(input)?;
+            let value = Vec::<u8>::decode(input)?;
+            if key_values.insert(key, value).is_some() {
+                return Err(BlockchainError::CodecError {
+                    reason: format!("duplicate snapshot key {}", hex::encode(key)),
+                });
+            }
+        }
+        Ok(Snapshot {
+            header,
+            state_root,
+            key_values,
+        })
+    }
+}
@@ -0,0 +1,310 @@
+//! Structured differences between two states.
+//!
+//! [`diff_key_values`] compares two raw key/value sets key by key. Each differing key is
+//! classified with [`classify_key`], and values of the components JamLiquor models (the
+//! validator sets, Safrole ticket counters, timeslot, CoreTime ledger) and of service
+//! accounts are decoded into named fields, so a difference reads as
+//! `active_validators[3]: 0x.. -> 0x..` rather than two opaque blobs. Values that do not
+//! decode, e.g. components of a GP v0.8 state, are compared as bytes from the first
+//! differing offset.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::codec::{decode_len, Decode};
+use crate::merkle::{KeyValues, StateKey};
+use crate::schema::{OpaqueHash, State, TicketState};
+use crate::state_keys::{
+    classify_key, KeyKind, ACTIVE_VALIDATORS, RECENT_HISTORY, SAFROLE, TIMESLOT,
+};
+
+/// One named field whose value differs; `None` where a side lacks the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// A key whose value differs between the two states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDiff {
+    pub key: StateKey,
+    pub kind: KeyKind,
+    /// Raw values; `None` where a side lacks the key.
+    pub expected: Option<Vec<u8>>,
+    pub actual: Option<Vec<u8>>,
+    /// Decoded fields that differ, in field order.
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Every difference between two states, in key order.
+///
+/// Memory Usage:
+/// - Per differing key: both raw values plus their differing fields as strings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub keys: Vec<KeyDiff>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Number of differing keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The differing field `field`, in any key.
+    pub fn field(&self, field: &str) -> Option<&FieldDiff> {
+        self.keys
+            .iter()
+            .flat_map(|key| &key.fields)
+            .find(|diff| diff.field == field)
+    }
+}
+
+/// Compare two key/value sets.
+pub fn diff_key_values(expected: &KeyValues, actual: &KeyValues) -> StateDiff {
+    let keys: BTreeSet<&StateKey> = expected.keys().chain(actual.keys()).collect();
+    let keys = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (expected, actual) = (expected.get(key), actual.get(key));
+            (expected != actual).then(|| diff_key(key, expected, actual))
+        })
+        .collect();
+    StateDiff { keys }
+}
+
+/// Compare two typed states through their key/value representation.
+pub fn diff_states(expected: &State, actual: &State) -> StateDiff {
+    diff_key_values(&expected.to_key_values(), &actual.to_key_values())
+}
+
+fn diff_key(key: &StateKey, expected: Option<&Vec<u8>>, actual: Option<&Vec<u8>>) -> KeyDiff {
+    let kind = classify_key(key);
+    let decoded = |value: Option<&Vec<u8>>| match value {
+        Some(value) => decode_fields(kind, value),
+        None => Some(Vec::new()),
+    };
+    let fields = match (decoded(expected), decoded(actual)) {
+        (Some(expected), Some(actual)) => diff_fields(expected, actual),
+        _ => vec![diff_bytes(
+            expected.map_or(&[][..], Vec::as_slice),
+            actual.map_or(&[][..], Vec::as_slice),
+        )],
+    };
+    KeyDiff {
+        key: *key,
+        kind,
+        expected: expected.cloned(),
+        actual: actual.cloned(),
+        fields,
+    }
+}
+
+/// Pair fields by name, keeping the expected side's order.
+fn diff_fields(expected: Vec<(String, String)>, actual: Vec<(String, String)>) -> Vec<FieldDiff> {
+    let actual_only: Vec<_> = actual
+        .iter()
+        .filter(|(name, _)| !expected.iter().any(|(other, _)| other == name))
+        .cloned()
+        .collect();
+    let mut diffs: Vec<FieldDiff> = expected
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let other = actual
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, value)| value.clone());
+            (other.as_ref() != Some(&value)).then_some(FieldDiff {
+                field,
+                expected: Some(value),
+                actual: other,
+            })
+        })
+        .collect();
+    diffs.extend(actual_only.into_iter().map(|(field, value)| FieldDiff {
+        field,
+        expected: None,
+        actual: Some(value),
+    }));
+    diffs
+}
+
+/// Undecodable values: the bytes from the first differing offset on.
+fn diff_bytes(expected: &[u8], actual: &[u8]) -> FieldDiff {
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let tail = |value: &[u8]| Some(format!("0x{}", hex::encode(&value[offset..])));
+    FieldDiff {
+        field: format!("bytes[{offset}..]"),
+        expected: tail(expected),
+        actual: tail(actual),
+    }
+}
+
+/// Named fields of a value, or `None` if the value does not decode as its key's kind.
+fn decode_fields(kind: KeyKind, value: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut input = value;
+    let mut fields = Vec::new();
+    match kind {
+        KeyKind::Component(RECENT_HISTORY) => {
+            let hash = Option::<[u8; 32]>::decode(&mut input).ok()?;
+            fields.push(("last_block_hash".to_string(), show_option(hash.map(hex_of))));
+        }
+        KeyKind::Component(SAFROLE) => {
+            let tickets = TicketState::decode(&mut input).ok()?;
+            let named = [
+                ("total_tickets", tickets.total_tickets.to_string()),
+                ("valid_tickets", tickets.valid_tickets.to_string()),
+                ("invalid_tickets", tickets.invalid_tickets.to_string()),
+                (
+                    "last_ticket_id",
+                    show_option(tickets.last_ticket_id.map(|id| hex_of(*id.as_bytes()))),
+                ),
+                ("sealed_by_tickets", tickets.sealed_by_tickets.to_string()),
+                (
+                    "next_sealed_by_tickets",
+                    tickets.next_sealed_by_tickets.to_string(),
+                ),
+                ("entropy", hex_of(*tickets.entropy.as_bytes())),
+                (
+                    "sealing_tickets",
+                    tickets.sealing_tickets.len().to_string(),
+                ),
+                ("next_tickets", tickets.next_tickets.len().to_string()),
+            ];
+            fields.extend(
+                named
+                    .into_iter()
+                    .map(|(name, value)| (format!("tickets.{name}"), value)),
+            );
+            validator_fields("pending_validators", &mut input, &mut fields)?;
+        }
+        KeyKind::Component(ACTIVE_VALIDATORS) => {
+            validator_fields("active_validators", &mut input, &mut fields)?;
+        }
+        KeyKind::Component(TIMESLOT) => {
+            fields.push((
+                "timeslot".to_string(),
+                u32::decode(&mut input).ok()?.to_string(),
+            ));
+        }
+        KeyKind::Extension => {
+            let mut field = |name: String, value: String| fields.push((name, value));
+            field(
+                "counter".to_string(),
+                u64::decode(&mut input).ok()?.to_string(),
+            );
+            field(
+                "coretime.total_allocated".to_string(),
+                u64::decode(&mut input).ok()?.to_string(),
+            );
+            field(
+                "coretime.total_consumed".to_string(),
+                u64::decode(&mut input).ok()?.to_string(),
+            );
+            field(
+                "coretime.last_block_slot".to_string(),
+                show_option(
+                    Option::<u64>::decode(&mut input)
+                        .ok()?
+                        .map(|s| s.to_string()),
+                ),
+            );
+            for _ in 0..decode_len(&mut input).ok()? {
+                let core = u16::decode(&mut input).ok()?;
+                for name in ["total_consumed", "last_block_slot", "last_block_consumed"] {
+                    field(
+                        format!("coretime.core[{core}].{name}"),
+                        u64::decode(&mut input).ok()?.to_string(),
+                    );
+                }
+            }
+        }
+        // GP account info starts with the code hash and balance; the rest of the
+        // encoding is compared as bytes.
+        KeyKind::ServiceAccount(_) => {
+            let code_hash = <[u8; 32]>::decode(&mut input).ok()?;
+            fields.push(("code_hash".to_string(), hex_of(code_hash)));
+            fields.push((
+                "balance".to_string(),
+                u64::decode(&mut input).ok()?.to_string(),
+            ));
+            fields.push(("rest".to_string(), format!("0x{}", hex::encode(input))));
+            input = &[];
+        }
+        KeyKind::Component(_) | KeyKind::ServiceData { .. } => return None,
+    }
+    input.is_empty().then_some(fields)
+}
+
+fn validator_fields(
+    name: &str,
+    input: &mut &[u8],
+    fields: &mut Vec<(String, String)>,
+) -> Option<()> {
+    let count = decode_len(input).ok()?;
+    fields.push((format!("{name}.len"), count.to_string()));
+    for i in 0..count {
+        let key = OpaqueHash::decode(input).ok()?;
+        fields.push((format!("{name}[{i}]"), hex_of(*key.as_bytes())));
+    }
+    Some(())
+}
+
+fn hex_of(bytes: [u8; 32]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn show_option(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "none".to_string())
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "<missing>".into());
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+impl fmt::Display for KeyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match (&self.expected, &self.actual) {
+            (Some(_), None) => "only in expected",
+            (None, Some(_)) => "only in actual",
+            _ => "differs",
+        };
+        write!(f, "0x{} {}: {status}", hex::encode(self.key), self.kind)?;
+        for field in &self.fields {
+            write!(f, "\n    {field}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "states match");
+        }
+        write!(f, "{} keys differ", self.len())?;
+        for key in &self.keys {
+            write!(f, "\n{key}")?;
+        }
+        Ok(())
+    }
+}
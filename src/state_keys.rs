@@ -0,0 +1,230 @@
+//! State serialization into merklizable key/values (GP Appendix D.1).
+//!
+//! Each modelled state component is stored under the GP component key `C(i)`. Values use
+//! the crate's binary codec and follow the simplified state model in [`State`], so roots
+//! are only comparable with other JamLiquor nodes until the state is aligned with GP v0.8.
+
+use crate::codec::{decode_exact, decode_seq, encode_seq, Decode, Encode};
+use crate::coretime::CoreTimeLedger;
+use crate::merkle::{merklize, KeyValues, StateKey, STATE_KEY_LEN};
+use crate::schema::{blake2b_256, BlockchainError, OpaqueHash, State, TicketState};
+
+/// `C(3)`: recent history (beta), here the hash of the last block.
+pub const RECENT_HISTORY: u8 = 3;
+/// `C(4)`: Safrole state (gamma): pending validator keys, sealing mode, ticket counters,
+/// and the epoch entropy and sealing tickets.
+pub const SAFROLE: u8 = 4;
+/// `C(6)`: entropy pool (eta). Only read from GP-serialized states; JamLiquor keeps the
+/// epoch entropy in [`SAFROLE`].
+pub const ENTROPY: u8 = 6;
+/// `C(8)`: active validator keys (kappa).
+pub const ACTIVE_VALIDATORS: u8 = 8;
+/// `C(11)`: most recent timeslot (tau).
+pub const TIMESLOT: u8 = 11;
+/// JamLiquor-specific component holding the counter and CoreTime ledger.
+///
+/// Chosen outside GP's component range; `C(255, s)` keys belong to service accounts.
+pub const JAMLIQUOR_EXTENSION: u8 = 0x80;
+
+/// GP names of the state components `C(1)` to `C(16)`.
+const COMPONENT_NAMES: [&str; 16] = [
+    "alpha (authorizer pools)",
+    "phi (authorizer queues)",
+    "beta (recent history)",
+    "gamma (Safrole state)",
+    "psi (disputes)",
+    "eta (entropy)",
+    "iota (staging validators)",
+    "kappa (active validators)",
+    "lambda (previous validators)",
+    "rho (pending reports)",
+    "tau (timeslot)",
+    "chi (privileged services)",
+    "pi (statistics)",
+    "theta (ready queue)",
+    "xi (accumulation history)",
+    "accumulation outputs",
+];
+
+/// The component key `C(i)`.
+pub fn component_key(index: u8) -> StateKey {
+    let mut key = [0u8; STATE_KEY_LEN];
+    key[0] = index;
+    key
+}
+
+/// The service data key `C(s, h)`: the service id interleaved with the hash of `h`.
+pub fn service_key(service: u32, h: &[u8]) -> StateKey {
+    let id = service.to_le_bytes();
+    let hash = blake2b_256(h);
+    let mut key = [0u8; STATE_KEY_LEN];
+    for i in 0..4 {
+        key[2 * i] = id[i];
+        key[2 * i + 1] = hash[i];
+    }
+    key[8..].copy_from_slice(&hash[4..STATE_KEY_LEN - 4]);
+    key
+}
+
+/// Key of item `storage_key` in a service's storage.
+pub fn storage_key(service: u32, storage_key: &[u8]) -> StateKey {
+    service_key(
+        service,
+        &[&u32::MAX.to_le_bytes()[..], storage_key].concat(),
+    )
+}
+
+/// Key of the preimage of `hash` held by a service.
+pub fn preimage_key(service: u32, hash: &OpaqueHash) -> StateKey {
+    service_key(
+        service,
+        &[&(u32::MAX - 1).to_le_bytes()[..], hash.as_bytes()].concat(),
+    )
+}
+
+/// Key of a service's request status for the preimage of `hash` with length `len`.
+pub fn lookup_key(service: u32, hash: &OpaqueHash, len: u32) -> StateKey {
+    service_key(service, &[&len.to_le_bytes()[..], hash.as_bytes()].concat())
+}
+
+/// What a state key addresses, decoded from its layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    /// `C(i)` for a GP component `1..=16`.
+    Component(u8),
+    /// The JamLiquor extension component.
+    Extension,
+    /// `C(255, s)`: the account of service `s`.
+    ServiceAccount(u32),
+    /// `C(s, h)`: a storage, preimage or lookup item of service `s`, with the first 27
+    /// bytes of the hash of `h`. The kind of item cannot be told from the key alone.
+    ServiceData { service: u32, hash: [u8; 27] },
+}
+
+/// Decode the layout of `key`.
+pub fn classify_key(key: &StateKey) -> KeyKind {
+    let rest_zero = |from: usize| key[from..].iter().all(|&b| b == 0);
+    match key[0] {
+        index @ 1..=16 if rest_zero(1) => KeyKind::Component(index),
+        JAMLIQUOR_EXTENSION if rest_zero(1) => KeyKind::Extension,
+        255 if key[2] == 0 && key[4] == 0 && key[6] == 0 && rest_zero(8) => {
+            KeyKind::ServiceAccount(u32::from_le_bytes([key[1], key[3], key[5], key[7]]))
+        }
+        _ => {
+            // Service data keys interleave the service id with the item hash.
+            let mut hash = [0u8; 27];
+            for i in 0..4 {
+                hash[i] = key[2 * i + 1];
+            }
+            hash[4..].copy_from_slice(&key[8..]);
+            KeyKind::ServiceData {
+                service: u32::from_le_bytes([key[0], key[2], key[4], key[6]]),
+                hash,
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyKind::Component(index) => {
+                write!(f, "C({index}) {}", COMPONENT_NAMES[*index as usize - 1])
+            }
+            KeyKind::Extension => write!(
+                f,
+                "C({JAMLIQUOR_EXTENSION}) JamLiquor extension (counter, CoreTime)"
+            ),
+            KeyKind::ServiceAccount(service) => {
+                write!(f, "C(255, {service}) delta (service account)")
+            }
+            KeyKind::ServiceData { service, hash } => write!(
+                f,
+                "service {service} storage, preimage or lookup item 0x{}",
+                hex::encode(hash)
+            ),
+        }
+    }
+}
+
+/// Name the state component a key belongs to, for diagnostics.
+pub fn describe_key(key: &StateKey) -> String {
+    classify_key(key).to_string()
+}
+
+impl State {
+    /// Serialize the state into its key/value representation.
+    ///
+    /// `last_state_root` is not part of the set: it is the root of the set itself.
+    pub fn to_key_values(&self) -> KeyValues {
+        let mut kvs = KeyValues::new();
+        kvs.insert(component_key(RECENT_HISTORY), self.last_block_hash.encode());
+
+        let mut safrole = self.ticket_state.encode();
+        encode_seq(&self.pending_validators, &mut safrole);
+        kvs.insert(component_key(SAFROLE), safrole);
+
+        let mut active = Vec::new();
+        encode_seq(&self.active_validators, &mut active);
+        kvs.insert(component_key(ACTIVE_VALIDATORS), active);
+
+        kvs.insert(component_key(TIMESLOT), (self.last_slot as u32).encode());
+
+        let mut extension = self.counter.encode();
+        self.coretime.encode_to(&mut extension);
+        kvs.insert(component_key(JAMLIQUOR_EXTENSION), extension);
+
+        kvs
+    }
+
+    /// Rebuild a state from its key/value representation.
+    pub fn from_key_values(kvs: &KeyValues) -> Result<Self, BlockchainError> {
+        let get = |index: u8| {
+            kvs.get(&component_key(index))
+                .map(Vec::as_slice)
+                .ok_or_else(|| BlockchainError::StateTransitionError {
+                    reason: format!("state component C({index}) missing"),
+                })
+        };
+
+        let mut safrole = get(SAFROLE)?;
+        let ticket_state = TicketState::decode(&mut safrole)?;
+        let pending_validators: Vec<OpaqueHash> = decode_seq(&mut safrole)?;
+        if !safrole.is_empty() {
+            return Err(BlockchainError::CodecError {
+                reason: format!("{} trailing bytes in C({SAFROLE})", safrole.len()),
+            });
+        }
+
+        let mut extension = get(JAMLIQUOR_EXTENSION)?;
+        let counter = u64::decode(&mut extension)?;
+        let coretime = CoreTimeLedger::decode(&mut extension)?;
+        if !extension.is_empty() {
+            return Err(BlockchainError::CodecError {
+                reason: format!(
+                    "{} trailing bytes in C({JAMLIQUOR_EXTENSION})",
+                    extension.len()
+                ),
+            });
+        }
+
+        let mut active = get(ACTIVE_VALIDATORS)?;
+        let active_validators = decode_seq(&mut active)?;
+
+        Ok(State {
+            last_slot: u64::from(decode_exact::<u32>(get(TIMESLOT)?)?),
+            counter,
+            ticket_state,
+            last_block_hash: decode_exact(get(RECENT_HISTORY)?)?,
+            last_state_root: Some(merklize(kvs)),
+            coretime,
+            active_validators,
+            pending_validators,
+        })
+    }
+
+    /// Merkle root of the state's key/value representation.
+    pub fn state_root(&self) -> [u8; 32] {
+        merklize(&self.to_key_values())
+    }
+}
@@ -0,0 +1,697 @@
+//! Persistent block and state storage.
+//!
+//! A [`Store`] keeps imported blocks by hash (indexed by slot), the state trie nodes
+//! produced by [`merkle::merklize_with_nodes`] by hash, and a few metadata entries such as
+//! the best and finalized heads. [`FileStore`] is the default on-disk backend;
+//! [`MemoryStore`] holds the same data in memory for tests and short-lived tools.
+//!
+//! Trie nodes are content-addressed and shared between states. Nodes that no retained
+//! state reaches any more are swept with [`stage_prune`] when a block is finalized.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+
+use crate::codec::{decode_exact, Decode, Encode};
+use crate::merkle::{self, EMPTY_ROOT};
+use crate::schema::{Block, BlockchainError, Header, OpaqueHash, State};
+use crate::wal::{Wal, WriteBatch, WriteOp};
+
+/// Metadata key of the best head hash.
+pub const META_BEST_HEAD: &str = "best_head";
+/// Metadata key of the finalized root record.
+pub const META_FINALIZED: &str = "finalized";
+
+/// A block as persisted, together with the root of its posterior state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredBlock {
+    pub block: Block,
+    pub state_root: [u8; 32],
+}
+
+/// The finalized root of the block tree.
+///
+/// The root may have no header (an unanchored or externally seeded root) and may record
+/// a parent state root that is not the root of its own trie, so both are kept explicitly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalizedRecord {
+    pub hash: OpaqueHash,
+    pub header: Option<Header>,
+    pub state_root: [u8; 32],
+    pub last_state_root: Option<[u8; 32]>,
+}
+
+/// Storage backend for blocks, state trie nodes and chain metadata.
+pub trait Store: Debug + Send {
+    /// Store a block with the root of its posterior state.
+    fn put_block(&mut self, block: &Block, state_root: [u8; 32]) -> Result<(), BlockchainError>;
+
+    /// Look up a block by header hash.
+    fn get_block(&self, hash: &OpaqueHash) -> Result<Option<StoredBlock>, BlockchainError>;
+
+    /// Hashes of the stored blocks at `slot`.
+    fn block_hashes_at(&self, slot: u32) -> Result<Vec<OpaqueHash>, BlockchainError>;
+
+    /// Hashes of all stored blocks, in ascending slot order.
+    fn block_hashes(&self) -> Result<Vec<OpaqueHash>, BlockchainError>;
+
+    /// Remove a block; removing an unknown block is not an error.
+    fn delete_block(&mut self, hash: &OpaqueHash) -> Result<(), BlockchainError>;
+
+    /// Store a trie node (or a non-embedded value) under its hash.
+    fn put_node(&mut self, hash: [u8; 32], bytes: &[u8]) -> Result<(), BlockchainError>;
+
+    /// Look up a trie node by hash.
+    fn get_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, BlockchainError>;
+
+    /// Remove a trie node; removing an unknown node is not an error.
+    fn delete_node(&mut self, hash: &[u8; 32]) -> Result<(), BlockchainError>;
+
+    /// Hashes of all stored trie nodes and values.
+    fn node_hashes(&self) -> Result<Vec<[u8; 32]>, BlockchainError>;
+
+    /// Set a metadata entry.
+    fn put_meta(&mut self, key: &str, value: &[u8]) -> Result<(), BlockchainError>;
+
+    /// Read a metadata entry.
+    fn get_meta(&self, key: &str) -> Result<Option<Vec<u8>>, BlockchainError>;
+
+    /// Apply a batch of mutations as one unit.
+    ///
+    /// The default applies the operations one by one; durable backends override this so
+    /// a crash never leaves a batch half applied.
+    fn commit(&mut self, batch: WriteBatch) -> Result<(), BlockchainError> {
+        for op in batch.ops() {
+            match op {
+                WriteOp::PutBlock { block, state_root } => self.put_block(block, *state_root)?,
+                WriteOp::DeleteBlock { hash } => self.delete_block(hash)?,
+                WriteOp::PutNode { hash, bytes } => self.put_node(*hash, bytes)?,
+                WriteOp::DeleteNode { hash } => self.delete_node(hash)?,
+                WriteOp::PutMeta { key, value } => self.put_meta(key, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Add the trie nodes of `state` missing from `store` to `batch` and return its root.
+///
+/// Nodes are shared between states, so this is the state delta against what is stored.
+pub fn stage_state(
+    store: &dyn Store,
+    batch: &mut WriteBatch,
+    state: &State,
+) -> Result<[u8; 32], BlockchainError> {
+    let mut result = Ok(());
+    let root = merkle::merklize_with_nodes(&state.to_key_values(), &mut |hash, bytes| {
+        if result.is_ok() {
+            match store.get_node(&hash) {
+                Ok(Some(_)) => {}
+                Ok(None) => batch.put_node(hash, bytes),
+                Err(e) => result = Err(e),
+            }
+        }
+    });
+    result.map(|_| root)
+}
+
+/// Add the removal of every trie node that none of the `live` state roots reaches to
+/// `batch`, returning the number of nodes removed.
+///
+/// Live states may be stored or staged earlier in `batch`.
+pub fn stage_prune(
+    store: &dyn Store,
+    batch: &mut WriteBatch,
+    live: &[[u8; 32]],
+) -> Result<usize, BlockchainError> {
+    let staged: HashMap<&[u8; 32], &Vec<u8>> = batch
+        .ops()
+        .iter()
+        .filter_map(|op| match op {
+            WriteOp::PutNode { hash, bytes } => Some((hash, bytes)),
+            _ => None,
+        })
+        .collect();
+    let lookup = |hash: &[u8; 32]| match staged.get(hash) {
+        Some(bytes) => Ok(Some(bytes.to_vec())),
+        None => store.get_node(hash),
+    };
+    let mut reachable = HashSet::new();
+    for root in live {
+        merkle::reachable(root, &lookup, &mut reachable)?;
+    }
+    let mut pruned = 0;
+    for hash in store.node_hashes()? {
+        if !reachable.contains(&hash) {
+            batch.delete_node(hash);
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Write a state's trie nodes to `store` and return its root.
+pub fn save_state(store: &mut dyn Store, state: &State) -> Result<[u8; 32], BlockchainError> {
+    let mut batch = WriteBatch::new();
+    let root = stage_state(store, &mut batch, state)?;
+    store.commit(batch)?;
+    Ok(root)
+}
+
+/// Rebuild the state with trie root `root` from `store`.
+///
+/// The returned state records `root` as its state root.
+pub fn load_state(store: &dyn Store, root: &[u8; 32]) -> Result<State, BlockchainError> {
+    if *root == EMPTY_ROOT {
+        return Err(BlockchainError::StoreError {
+            reason: "cannot load a state from the empty root".to_string(),
+        });
+    }
+    let kvs = merkle::collect(root, &|hash| store.get_node(hash))?;
+    let mut state = State::from_key_values(&kvs)?;
+    state.last_state_root = Some(*root);
+    Ok(state)
+}
+
+/// Read the best head, if one has been recorded.
+pub fn best_head(store: &dyn Store) -> Result<Option<OpaqueHash>, BlockchainError> {
+    store
+        .get_meta(META_BEST_HEAD)?
+        .map(|bytes| decode_exact(&bytes))
+        .transpose()
+}
+
+/// Read the finalized root, if one has been recorded.
+pub fn finalized(store: &dyn Store) -> Result<Option<FinalizedRecord>, BlockchainError> {
+    store
+        .get_meta(META_FINALIZED)?
+        .map(|bytes| decode_exact(&bytes))
+        .transpose()
+}
+
+/// In-memory store.
+///
+/// Memory Usage:
+/// - Dynamic: every stored block, trie node and metadata value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStore {
+    blocks: HashMap<OpaqueHash, StoredBlock>,
+    slots: BTreeMap<u32, Vec<OpaqueHash>>,
+    nodes: HashMap<[u8; 32], Vec<u8>>,
+    meta: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn put_block(&mut self, block: &Block, state_root: [u8; 32]) -> Result<(), BlockchainError> {
+        let hash = block.header.hash();
+        let previous = self.blocks.insert(
+            hash,
+            StoredBlock {
+                block: block.clone(),
+                state_root,
+            },
+        );
+        if previous.is_none() {
+            self.slots.entry(block.header.slot).or_default().push(hash);
+        }
+        Ok(())
+    }
+
+    fn get_block(&self, hash: &OpaqueHash) -> Result<Option<StoredBlock>, BlockchainError> {
+        Ok(self.blocks.get(hash).cloned())
+    }
+
+    fn block_hashes_at(&self, slot: u32) -> Result<Vec<OpaqueHash>, BlockchainError> {
+        Ok(self.slots.get(&slot).cloned().unwrap_or_default())
+    }
+
+    fn block_hashes(&self) -> Result<Vec<OpaqueHash>, BlockchainError> {
+        Ok(self.slots.values().flatten().copied().collect())
+    }
+
+    fn delete_block(&mut self, hash: &OpaqueHash) -> Result<(), BlockchainError> {
+        if let Some(stored) = self.blocks.remove(hash) {
+            let slot = stored.block.header.slot;
+            if let Some(hashes) = self.slots.get_mut(&slot) {
+                hashes.retain(|h| h != hash);
+                if hashes.is_empty() {
+                    self.slots.remove(&slot);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn put_node(&mut self, hash: [u8; 32], bytes: &[u8]) -> Result<(), BlockchainError> {
+        self.nodes.entry(hash).or_insert_with(|| bytes.to_vec());
+        Ok(())
+    }
+
+    fn get_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        Ok(self.nodes.get(hash).cloned())
+    }
+
+    fn delete_node(&mut self, hash: &[u8; 32]) -> Result<(), BlockchainError> {
+        self.nodes.remove(hash);
+        Ok(())
+    }
+
+    fn node_hashes(&self) -> Result<Vec<[u8; 32]>, BlockchainError> {
+        Ok(self.nodes.keys().copied().collect())
+    }
+
+    fn put_meta(&mut self, key: &str, value: &[u8]) -> Result<(), BlockchainError> {
+        self.meta.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<Vec<u8>>, BlockchainError> {
+        Ok(self.meta.get(key).cloned())
+    }
+}
+
+/// Store backed by plain files in a data directory.
+///
+/// Layout:
+/// - `blocks/<hash>.bin`: binary-encoded [`StoredBlock`]
+/// - `slots/<slot>`: concatenated hashes of the blocks at that slot
+/// - `nodes/<first byte>/<hash>`: trie nodes and values
+/// - `meta/<key>`: metadata values
+/// - `wal.log`: [write-ahead log](crate::wal) of the batch being committed
+///
+/// Every change goes through [`Store::commit`]: the batch is synced to the log before any
+/// file is written, and files are written to a temporary path, renamed into place and
+/// their directory synced, so a crash leaves the store either before or after the batch
+/// once the log is replayed. A batch that fails to apply stays in the log and is replayed
+/// before the next commit.
+///
+/// Memory Usage:
+/// - Fixed: ~56 bytes (paths and a flag)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStore {
+    root: PathBuf,
+    wal: Wal,
+    /// The log holds a batch that has not been fully applied.
+    unapplied: bool,
+    #[cfg(feature = "fault-injection")]
+    faults: Faults,
+}
+
+/// Simulated power cut for the crash-recovery tests (`fault-injection` feature).
+#[cfg(feature = "fault-injection")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Faults {
+    /// Number of disk writes performed since the store was opened.
+    writes: usize,
+    /// Write at which the power cut happens.
+    crash_at: Option<usize>,
+}
+
+impl FileStore {
+    /// Open (creating if needed) a store in `dir`, recovering any interrupted commit.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, BlockchainError> {
+        let root = dir.as_ref().to_path_buf();
+        for sub in ["blocks", "slots", "nodes", "meta"] {
+            fs::create_dir_all(root.join(sub))?;
+        }
+        sync_dir(&root)?;
+        let mut store = FileStore {
+            wal: Wal::new(root.join("wal.log")),
+            root,
+            unapplied: false,
+            #[cfg(feature = "fault-injection")]
+            faults: Faults::default(),
+        };
+        store.replay()?;
+
+        debug!("Opened file store at {}", store.root.display());
+        Ok(store)
+    }
+
+    /// Simulate a power cut at the `write`-th disk write from now (counting from zero).
+    ///
+    /// If that write is a log append only part of the record reaches the disk; every
+    /// later write fails as if the process had died. Reopening the directory with
+    /// [`FileStore::open`] then exercises recovery.
+    #[cfg(feature = "fault-injection")]
+    pub fn crash_at_write(mut self, write: usize) -> Self {
+        self.faults.crash_at = Some(self.faults.writes + write);
+        self
+    }
+
+    /// Restore power after [`FileStore::crash_at_write`] without reopening the store.
+    #[cfg(feature = "fault-injection")]
+    pub fn restore_power(&mut self) {
+        self.faults.crash_at = None;
+    }
+
+    /// Data directory of the store.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    fn block_path(&self, hash: &OpaqueHash) -> PathBuf {
+        self.root
+            .join("blocks")
+            .join(format!("{}.bin", hex::encode(hash.as_bytes())))
+    }
+
+    fn slot_path(&self, slot: u32) -> PathBuf {
+        self.root.join("slots").join(slot.to_string())
+    }
+
+    fn node_path(&self, hash: &[u8; 32]) -> PathBuf {
+        self.root
+            .join("nodes")
+            .join(format!("{:02x}", hash[0]))
+            .join(hex::encode(hash))
+    }
+
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.root.join("meta").join(key)
+    }
+
+    /// Account for one disk write, failing if the simulated crash has happened.
+    #[cfg(feature = "fault-injection")]
+    fn write_point(&mut self) -> Result<(), BlockchainError> {
+        let write = self.faults.writes;
+        self.faults.writes += 1;
+        match self.faults.crash_at {
+            Some(crash) if write >= crash => Err(BlockchainError::StoreError {
+                reason: format!("simulated crash at write {crash}"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    fn write_point(&mut self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+
+    /// Apply every batch left in the log, then clear it.
+    fn replay(&mut self) -> Result<(), BlockchainError> {
+        let pending = self.wal.recover()?;
+        if !pending.is_empty() {
+            info!("Replaying {} interrupted store commits", pending.len());
+        }
+        for batch in &pending {
+            self.apply(batch)?;
+        }
+        self.write_point()?;
+        self.wal.truncate()?;
+        self.unapplied = false;
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, bytes: &[u8]) -> Result<(), BlockchainError> {
+        self.write_point()?;
+        write_atomic(path, bytes)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<(), BlockchainError> {
+        self.write_point()?;
+        remove_if_exists(path)
+    }
+
+    fn write_slot(&mut self, slot: u32, hashes: &[OpaqueHash]) -> Result<(), BlockchainError> {
+        let path = self.slot_path(slot);
+        if hashes.is_empty() {
+            return self.remove_file(&path);
+        }
+        let bytes: Vec<u8> = hashes.iter().flat_map(|h| *h.as_bytes()).collect();
+        self.write_file(&path, &bytes)
+    }
+
+    /// Apply a batch to the data files. Every operation is idempotent.
+    fn apply(&mut self, batch: &WriteBatch) -> Result<(), BlockchainError> {
+        for op in batch.ops() {
+            match op {
+                WriteOp::PutBlock { block, state_root } => {
+                    let hash = block.header.hash();
+                    let stored = StoredBlock {
+                        block: (**block).clone(),
+                        state_root: *state_root,
+                    };
+                    self.write_file(&self.block_path(&hash), &stored.encode())?;
+
+                    let mut hashes = self.block_hashes_at(block.header.slot)?;
+                    if !hashes.contains(&hash) {
+                        hashes.push(hash);
+                        self.write_slot(block.header.slot, &hashes)?;
+                    }
+                }
+                WriteOp::DeleteBlock { hash } => {
+                    let Some(stored) = self.get_block(hash)? else {
+                        continue;
+                    };
+                    let slot = stored.block.header.slot;
+                    let mut hashes = self.block_hashes_at(slot)?;
+                    hashes.retain(|h| h != hash);
+                    self.write_slot(slot, &hashes)?;
+                    self.remove_file(&self.block_path(hash))?;
+                }
+                WriteOp::PutNode { hash, bytes } => {
+                    let path = self.node_path(hash);
+                    if !path.exists() {
+                        let shard = path.parent().expect("node path has a shard directory");
+                        if !shard.exists() {
+                            fs::create_dir(shard)?;
+                            sync_dir(&self.root.join("nodes"))?;
+                        }
+                        self.write_file(&path, bytes)?;
+                    }
+                }
+                WriteOp::DeleteNode { hash } => {
+                    self.remove_file(&self.node_path(hash))?;
+                }
+                WriteOp::PutMeta { key, value } => {
+                    self.write_file(&self.meta_path(key), value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `bytes` to `path` through a synced temporary file and a rename, then sync the
+/// directory so the rename itself survives a power cut.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), BlockchainError> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_dir(parent_dir(path))
+}
+
+/// Sync a directory, making created, renamed and removed entries in it durable.
+pub(crate) fn sync_dir(dir: &Path) -> Result<(), BlockchainError> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Directory holding `path`, `.` for a bare file name.
+pub(crate) fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, BlockchainError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), BlockchainError> {
+    match fs::remove_file(path) {
+        Ok(()) => sync_dir(parent_dir(path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse_hashes(bytes: &[u8]) -> Result<Vec<OpaqueHash>, BlockchainError> {
+    if !bytes.len().is_multiple_of(32) {
+        return Err(BlockchainError::StoreError {
+            reason: format!(
+                "slot index of {} bytes is not a list of hashes",
+                bytes.len()
+            ),
+        });
+    }
+    let (hashes, _) = bytes.as_chunks::<32>();
+    Ok(hashes.iter().map(|hash| OpaqueHash::new(*hash)).collect())
+}
+
+impl Store for FileStore {
+    fn put_block(&mut self, block: &Block, state_root: [u8; 32]) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::new();
+        batch.put_block(block, state_root);
+        self.commit(batch)
+    }
+
+    fn get_block(&self, hash: &OpaqueHash) -> Result<Option<StoredBlock>, BlockchainError> {
+        read_if_exists(&self.block_path(hash))?
+            .map(|bytes| decode_exact(&bytes))
+            .transpose()
+    }
+
+    fn block_hashes_at(&self, slot: u32) -> Result<Vec<OpaqueHash>, BlockchainError> {
+        match read_if_exists(&self.slot_path(slot))? {
+            Some(bytes) => parse_hashes(&bytes),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn block_hashes(&self) -> Result<Vec<OpaqueHash>, BlockchainError> {
+        let mut slots = Vec::new();
+        for entry in fs::read_dir(self.root.join("slots"))? {
+            let name = entry?.file_name();
+            // Leftover temporary files from an interrupted write are skipped.
+            if let Some(slot) = name.to_str().and_then(|name| name.parse::<u32>().ok()) {
+                slots.push(slot);
+            }
+        }
+        slots.sort_unstable();
+
+        let mut hashes = Vec::new();
+        for slot in slots {
+            hashes.extend(self.block_hashes_at(slot)?);
+        }
+        Ok(hashes)
+    }
+
+    fn delete_block(&mut self, hash: &OpaqueHash) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::new();
+        batch.delete_block(*hash);
+        self.commit(batch)
+    }
+
+    fn put_node(&mut self, hash: [u8; 32], bytes: &[u8]) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::new();
+        batch.put_node(hash, bytes);
+        self.commit(batch)
+    }
+
+    fn get_node(&self, hash: &[u8; 32]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        read_if_exists(&self.node_path(hash))
+    }
+
+    fn delete_node(&mut self, hash: &[u8; 32]) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::new();
+        batch.delete_node(*hash);
+        self.commit(batch)
+    }
+
+    fn node_hashes(&self) -> Result<Vec<[u8; 32]>, BlockchainError> {
+        let mut hashes = Vec::new();
+        for shard in fs::read_dir(self.root.join("nodes"))? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(shard.path())? {
+                // Leftover temporary files from an interrupted write are skipped.
+                let name = entry?.file_name();
+                let mut hash = [0u8; 32];
+                if name
+                    .to_str()
+                    .is_some_and(|name| hex::decode_to_slice(name, &mut hash).is_ok())
+                {
+                    hashes.push(hash);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    fn put_meta(&mut self, key: &str, value: &[u8]) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::new();
+        batch.put_meta(key, value);
+        self.commit(batch)
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<Vec<u8>>, BlockchainError> {
+        read_if_exists(&self.meta_path(key))
+    }
+
+    /// Log the batch, apply it, then clear the log.
+    ///
+    /// A batch left in the log by a failed commit is applied first, so clearing the log
+    /// never drops it.
+    fn commit(&mut self, batch: WriteBatch) -> Result<(), BlockchainError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        if self.unapplied {
+            self.replay()?;
+        }
+        let record = Wal::record(&batch);
+        #[cfg(feature = "fault-injection")]
+        if self.faults.crash_at == Some(self.faults.writes) {
+            // A power cut mid-append leaves a torn record behind.
+            self.wal.append(&record[..record.len() / 2])?;
+        }
+        self.write_point()?;
+        self.unapplied = true;
+        self.wal.append(&record)?;
+
+        self.apply(&batch)?;
+
+        self.write_point()?;
+        self.wal.truncate()?;
+        self.unapplied = false;
+        Ok(())
+    }
+}
+
+impl Encode for StoredBlock {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.block.encode_to(out);
+        self.state_root.encode_to(out);
+    }
+}
+
+impl Decode for StoredBlock {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(StoredBlock {
+            block: Block::decode(input)?,
+            state_root: <[u8; 32]>::decode(input)?,
+        })
+    }
+}
+
+impl Encode for FinalizedRecord {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.hash.encode_to(out);
+        self.header.encode_to(out);
+        self.state_root.encode_to(out);
+        self.last_state_root.encode_to(out);
+    }
+}
+
+impl Decode for FinalizedRecord {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(FinalizedRecord {
+            hash: OpaqueHash::decode(input)?,
+            header: Option::decode(input)?,
+            state_root: <[u8; 32]>::decode(input)?,
+            last_state_root: Option::decode(input)?,
+        })
+    }
+}
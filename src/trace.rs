@@ -0,0 +1,321 @@
+//! Replay of multi-block conformance traces.
+//!
+//! A trace directory (the jam-test-vectors `traces/*` sets) holds an optional
+//! `genesis.json` and numbered step files, each a `pre_state`/`block`/`post_state` triple
+//! with raw state key/values. Steps are replayed in file name order through one
+//! [`Importer`]; whenever the importer's head does not already hold the step's pre-state
+//! it is re-anchored there, so a single divergence does not hide later steps' behaviour.
+//!
+//! After each block the posterior key/values are compared with `post_state`. A block the
+//! importer rejects passes only if the trace expects the state to stay unchanged. Blocks
+//! of GP traces carry Bandersnatch seals, which the importer cannot verify yet (see
+//! [`crate::authorer`]), so their steps fail unless the block was meant to be rejected.
+//!
+//! Traces written by JamLiquor itself (their states carry the
+//! [`JAMLIQUOR_EXTENSION`] component) are compared key for key and by state root.
+//! GP-serialized traces are only compared on the components whose JamLiquor encoding
+//! already follows GP; the remaining components are listed as unmodelled and the state
+//! root, which covers them, is not compared. Such steps are only [`StepVerdict::Partial`]:
+//! they are replayed on, but never count as passed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde_json::Value;
+
+use crate::chain_spec::ChainSpec;
+use crate::genesis::{field, parse_hex, parse_keyvals, Genesis};
+use crate::merkle::{KeyValues, StateKey};
+use crate::schema::{Block, BlockchainError};
+use crate::state_keys::{component_key, describe_key, JAMLIQUOR_EXTENSION, TIMESLOT};
+use crate::Importer;
+
+/// Components whose JamLiquor encoding matches GP, so they can be checked against a
+/// GP-serialized post-state. The others follow the simplified model in
+/// [`crate::state_keys`].
+const GP_ENCODED_COMPONENTS: &[u8] = &[TIMESLOT];
+
+/// A state as given in a trace: its root and every key/value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawState {
+    pub state_root: [u8; 32],
+    pub key_values: KeyValues,
+}
+
+impl RawState {
+    fn from_json(value: &Value) -> Result<Self, BlockchainError> {
+        let root = parse_hex(field(value, "state_root")?, "state_root")?;
+        let state_root = root
+            .try_into()
+            .map_err(|_| BlockchainError::InvalidBlockStructure {
+                reason: "`state_root` is not 32 bytes".to_string(),
+            })?;
+        Ok(RawState {
+            state_root,
+            key_values: parse_keyvals(field(value, "keyvals")?)?,
+        })
+    }
+}
+
+/// One step of a trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub pre_state: RawState,
+    pub block: Block,
+    pub post_state: RawState,
+}
+
+impl TraceStep {
+    /// Load a step file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        let value: Value = serde_json::from_slice(&fs::read(path.as_ref())?)?;
+        Ok(TraceStep {
+            pre_state: RawState::from_json(field(&value, "pre_state")?)?,
+            block: serde_json::from_value(field(&value, "block")?.clone())?,
+            post_state: RawState::from_json(field(&value, "post_state")?)?,
+        })
+    }
+}
+
+/// The first key whose value differs between the expected and computed state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDiff {
+    pub key: StateKey,
+    /// The state component the key belongs to.
+    pub component: String,
+    pub expected: Option<Vec<u8>>,
+    pub actual: Option<Vec<u8>>,
+}
+
+impl std::fmt::Display for KeyDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<Vec<u8>>| match value {
+            Some(value) => format!("0x{}", hex::encode(value)),
+            None => "<missing>".to_string(),
+        };
+        write!(
+            f,
+            "key 0x{} ({}): expected {}, got {}",
+            hex::encode(self.key),
+            self.component,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+/// Compare two key/value sets and return the first difference in key order.
+pub fn first_difference(expected: &KeyValues, actual: &KeyValues) -> Option<KeyDiff> {
+    let mut keys: Vec<&StateKey> = expected.keys().chain(actual.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter().find_map(|key| {
+        let (expected, actual) = (expected.get(key), actual.get(key));
+        (expected != actual).then(|| KeyDiff {
+            key: *key,
+            component: describe_key(key),
+            expected: expected.cloned(),
+            actual: actual.cloned(),
+        })
+    })
+}
+
+/// How a replayed step compares with the trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepVerdict {
+    /// Every key and the state root match.
+    Passed,
+    /// The compared keys match, but unmodelled components and the state root were not
+    /// compared.
+    Partial,
+    /// A compared key or the state root differs.
+    Failed,
+}
+
+/// Result of replaying one step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepOutcome {
+    pub file: PathBuf,
+    pub slot: u32,
+    /// Import error, if the importer rejected the block.
+    pub error: Option<String>,
+    pub expected_root: [u8; 32],
+    pub actual_root: [u8; 32],
+    pub first_difference: Option<KeyDiff>,
+    /// Components of the expected post-state left out of the comparison because they
+    /// are not modelled in their GP encoding.
+    pub unmodelled: Vec<String>,
+}
+
+impl StepOutcome {
+    pub fn verdict(&self) -> StepVerdict {
+        if self.first_difference.is_some() {
+            StepVerdict::Failed
+        } else if !self.unmodelled.is_empty() {
+            StepVerdict::Partial
+        } else if self.expected_root == self.actual_root {
+            StepVerdict::Passed
+        } else {
+            StepVerdict::Failed
+        }
+    }
+
+    /// Whether every key and the state root match.
+    pub fn passed(&self) -> bool {
+        self.verdict() == StepVerdict::Passed
+    }
+}
+
+/// Outcome of a trace replay. Replay stops at the first failing step; partially verified
+/// steps are replayed past.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceReport {
+    pub steps: Vec<StepOutcome>,
+}
+
+impl TraceReport {
+    /// Whether every step was fully verified and passed.
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(StepOutcome::passed)
+    }
+
+    /// The failing step, if any.
+    pub fn failure(&self) -> Option<&StepOutcome> {
+        self.steps
+            .iter()
+            .find(|step| step.verdict() == StepVerdict::Failed)
+    }
+
+    /// Steps that did not fail but were only partially verified.
+    pub fn partial(&self) -> impl Iterator<Item = &StepOutcome> {
+        self.steps
+            .iter()
+            .filter(|step| step.verdict() == StepVerdict::Partial)
+    }
+}
+
+/// Step files of a trace directory in replay order.
+pub fn step_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let is_json = path.extension().is_some_and(|ext| ext == "json");
+        let is_genesis = path.file_stem().is_some_and(|stem| stem == "genesis");
+        if is_json && !is_genesis {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Replay every step of the trace in `dir`.
+pub fn replay_trace<P: AsRef<Path>>(dir: P, spec: &ChainSpec) -> Result<TraceReport> {
+    let dir = dir.as_ref();
+    let genesis_path = dir.join("genesis.json");
+    let mut importer = if genesis_path.exists() {
+        let genesis = Genesis::load(&genesis_path, spec)
+            .with_context(|| format!("Failed to load {}", genesis_path.display()))?;
+        Some(Importer::from_genesis(spec.clone(), &genesis))
+    } else {
+        None
+    };
+
+    let mut report = TraceReport::default();
+    for file in step_files(dir)? {
+        let step =
+            TraceStep::load(&file).with_context(|| format!("Failed to load {}", file.display()))?;
+        let outcome = replay_step(&mut importer, &step, spec, &file)?;
+        let failed = outcome.verdict() == StepVerdict::Failed;
+        report.steps.push(outcome);
+        if failed {
+            warn!("Trace step {} failed", file.display());
+            break;
+        }
+    }
+
+    info!(
+        "Replayed {} steps of {}: {}",
+        report.steps.len(),
+        dir.display(),
+        if report.failure().is_some() {
+            "FAILED"
+        } else if report.passed() {
+            "ok"
+        } else {
+            "partially verified"
+        }
+    );
+    Ok(report)
+}
+
+fn replay_step(
+    importer: &mut Option<Importer>,
+    step: &TraceStep,
+    spec: &ChainSpec,
+    file: &Path,
+) -> Result<StepOutcome> {
+    let parent = step.block.header.parent;
+    let anchored = importer.as_ref().is_some_and(|importer| {
+        importer.best_head() == parent
+            && importer.last_state_root() == Some(step.pre_state.state_root)
+    });
+    if !anchored {
+        debug!("Anchoring at pre-state of {}", file.display());
+        let mut state = Genesis::decode_state(&step.pre_state.key_values, spec)
+            .with_context(|| format!("Failed to decode pre-state of {}", file.display()))?;
+        state.last_block_hash = Some(*parent.as_bytes());
+        state.last_state_root = Some(step.pre_state.state_root);
+        *importer = Some(Importer::from_state(spec.clone(), parent, None, state));
+    }
+    let importer = importer.as_mut().expect("anchored above");
+
+    let error = importer
+        .import_decoded(step.block.clone())
+        .err()
+        .map(|e| format!("{e:#}"));
+    let (actual_root, actual) = match &error {
+        // A rejected block leaves the state as it was.
+        Some(_) => (step.pre_state.state_root, step.pre_state.key_values.clone()),
+        None => {
+            let state = importer.state();
+            let root = state.last_state_root.unwrap_or_default();
+            (root, state.to_key_values())
+        }
+    };
+
+    let expected = &step.post_state.key_values;
+    let native = expected.contains_key(&component_key(JAMLIQUOR_EXTENSION));
+    let compared = |kvs: &KeyValues| -> KeyValues {
+        kvs.iter()
+            .filter(|(key, _)| native || is_gp_encoded(key))
+            .map(|(key, value)| (*key, value.clone()))
+            .collect()
+    };
+    let mut unmodelled: Vec<String> = expected
+        .keys()
+        .filter(|key| !native && !is_gp_encoded(key))
+        .map(describe_key)
+        .collect();
+    unmodelled.sort();
+    unmodelled.dedup();
+
+    Ok(StepOutcome {
+        file: file.to_path_buf(),
+        slot: step.block.header.slot,
+        error,
+        expected_root: step.post_state.state_root,
+        actual_root,
+        first_difference: first_difference(&compared(expected), &compared(&actual)),
+        unmodelled,
+    })
+}
+
+fn is_gp_encoded(key: &StateKey) -> bool {
+    GP_ENCODED_COMPONENTS
+        .iter()
+        .any(|&index| *key == component_key(index))
+}
@@ -0,0 +1,248 @@
+//! Runners for the jam-test-vectors categories the importer can check.
+//!
+//! - `codec`: every `<name>.json` with a sibling `<name>.bin` whose type is modelled
+//!   (blocks, headers and extrinsics) must encode to exactly the binary vector and decode
+//!   back to the same value. Only self-generated pairs (written with `jamliquor encode`)
+//!   are supported: the binary codec is JamLiquor-internal (see [`crate::codec`]), so the
+//!   GP-encoded upstream `*.bin` files are expected to fail.
+//! - `traces`: every trace directory is replayed with [`crate::trace::replay_trace`].
+//! - `erasure` (or `erasure_coding`): every `data` must encode to exactly the vector's
+//!   `shards` for the chain spec, and be reconstructed from the recovery shards alone.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::chain_spec::ChainSpec;
+use crate::codec::{decode_exact, Decode, Encode};
+use crate::erasure::ErasureCoder;
+use crate::genesis::{field, parse_hex};
+use crate::schema::{Block, Extrinsic, Header};
+use crate::trace;
+
+/// Result of checking one vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorOutcome {
+    pub path: PathBuf,
+    /// Why the vector failed, if it did.
+    pub failure: Option<String>,
+    /// What was left unchecked, if the vector did not fail but was only partially verified.
+    pub partial: Option<String>,
+}
+
+impl VectorOutcome {
+    /// Whether the vector was fully verified and passed.
+    pub fn passed(&self) -> bool {
+        self.failure.is_none() && self.partial.is_none()
+    }
+}
+
+/// Run every vector of `category` (`codec`, `traces`, `erasure`, or a subdirectory)
+/// below `vectors_dir`.
+pub fn run_category<P: AsRef<Path>>(
+    vectors_dir: P,
+    category: &str,
+    spec: &ChainSpec,
+) -> Result<Vec<VectorOutcome>> {
+    let dir = vectors_dir.as_ref().join(category);
+    let outcomes = match category.split('/').next() {
+        Some("codec") => {
+            // Codec vectors are split by chain spec; otherwise the directory is used as is.
+            let by_spec = dir.join(spec.name);
+            run_codec_vectors(if by_spec.is_dir() { by_spec } else { dir })?
+        }
+        Some("traces") => run_trace_vectors(&dir, spec)?,
+        Some("erasure" | "erasure_coding") => {
+            let by_spec = dir.join(spec.name);
+            run_erasure_vectors(if by_spec.is_dir() { by_spec } else { dir }, spec)?
+        }
+        _ => anyhow::bail!(
+            "unsupported vector category `{category}` (expected codec, traces or erasure)"
+        ),
+    };
+    info!(
+        "{category}: {} of {} vectors passed",
+        outcomes.iter().filter(|outcome| outcome.passed()).count(),
+        outcomes.len()
+    );
+    Ok(outcomes)
+}
+
+/// Check every modelled JSON/binary vector pair in `dir`.
+///
+/// The binary side is compared with the internal codec, so this is a round-trip check
+/// for self-generated pairs, not a conformance check against upstream codec vectors.
+pub fn run_codec_vectors<P: AsRef<Path>>(dir: P) -> Result<Vec<VectorOutcome>> {
+    let dir = dir.as_ref();
+    let mut outcomes = Vec::new();
+    for json in sorted_files(dir, "json")? {
+        let bin = json.with_extension("bin");
+        if !bin.exists() {
+            continue;
+        }
+        let stem = json
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let check = if stem.starts_with("block") {
+            check_pair::<Block>
+        } else if stem.starts_with("header") {
+            check_pair::<Header>
+        } else if stem.starts_with("extrinsic") {
+            check_pair::<Extrinsic>
+        } else {
+            debug!("Skipping unmodelled codec vector {}", json.display());
+            continue;
+        };
+        outcomes.push(VectorOutcome {
+            failure: check(&json, &bin).err().map(|e| format!("{e:#}")),
+            partial: None,
+            path: json,
+        });
+    }
+    Ok(outcomes)
+}
+
+fn check_pair<T>(json: &Path, bin: &Path) -> Result<()>
+where
+    T: DeserializeOwned + Encode + Decode + PartialEq,
+{
+    let value: T = serde_json::from_slice(&fs::read(json)?)
+        .with_context(|| format!("Failed to parse {}", json.display()))?;
+    let expected = fs::read(bin)?;
+    let encoded = value.encode();
+    if encoded != expected {
+        let at = encoded
+            .iter()
+            .zip(&expected)
+            .position(|(a, b)| a != b)
+            .unwrap_or(encoded.len().min(expected.len()));
+        anyhow::bail!(
+            "encoding differs from {} at byte {at} ({} bytes encoded, {} expected)",
+            bin.display(),
+            encoded.len(),
+            expected.len()
+        );
+    }
+    let decoded: T =
+        decode_exact(&expected).with_context(|| format!("Failed to decode {}", bin.display()))?;
+    if decoded != value {
+        anyhow::bail!("{} decodes to a different value", bin.display());
+    }
+    Ok(())
+}
+
+/// Check every erasure coding vector in `dir` against the coder for `spec`.
+pub fn run_erasure_vectors<P: AsRef<Path>>(dir: P, spec: &ChainSpec) -> Result<Vec<VectorOutcome>> {
+    let coder = ErasureCoder::for_spec(spec)?;
+    let mut outcomes = Vec::new();
+    for path in sorted_files(dir.as_ref(), "json")? {
+        outcomes.push(VectorOutcome {
+            failure: check_erasure(&coder, &path).err().map(|e| format!("{e:#}")),
+            partial: None,
+            path,
+        });
+    }
+    Ok(outcomes)
+}
+
+fn check_erasure(coder: &ErasureCoder, path: &Path) -> Result<()> {
+    let value: Value = serde_json::from_slice(&fs::read(path)?)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let data = parse_hex(field(&value, "data")?, "data")?;
+    let shards = value
+        .get("shards")
+        .or_else(|| value.get("chunks"))
+        .and_then(Value::as_array)
+        .context("vector has no `shards` array")?
+        .iter()
+        .map(|shard| parse_hex(shard, "shards"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let encoded = coder.encode(&data);
+    if let Some(i) = (0..encoded.len().max(shards.len())).find(|&i| encoded.get(i) != shards.get(i))
+    {
+        anyhow::bail!(
+            "shard {i} differs ({} shards encoded, {} expected)",
+            encoded.len(),
+            shards.len()
+        );
+    }
+    let recovery: Vec<_> = shards
+        .into_iter()
+        .enumerate()
+        .skip(coder.total() - coder.original())
+        .collect();
+    if coder.reconstruct(&recovery, data.len())? != data {
+        anyhow::bail!(
+            "data reconstructed from the last {} shards differs",
+            coder.original()
+        );
+    }
+    Ok(())
+}
+
+/// Replay every trace directory below `dir`, or `dir` itself if it holds step files.
+pub fn run_trace_vectors(dir: &Path, spec: &ChainSpec) -> Result<Vec<VectorOutcome>> {
+    let mut traces: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            traces.push(path);
+        }
+    }
+    traces.sort();
+    if traces.is_empty() {
+        traces.push(dir.to_path_buf());
+    }
+
+    let mut outcomes = Vec::new();
+    for path in traces {
+        let report = trace::replay_trace(&path, spec)?;
+        let failure = report.failure().map(|step| {
+            let mut reason = format!("diverged at {}", step.file.display());
+            if let Some(error) = &step.error {
+                reason.push_str(&format!(": {error}"));
+            }
+            if let Some(diff) = &step.first_difference {
+                reason.push_str(&format!(": {diff}"));
+            }
+            reason
+        });
+        let partial = report.partial().next().map(|_| {
+            let mut unmodelled: Vec<&str> = report
+                .partial()
+                .flat_map(|step| step.unmodelled.iter().map(String::as_str))
+                .collect();
+            unmodelled.sort();
+            unmodelled.dedup();
+            format!(
+                "{} steps not compared on {} or the state root",
+                report.partial().count(),
+                unmodelled.join(", ")
+            )
+        });
+        outcomes.push(VectorOutcome {
+            path,
+            failure,
+            partial,
+        });
+    }
+    Ok(outcomes)
+}
+
+fn sorted_files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,426 @@
+//! Signature and proof pre-verification.
+//!
+//! Before a block is applied, every signature and VRF proof in its extrinsic is collected
+//! into a list of [`SignatureCheck`]s and verified in one go by a [`SignatureVerifier`]:
+//! spread over a configurable number of worker threads, or inline for single-threaded
+//! edge builds. Results are cached by the hash of the check, so importing the same
+//! extrinsic items again on a competing fork does not verify them twice.
+
+use std::collections::{HashMap, VecDeque};
+use std::thread;
+
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error_code::{ErrorCode, Subsystem};
+use crate::finality::{supermajority, verify_ed25519};
+use crate::schema::{blake2b_256, BlockchainError, Extrinsic, OpaqueHash};
+
+/// Context string for guarantor signatures (GP 11.26).
+pub const GUARANTEE_CONTEXT: &[u8] = b"jam_guarantee";
+/// Context string for availability assurances (GP 11.13).
+pub const AVAILABLE_CONTEXT: &[u8] = b"jam_available";
+/// Context strings for positive and negative judgements (GP 10.4).
+pub const VALID_CONTEXT: &[u8] = b"jam_valid";
+pub const INVALID_CONTEXT: &[u8] = b"jam_invalid";
+/// Context string for ticket VRF proofs (GP 6.29).
+pub const TICKET_SEAL_CONTEXT: &[u8] = b"jam_ticket_seal";
+/// Number of guarantor credentials a guarantee may carry (GP 11.23).
+pub const GUARANTEE_CREDENTIALS: std::ops::RangeInclusive<usize> = 2..=3;
+
+/// Signature scheme of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CheckKind {
+    Ed25519,
+    /// Bandersnatch Ring VRF proof against the epoch's ticket ring.
+    RingVrf,
+}
+
+/// One signature or proof to verify.
+///
+/// Memory Usage:
+/// - Fixed: ~80 bytes plus the message and signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureCheck {
+    pub kind: CheckKind,
+    /// Subsystem and index of the extrinsic item the signature belongs to.
+    pub subsystem: Subsystem,
+    pub index: usize,
+    /// Signer's key, or `None` if the item names a validator outside the active set.
+    /// Ring VRF checks carry the ring commitment.
+    pub key: Option<OpaqueHash>,
+    pub message: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignatureCheck {
+    /// Cache key: the hash of the scheme, key, message and signature.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(34 + self.message.len() + self.signature.len());
+        data.push(self.kind as u8);
+        match &self.key {
+            Some(key) => data.extend_from_slice(key.as_bytes()),
+            None => data.push(0xff),
+        }
+        data.extend_from_slice(&blake2b_256(&self.message));
+        data.extend_from_slice(&self.signature);
+        blake2b_256(&data)
+    }
+
+    /// Verify the signature, returning why it does not verify.
+    pub fn verify(&self) -> Result<(), String> {
+        let Some(key) = &self.key else {
+            return Err("validator index outside the active set".to_string());
+        };
+        match self.kind {
+            CheckKind::Ed25519 => verify_ed25519(key, &self.message, &self.signature),
+            // Bandersnatch ring proofs are not implemented yet; until they are, only the
+            // proof length is checked, as in `TicketEnvelope::validate`.
+            CheckKind::RingVrf if self.signature.len() < 64 => Err(format!(
+                "ring proof too short: {} bytes",
+                self.signature.len()
+            )),
+            CheckKind::RingVrf => Ok(()),
+        }
+    }
+}
+
+/// Collect every signature and proof check in `extrinsic`.
+///
+/// Ed25519 signers are looked up by validator index in `validators`, the set active for
+/// the block (see [`crate::stf::signing_validators`]). Items that do not parse, guarantees
+/// without 2 or 3 credentials and verdicts without a supermajority of votes are rejected
+/// here, so no item reaches the STF unsigned.
+pub fn collect_checks(
+    extrinsic: &Extrinsic,
+    validators: &[OpaqueHash],
+) -> Result<Vec<SignatureCheck>, BlockchainError> {
+    let key = |index: u16| validators.get(usize::from(index)).copied();
+    let mut checks = Vec::new();
+
+    for (i, ticket) in extrinsic.tickets.iter().enumerate() {
+        let mut message = TICKET_SEAL_CONTEXT.to_vec();
+        message.push(ticket.attempt);
+        checks.push(SignatureCheck {
+            kind: CheckKind::RingVrf,
+            subsystem: Subsystem::Safrole,
+            index: i,
+            key: Some(OpaqueHash::default()),
+            message,
+            signature: ticket.signature.clone(),
+        });
+    }
+
+    for (i, value) in extrinsic.guarantees.iter().enumerate() {
+        let guarantee: SignedGuarantee = parse(value, Subsystem::Guarantees, i)?;
+        if !GUARANTEE_CREDENTIALS.contains(&guarantee.signatures.len()) {
+            return Err(BlockchainError::rejected(
+                ErrorCode::InsufficientGuarantees,
+                Some(i),
+                format!(
+                    "Guarantee {} has {} credentials, expected 2 or 3",
+                    i,
+                    guarantee.signatures.len()
+                ),
+            ));
+        }
+        // Work reports are carried as JSON, so their canonical JSON form stands in for
+        // the GP encoding of the report.
+        let report = serde_json::to_vec(&guarantee.report).unwrap_or_default();
+        let mut message = GUARANTEE_CONTEXT.to_vec();
+        message.extend_from_slice(&blake2b_256(&report));
+        for credential in guarantee.signatures {
+            checks.push(SignatureCheck {
+                kind: CheckKind::Ed25519,
+                subsystem: Subsystem::Guarantees,
+                index: i,
+                key: key(credential.validator_index),
+                message: message.clone(),
+                signature: decode_hex(&credential.signature, Subsystem::Guarantees, i)?,
+            });
+        }
+    }
+
+    for (i, value) in extrinsic.assurances.iter().enumerate() {
+        let assurance: SignedAssurance = parse(value, Subsystem::Assurances, i)?;
+        let mut payload = decode_hex(&assurance.anchor, Subsystem::Assurances, i)?;
+        payload.extend_from_slice(&decode_hex(&assurance.bitfield, Subsystem::Assurances, i)?);
+        let mut message = AVAILABLE_CONTEXT.to_vec();
+        message.extend_from_slice(&blake2b_256(&payload));
+        checks.push(SignatureCheck {
+            kind: CheckKind::Ed25519,
+            subsystem: Subsystem::Assurances,
+            index: i,
+            key: key(assurance.validator_index),
+            message,
+            signature: decode_hex(&assurance.signature, Subsystem::Assurances, i)?,
+        });
+    }
+
+    if !extrinsic.disputes.is_null() {
+        let disputes: SignedDisputes = parse(&extrinsic.disputes, Subsystem::Disputes, 0)?;
+        let required = supermajority(validators.len());
+        for (i, verdict) in disputes.verdicts.iter().enumerate() {
+            if verdict.votes.len() != required {
+                return Err(BlockchainError::rejected(
+                    ErrorCode::BadVoteSplit,
+                    Some(i),
+                    format!(
+                        "Verdict {} has {} votes, {} required",
+                        i,
+                        verdict.votes.len(),
+                        required
+                    ),
+                ));
+            }
+            let target = decode_hex(&verdict.target, Subsystem::Disputes, i)?;
+            for vote in &verdict.votes {
+                let context = if vote.vote {
+                    VALID_CONTEXT
+                } else {
+                    INVALID_CONTEXT
+                };
+                let mut message = context.to_vec();
+                message.extend_from_slice(&target);
+                checks.push(SignatureCheck {
+                    kind: CheckKind::Ed25519,
+                    subsystem: Subsystem::Disputes,
+                    index: i,
+                    key: key(vote.index),
+                    message,
+                    signature: decode_hex(&vote.signature, Subsystem::Disputes, i)?,
+                });
+            }
+        }
+    }
+
+    Ok(checks)
+}
+
+/// A newly verified check: its hash and whether it verified.
+type Verified = ([u8; 32], bool);
+
+/// How a [`SignatureVerifier`] runs its checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationConfig {
+    /// Worker threads; `1` verifies inline on the importing thread.
+    pub threads: usize,
+    /// Maximum number of results kept in the cache.
+    pub cache_capacity: usize,
+}
+
+impl VerificationConfig {
+    /// Verify inline without spawning threads, for constrained edge devices.
+    pub fn single_threaded() -> Self {
+        VerificationConfig {
+            threads: 1,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for VerificationConfig {
+    /// One worker per available CPU.
+    fn default() -> Self {
+        VerificationConfig {
+            threads: thread::available_parallelism().map_or(1, usize::from),
+            cache_capacity: 16_384,
+        }
+    }
+}
+
+/// Verifies batches of checks and remembers the outcomes.
+///
+/// Memory Usage:
+/// - Cache: ~33 bytes per entry (twice that with the eviction queue), up to
+///   `cache_capacity` entries
+#[derive(Debug, Default)]
+pub struct SignatureVerifier {
+    config: VerificationConfig,
+    cache: HashMap<[u8; 32], bool>,
+    /// Cached hashes in insertion order, oldest first.
+    order: VecDeque<[u8; 32]>,
+}
+
+impl SignatureVerifier {
+    pub fn new(config: VerificationConfig) -> Self {
+        SignatureVerifier {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &VerificationConfig {
+        &self.config
+    }
+
+    /// Number of cached results.
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Verify every check, rejecting with the first failing one in extrinsic order, and
+    /// cache the new results.
+    pub fn verify(&mut self, checks: &[SignatureCheck]) -> Result<(), BlockchainError> {
+        let (verified, result) = self.evaluate(checks);
+        for (hash, valid) in verified {
+            self.remember(hash, valid);
+        }
+        result
+    }
+
+    /// [`SignatureVerifier::verify`] without adding to the cache.
+    pub fn check(&self, checks: &[SignatureCheck]) -> Result<(), BlockchainError> {
+        self.evaluate(checks).1
+    }
+
+    /// Verify the checks not in the cache, returning their results and the verdict.
+    fn evaluate(&self, checks: &[SignatureCheck]) -> (Vec<Verified>, Result<(), BlockchainError>) {
+        let hashes: Vec<_> = checks.iter().map(SignatureCheck::hash).collect();
+        let pending: Vec<usize> = (0..checks.len())
+            .filter(|&i| !self.cache.contains_key(&hashes[i]))
+            .collect();
+        debug!(
+            "Verifying {} signatures ({} cached) on {} threads",
+            pending.len(),
+            checks.len() - pending.len(),
+            self.config.threads
+        );
+
+        let mut outcomes: HashMap<usize, Result<(), String>> =
+            self.run(checks, &pending).into_iter().collect();
+        let verified = outcomes
+            .iter()
+            .map(|(&i, outcome)| (hashes[i], outcome.is_ok()))
+            .collect();
+
+        let result = checks.iter().enumerate().try_for_each(|(i, check)| {
+            let outcome = outcomes
+                .remove(&i)
+                .unwrap_or_else(|| match self.cache[&hashes[i]] {
+                    true => Ok(()),
+                    false => Err("signature does not verify".to_string()),
+                });
+            outcome.map_err(|reason| BlockchainError::Rejected {
+                subsystem: check.subsystem,
+                code: ErrorCode::BadSignature,
+                index: Some(check.index),
+                reason: format!("{:?} signature invalid: {reason}", check.kind),
+            })
+        });
+        (verified, result)
+    }
+
+    /// Verify the checks at `pending`, spread over the configured workers.
+    fn run(
+        &self,
+        checks: &[SignatureCheck],
+        pending: &[usize],
+    ) -> Vec<(usize, Result<(), String>)> {
+        let verify = |batch: &[usize]| -> Vec<_> {
+            batch.iter().map(|&i| (i, checks[i].verify())).collect()
+        };
+        let threads = self.config.threads.max(1);
+        if threads == 1 || pending.len() < 2 {
+            return verify(pending);
+        }
+
+        let batch_size = pending.len().div_ceil(threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> = pending
+                .chunks(batch_size)
+                .map(|batch| scope.spawn(move || verify(batch)))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("verification worker panicked"))
+                .collect()
+        })
+    }
+
+    fn remember(&mut self, hash: [u8; 32], valid: bool) {
+        if self.config.cache_capacity == 0 {
+            return;
+        }
+        if self.cache.insert(hash, valid).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.order.len() > self.config.cache_capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn malformed(subsystem: Subsystem, index: usize, reason: String) -> BlockchainError {
+    BlockchainError::Rejected {
+        subsystem,
+        code: ErrorCode::Malformed,
+        index: Some(index),
+        reason,
+    }
+}
+
+fn parse<T: DeserializeOwned>(
+    value: &Value,
+    subsystem: Subsystem,
+    index: usize,
+) -> Result<T, BlockchainError> {
+    T::deserialize(value)
+        .map_err(|e| malformed(subsystem, index, format!("Invalid {subsystem} item: {e}")))
+}
+
+fn decode_hex(value: &str, subsystem: Subsystem, index: usize) -> Result<Vec<u8>, BlockchainError> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| {
+        malformed(
+            subsystem,
+            index,
+            format!("Invalid hex in {subsystem} item: {e}"),
+        )
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedGuarantee {
+    report: Value,
+    #[serde(default)]
+    signatures: Vec<Credential>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Credential {
+    validator_index: u16,
+    signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedAssurance {
+    #[serde(default)]
+    anchor: String,
+    bitfield: String,
+    validator_index: u16,
+    signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedDisputes {
+    #[serde(default)]
+    verdicts: Vec<SignedVerdict>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedVerdict {
+    target: String,
+    #[serde(default)]
+    votes: Vec<SignedVote>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedVote {
+    vote: bool,
+    index: u16,
+    signature: String,
+}
@@ -0,0 +1,459 @@
+//! Work packages, their bundles and the work reports refinement produces (GP 14).
+//!
+//! Package types use the crate's binary codec; a [`WorkPackageBundle`] is the package
+//! together with everything refinement needs (extrinsic blobs, imported segments and
+//! their justifications) and is what gets erasure-coded for auditors. A [`WorkReport`]
+//! converts to the JSON guarantee form the guarantees extrinsic carries, so reports
+//! produced locally go through the same reports STF as reports from the network.
+
+use serde_json::{json, Value};
+
+use crate::codec::{decode_seq, encode_seq, Decode, Encode};
+use crate::schema::{blake2b_256, BlockchainError, OpaqueHash};
+
+/// Maximum work items in a package `I`.
+pub const MAX_WORK_ITEMS: usize = 16;
+/// Maximum segments a package may import or export `W_M`/`W_X`.
+pub const MAX_PACKAGE_SEGMENTS: usize = 3072;
+
+/// The authorizer a package runs Is-Authorized with: its code and parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Authorizer {
+    pub code_hash: OpaqueHash,
+    pub params: Vec<u8>,
+}
+
+impl Authorizer {
+    /// The authorizer hash `a` that core authorization pools hold.
+    pub fn hash(&self) -> OpaqueHash {
+        OpaqueHash::new(blake2b_256(
+            &[self.code_hash.as_bytes(), self.params.as_slice()].concat(),
+        ))
+    }
+}
+
+/// Chain state a package was refined against (GP 11.4).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefineContext {
+    pub anchor: OpaqueHash,
+    pub state_root: OpaqueHash,
+    pub beefy_root: OpaqueHash,
+    pub lookup_anchor: OpaqueHash,
+    pub lookup_anchor_slot: u32,
+    pub prerequisites: Vec<OpaqueHash>,
+}
+
+/// A segment to import: index `index` of the exports under `tree_root`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSpec {
+    pub tree_root: OpaqueHash,
+    pub index: u16,
+}
+
+/// Segments root of the exports of a package that imports name by its hash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SegmentRootLookup {
+    pub work_package_hash: OpaqueHash,
+    pub segment_tree_root: OpaqueHash,
+}
+
+/// An extrinsic blob a work item refers to by hash and length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtrinsicSpec {
+    pub hash: OpaqueHash,
+    pub len: u32,
+}
+
+impl ExtrinsicSpec {
+    /// The spec of `blob`.
+    pub fn of(blob: &[u8]) -> Self {
+        ExtrinsicSpec {
+            hash: OpaqueHash::new(blake2b_256(blob)),
+            len: blob.len() as u32,
+        }
+    }
+}
+
+/// One unit of work for a service.
+///
+/// Memory Usage:
+/// - Fixed: ~100 bytes plus the payload
+/// - Per import: 34 bytes; per extrinsic: 36 bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkItem {
+    pub service: u32,
+    /// Code the service refines with, as of the package's lookup anchor.
+    pub code_hash: OpaqueHash,
+    pub payload: Vec<u8>,
+    pub refine_gas_limit: u64,
+    pub accumulate_gas_limit: u64,
+    pub import_segments: Vec<ImportSpec>,
+    pub extrinsic: Vec<ExtrinsicSpec>,
+    /// Number of segments the item exports.
+    pub export_count: u16,
+}
+
+/// Work items submitted together for refinement on one core.
+///
+/// Memory Usage:
+/// - Fixed: ~250 bytes plus the authorization token and parameters
+/// - Per item: see [`WorkItem`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkPackage {
+    /// Token passed to the authorizer.
+    pub authorization: Vec<u8>,
+    /// Service hosting the authorizer code.
+    pub auth_code_host: u32,
+    pub authorizer: Authorizer,
+    pub context: RefineContext,
+    pub items: Vec<WorkItem>,
+}
+
+impl WorkPackage {
+    pub fn hash(&self) -> OpaqueHash {
+        OpaqueHash::new(blake2b_256(&self.encode()))
+    }
+}
+
+/// A package with the data refinement reads besides the chain state.
+///
+/// Memory Usage:
+/// - The package, plus every extrinsic blob and 4104 bytes per imported segment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkPackageBundle {
+    pub package: WorkPackage,
+    /// Extrinsic blobs of all items, in item order.
+    pub extrinsics: Vec<Vec<u8>>,
+    /// Imported segments of all items, in item order.
+    pub imports: Vec<Vec<u8>>,
+    /// Merkle justification of each imported segment under its segments root.
+    pub justifications: Vec<Vec<OpaqueHash>>,
+    /// Segments roots of the packages imports name by hash.
+    pub segment_root_lookup: Vec<SegmentRootLookup>,
+}
+
+/// Why refinement of an item produced no output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkError {
+    OutOfGas,
+    Panic,
+    /// The item exported a different number of segments than it declared.
+    BadExports,
+    /// The output exceeds the report size limit.
+    Oversize,
+    /// The code is unavailable at the lookup anchor.
+    BadCode,
+    CodeOversize,
+}
+
+impl WorkError {
+    /// Name used in JSON reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkError::OutOfGas => "out_of_gas",
+            WorkError::Panic => "panic",
+            WorkError::BadExports => "bad_exports",
+            WorkError::Oversize => "oversize",
+            WorkError::BadCode => "bad_code",
+            WorkError::CodeOversize => "code_oversize",
+        }
+    }
+}
+
+/// Availability specification of a refined package.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PackageSpec {
+    pub hash: OpaqueHash,
+    /// Length of the encoded bundle.
+    pub length: u32,
+    pub erasure_root: OpaqueHash,
+    pub exports_root: OpaqueHash,
+    pub exports_count: u16,
+}
+
+/// Outcome of refining one work item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkResult {
+    pub service_id: u32,
+    pub code_hash: OpaqueHash,
+    pub payload_hash: OpaqueHash,
+    pub accumulate_gas: u64,
+    pub result: Result<Vec<u8>, WorkError>,
+    pub refine_gas_used: u64,
+    pub imports: u16,
+    pub extrinsic_count: u16,
+    pub extrinsic_size: u32,
+    pub exports: u16,
+}
+
+/// A guarantor's report of a refined package.
+///
+/// Memory Usage:
+/// - Fixed: ~400 bytes plus the authorizer output
+/// - Per result: ~150 bytes plus its output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkReport {
+    pub package_spec: PackageSpec,
+    pub context: RefineContext,
+    pub core_index: u16,
+    pub authorizer_hash: OpaqueHash,
+    pub auth_output: Vec<u8>,
+    pub results: Vec<WorkResult>,
+    pub auth_gas_used: u64,
+    pub segment_root_lookup: Vec<SegmentRootLookup>,
+}
+
+impl WorkReport {
+    /// JSON form, as in the guarantees extrinsic.
+    pub fn to_json(&self) -> Value {
+        let spec = &self.package_spec;
+        let context = &self.context;
+        let results: Vec<Value> = self
+            .results
+            .iter()
+            .map(|result| {
+                json!({
+                    "service_id": result.service_id,
+                    "code_hash": result.code_hash,
+                    "payload_hash": result.payload_hash,
+                    "accumulate_gas": result.accumulate_gas,
+                    "result": match &result.result {
+                        Ok(output) => json!({ "ok": hex_of(output) }),
+                        Err(error) => json!({ error.name(): null }),
+                    },
+                    "refine_load": {
+                        "gas_used": result.refine_gas_used,
+                        "imports": result.imports,
+                        "extrinsic_count": result.extrinsic_count,
+                        "extrinsic_size": result.extrinsic_size,
+                        "exports": result.exports,
+                    },
+                })
+            })
+            .collect();
+        json!({
+            "package_spec": {
+                "hash": spec.hash,
+                "length": spec.length,
+                "erasure_root": spec.erasure_root,
+                "exports_root": spec.exports_root,
+                "exports_count": spec.exports_count,
+            },
+            "context": {
+                "anchor": context.anchor,
+                "state_root": context.state_root,
+                "beefy_root": context.beefy_root,
+                "lookup_anchor": context.lookup_anchor,
+                "lookup_anchor_slot": context.lookup_anchor_slot,
+                "prerequisites": context.prerequisites,
+            },
+            "core_index": self.core_index,
+            "authorizer_hash": self.authorizer_hash,
+            "auth_output": hex_of(&self.auth_output),
+            "segment_root_lookup": self
+                .segment_root_lookup
+                .iter()
+                .map(|lookup| {
+                    json!({
+                        "work_package_hash": lookup.work_package_hash,
+                        "segment_tree_root": lookup.segment_tree_root,
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "results": results,
+            "auth_gas_used": self.auth_gas_used,
+        })
+    }
+
+    /// Hash of the report, as signed by guarantors.
+    ///
+    /// Reports are carried as JSON, so their canonical JSON form stands in for the GP
+    /// encoding, as in [`crate::verification::collect_checks`].
+    pub fn hash(&self) -> OpaqueHash {
+        OpaqueHash::new(blake2b_256(
+            &serde_json::to_vec(&self.to_json()).unwrap_or_default(),
+        ))
+    }
+
+    /// An unsigned guarantee of the report at `slot`, for the guarantees extrinsic.
+    pub fn guarantee(&self, slot: u32) -> Value {
+        json!({
+            "report": self.to_json(),
+            "slot": slot,
+            "signatures": [],
+        })
+    }
+}
+
+fn hex_of(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+impl Encode for Authorizer {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.code_hash.encode_to(out);
+        self.params.encode_to(out);
+    }
+}
+
+impl Decode for Authorizer {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(Authorizer {
+            code_hash: OpaqueHash::decode(input)?,
+            params: Vec::decode(input)?,
+        })
+    }
+}
+
+impl Encode for RefineContext {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.anchor.encode_to(out);
+        self.state_root.encode_to(out);
+        self.beefy_root.encode_to(out);
+        self.lookup_anchor.encode_to(out);
+        self.lookup_anchor_slot.encode_to(out);
+        encode_seq(&self.prerequisites, out);
+    }
+}
+
+impl Decode for RefineContext {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(RefineContext {
+            anchor: OpaqueHash::decode(input)?,
+            state_root: OpaqueHash::decode(input)?,
+            beefy_root: OpaqueHash::decode(input)?,
+            lookup_anchor: OpaqueHash::decode(input)?,
+            lookup_anchor_slot: u32::decode(input)?,
+            prerequisites: decode_seq(input)?,
+        })
+    }
+}
+
+impl Encode for ImportSpec {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.tree_root.encode_to(out);
+        self.index.encode_to(out);
+    }
+}
+
+impl Decode for ImportSpec {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(ImportSpec {
+            tree_root: OpaqueHash::decode(input)?,
+            index: u16::decode(input)?,
+        })
+    }
+}
+
+impl Encode for SegmentRootLookup {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.work_package_hash.encode_to(out);
+        self.segment_tree_root.encode_to(out);
+    }
+}
+
+impl Decode for SegmentRootLookup {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(SegmentRootLookup {
+            work_package_hash: OpaqueHash::decode(input)?,
+            segment_tree_root: OpaqueHash::decode(input)?,
+        })
+    }
+}
+
+impl Encode for ExtrinsicSpec {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.hash.encode_to(out);
+        self.len.encode_to(out);
+    }
+}
+
+impl Decode for ExtrinsicSpec {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(ExtrinsicSpec {
+            hash: OpaqueHash::decode(input)?,
+            len: u32::decode(input)?,
+        })
+    }
+}
+
+impl Encode for WorkItem {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.service.encode_to(out);
+        self.code_hash.encode_to(out);
+        self.payload.encode_to(out);
+        self.refine_gas_limit.encode_to(out);
+        self.accumulate_gas_limit.encode_to(out);
+        encode_seq(&self.import_segments, out);
+        encode_seq(&self.extrinsic, out);
+        self.export_count.encode_to(out);
+    }
+}
+
+impl Decode for WorkItem {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(WorkItem {
+            service: u32::decode(input)?,
+            code_hash: OpaqueHash::decode(input)?,
+            payload: Vec::decode(input)?,
+            refine_gas_limit: u64::decode(input)?,
+            accumulate_gas_limit: u64::decode(input)?,
+            import_segments: decode_seq(input)?,
+            extrinsic: decode_seq(input)?,
+            export_count: u16::decode(input)?,
+        })
+    }
+}
+
+impl Encode for WorkPackage {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.authorization.encode_to(out);
+        self.auth_code_host.encode_to(out);
+        self.authorizer.encode_to(out);
+        self.context.encode_to(out);
+        encode_seq(&self.items, out);
+    }
+}
+
+impl Decode for WorkPackage {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        Ok(WorkPackage {
+            authorization: Vec::decode(input)?,
+            auth_code_host: u32::decode(input)?,
+            authorizer: Authorizer::decode(input)?,
+            context: RefineContext::decode(input)?,
+            items: decode_seq(input)?,
+        })
+    }
+}
+
+impl Encode for WorkPackageBundle {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.package.encode_to(out);
+        encode_seq(&self.extrinsics, out);
+        encode_seq(&self.imports, out);
+        for justification in &self.justifications {
+            encode_seq(justification, out);
+        }
+        encode_seq(&self.segment_root_lookup, out);
+    }
+}
+
+impl Decode for WorkPackageBundle {
+    fn decode(input: &mut &[u8]) -> Result<Self, BlockchainError> {
+        let package = WorkPackage::decode(input)?;
+        let extrinsics = decode_seq(input)?;
+        let imports: Vec<Vec<u8>> = decode_seq(input)?;
+        // One justification per imported segment.
+        let justifications = (0..imports.len())
+            .map(|_| decode_seq(input))
+            .collect::<Result<_, _>>()?;
+        Ok(WorkPackageBundle {
+            package,
+            extrinsics,
+            imports,
+            justifications,
+            segment_root_lookup: decode_seq(input)?,
+        })
+    }
+}
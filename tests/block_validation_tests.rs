@@ -1,7 +1,11 @@
 use anyhow::Result;
+use ed25519_dalek::SigningKey;
+use jamliquor::authorer::{Authorer, SealKind};
+use jamliquor::chain_spec::ChainSpec;
 use jamliquor::importer::Importer;
 use jamliquor::schema::{
-    Block, BlockchainError, Extrinsic, Header, OpaqueHash, Preimage, TicketBody, TicketEnvelope,
+    Block, BlockchainError, Extrinsic, Header, OpaqueHash, Preimage, State, TicketBody,
+    TicketEnvelope,
 };
 use std::fs::File;
 use tempfile::tempdir;
@@ -48,6 +52,25 @@ fn create_test_block() -> Block {
     block
 }
 
+/// The only validator of [`importer`], so the fallback key of every slot.
+fn author() -> Authorer {
+    Authorer::new(SigningKey::from_bytes(&[1; 32]), ChainSpec::default())
+}
+
+fn importer() -> Importer {
+    let mut state = State::new();
+    state.active_validators = vec![*author().public_key()];
+    Importer::from_state(ChainSpec::default(), OpaqueHash::default(), None, state)
+}
+
+fn seal(block: &mut Block) {
+    block.header = author().seal(
+        block.header.clone(),
+        &OpaqueHash::default(),
+        &SealKind::Fallback,
+    );
+}
+
 fn write_block_to_temp_file(block: &Block) -> Result<std::path::PathBuf> {
     let dir = tempdir()?;
     let file_path = dir.path().join("block.json");
@@ -60,8 +83,9 @@ fn write_block_to_temp_file(block: &Block) -> Result<std::path::PathBuf> {
 
 #[test]
 fn test_empty_block_validation() -> Result<()> {
-    let mut importer = Importer::new();
-    let block = create_test_block();
+    let mut importer = importer();
+    let mut block = create_test_block();
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
 
     // Should pass with default valid block
@@ -72,12 +96,13 @@ fn test_empty_block_validation() -> Result<()> {
 
 #[test]
 fn test_invalid_slot() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let mut block = create_test_block();
 
     // Set slot to 0 (invalid)
     block.header.slot = 0;
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -90,7 +115,7 @@ fn test_invalid_slot() -> Result<()> {
 
 #[test]
 fn test_ticket_validation() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let mut block = create_test_block();
 
     // Add a ticket with invalid signature (empty)
@@ -105,6 +130,7 @@ fn test_ticket_validation() -> Result<()> {
         attempt: 1,
     }]);
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -117,7 +143,7 @@ fn test_ticket_validation() -> Result<()> {
 
 #[test]
 fn test_preimage_validation() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let mut block = create_test_block();
 
     // Add invalid preimage with empty blob
@@ -126,6 +152,7 @@ fn test_preimage_validation() -> Result<()> {
         blob: Vec::new(),
     });
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -137,6 +164,7 @@ fn test_preimage_validation() -> Result<()> {
     block.extrinsic.preimages[0].requester = 0;
     block.extrinsic.preimages[0].blob = vec![1, 2, 3];
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -149,7 +177,7 @@ fn test_preimage_validation() -> Result<()> {
 
 #[test]
 fn test_ticket_count_mismatch() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let mut block = create_test_block();
 
     // Add a ticket but no corresponding mark
@@ -158,6 +186,7 @@ fn test_ticket_count_mismatch() -> Result<()> {
         signature: vec![1; 64], // Valid signature length
     });
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -175,6 +204,7 @@ fn test_ticket_count_mismatch() -> Result<()> {
         signature: vec![1; 64],
     });
 
+    seal(&mut block);
     let block_path = write_block_to_temp_file(&block)?;
     let result = importer.import_block(block_path);
 
@@ -187,10 +217,11 @@ fn test_ticket_count_mismatch() -> Result<()> {
 
 #[test]
 fn test_entropy_validation() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let mut block = create_test_block();
 
     // Set invalid entropy source (wrong size)
+    seal(&mut block);
     block.header.entropy_source = vec![0u8; 31]; // Invalid size
 
     let block_path = write_block_to_temp_file(&block)?;
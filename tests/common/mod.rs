@@ -1,5 +1,13 @@
 use std::path::PathBuf;
 
+use ed25519_dalek::SigningKey;
+use jamliquor::authorer::{fallback_index, Authorer, EpochSealing, SealKind};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::genesis::Genesis;
+use jamliquor::refine::{Entry, Invocation, InvocationResult};
+use jamliquor::schema::{Block, Extrinsic, Header, OpaqueHash, State};
+use jamliquor::{stf, Importer};
+
 /// Get the path to test vectors
 pub fn get_vector_path(vector_name: &str) -> PathBuf {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -17,3 +25,146 @@ pub fn create_temp_test_dir() -> tempfile::TempDir {
 pub fn get_test_seed() -> u64 {
     42 // Consistent seed for property-based testing
 }
+
+/// Header on `parent` with zero commitments and an all-zero entropy source and seal.
+pub fn header(parent: OpaqueHash, slot: u32, author_index: u16) -> Header {
+    Header {
+        parent,
+        parent_state_root: OpaqueHash::default(),
+        extrinsic_hash: OpaqueHash::default(),
+        slot,
+        epoch_mark: None,
+        tickets_mark: None,
+        offenders_mark: Vec::new(),
+        author_index,
+        entropy_source: vec![0u8; 96],
+        seal: vec![0u8; 96],
+    }
+}
+
+pub fn empty_extrinsic() -> Extrinsic {
+    Extrinsic {
+        tickets: Vec::new(),
+        preimages: Vec::new(),
+        guarantees: Vec::new(),
+        assurances: Vec::new(),
+        disputes: serde_json::Value::Null,
+    }
+}
+
+/// Block with an empty extrinsic.
+pub fn block(parent: OpaqueHash, slot: u32, author_index: u16) -> Block {
+    Block {
+        header: header(parent, slot, author_index),
+        extrinsic: empty_extrinsic(),
+    }
+}
+
+/// State with the tiny spec's validators, keys `signing_keys(6)`, active.
+pub fn validator_state() -> State {
+    let mut state = State::new();
+    state.active_validators = public_keys(&signing_keys(6));
+    state
+}
+
+/// Tiny-spec importer rooted at the zero hash on [`validator_state`].
+pub fn importer() -> Importer {
+    Importer::from_state(
+        ChainSpec::tiny(),
+        OpaqueHash::default(),
+        None,
+        validator_state(),
+    )
+}
+
+/// Genesis at slot 0 on [`validator_state`].
+pub fn genesis() -> Genesis {
+    Genesis::from_state(header(OpaqueHash::default(), 0, 0), validator_state())
+}
+
+/// `block` sealed on top of `prior` by a key from [`signing_keys`]: the slot's fallback
+/// key, or in a ticket-sealed epoch its author index and the slot's ticket.
+pub fn seal(prior: &State, mut block: Block) -> Block {
+    let spec = ChainSpec::tiny();
+    let header = &block.header;
+    let validators = stf::signing_validators(prior, header);
+    let sealing = EpochSealing::for_block(&spec, prior, header.slot, header.epoch_mark.as_ref());
+    let phase = header.slot % spec.epoch_length;
+    let kind = if sealing.tickets.is_empty() {
+        block.header.author_index =
+            fallback_index(&sealing.entropy, phase, validators.len()) as u16;
+        SealKind::Fallback
+    } else {
+        SealKind::Ticket(sealing.tickets[phase as usize % sealing.tickets.len()].clone())
+    };
+    let author = validators[usize::from(block.header.author_index)];
+    let key = (1..=u8::MAX)
+        .map(|i| SigningKey::from_bytes(&[i; 32]))
+        .find(|key| key.verifying_key().to_bytes() == *author.as_bytes())
+        .expect("author is one of the test keys");
+    block.header = Authorer::new(key, spec).seal(block.header, &sealing.entropy, &kind);
+    block
+}
+
+/// `block` sealed on top of the state `importer` holds for its parent, or for its root
+/// while the root is unanchored.
+pub fn sealed(importer: &Importer, block: Block) -> Block {
+    let tree = importer.tree();
+    let parent = tree
+        .get(&block.header.parent)
+        .or_else(|| tree.get(&tree.root()))
+        .expect("tree root");
+    seal(&parent.state.clone(), block)
+}
+
+/// Sealed empty block on `parent` naming the posterior state root `importer` holds
+/// for it.
+pub fn child(importer: &Importer, parent: OpaqueHash, slot: u32) -> Block {
+    let mut block = block(parent, slot, 0);
+    if let Some(root) = importer
+        .tree()
+        .get(&parent)
+        .and_then(|node| node.state.last_state_root)
+    {
+        block.header.parent_state_root = OpaqueHash::new(root);
+    }
+    sealed(importer, block)
+}
+
+/// Ed25519 keys `[1; 32]` to `[count; 32]`.
+pub fn signing_keys(count: u8) -> Vec<SigningKey> {
+    signing_keys_from(1, count)
+}
+
+/// Ed25519 keys `[first; 32]` onwards, `count` of them.
+pub fn signing_keys_from(first: u8, count: u8) -> Vec<SigningKey> {
+    (first..first + count)
+        .map(|i| SigningKey::from_bytes(&[i; 32]))
+        .collect()
+}
+
+pub fn public_keys(keys: &[SigningKey]) -> Vec<OpaqueHash> {
+    keys.iter()
+        .map(|key| OpaqueHash::new(key.verifying_key().to_bytes()))
+        .collect()
+}
+
+/// A mock service that authorizes any package, refines each item by echoing its
+/// extrinsics after `prefix`, and exports `exports` segments filled with `1, 2, ...`.
+pub fn service(
+    prefix: &'static [u8],
+    exports: usize,
+) -> impl FnMut(&Invocation) -> InvocationResult {
+    move |invocation: &Invocation| match invocation.entry {
+        Entry::IsAuthorized => InvocationResult {
+            output: Ok(b"authorized".to_vec()),
+            gas_used: 10,
+            exports: Vec::new(),
+        },
+        Entry::Refine => InvocationResult {
+            output: Ok([prefix, &invocation.extrinsics.concat()].concat()),
+            gas_used: 400,
+            exports: (0..exports).map(|i| vec![i as u8 + 1; 100]).collect(),
+        },
+    }
+}
@@ -4,16 +4,30 @@
 //! core system behaviors and invariants.
 
 use anyhow::Result;
+use jamliquor::authorer::{Authorer, SealKind};
+use jamliquor::chain_spec::ChainSpec;
 use jamliquor::state::State;
 use jamliquor::Importer;
 
 use jamliquor::schema::{
-    Block, Extrinsic, Header, OpaqueHash, Preimage, TicketBody, TicketEnvelope,
+    self, Block, Extrinsic, Header, OpaqueHash, Preimage, TicketBody, TicketEnvelope,
 };
 use serde_json::{to_value, Value};
 use std::fs::File;
 use tempfile::tempdir;
 
+/// The only validator of [`importer`], so the fallback key of every slot.
+fn author() -> Authorer {
+    let key = crate::common::signing_keys(1).remove(0);
+    Authorer::new(key, ChainSpec::default())
+}
+
+fn importer() -> Importer {
+    let mut state = schema::State::new();
+    state.active_validators = vec![*author().public_key()];
+    Importer::from_state(ChainSpec::default(), OpaqueHash::default(), None, state)
+}
+
 fn build_sample_block() -> (Block, Value) {
     let mut block = Block {
         header: Header {
@@ -55,6 +69,7 @@ fn build_sample_block() -> (Block, Value) {
         .compute_hash()
         .expect("failed to compute extrinsic hash");
     block.header.extrinsic_hash = OpaqueHash::new(extrinsic_hash);
+    block.header = author().seal(block.header, &OpaqueHash::default(), &SealKind::Fallback);
 
     let block_json = to_value(&block).expect("failed to serialize block to JSON");
     (block, block_json)
@@ -71,7 +86,7 @@ fn write_block_json(value: &Value) -> Result<std::path::PathBuf> {
 /// Test block import from vector
 #[test]
 fn test_block_import_from_vector() -> Result<()> {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let (expected_block, block_json) = build_sample_block();
     let vector_path = write_block_json(&block_json)?;
 
@@ -105,7 +120,7 @@ fn test_safrole_vectors() -> Result<()> {
 #[test]
 fn test_state_transition() -> Result<()> {
     let mut state = State::new();
-    let mut importer = Importer::new();
+    let mut importer = importer();
 
     // Simulate block import and state transition
     let (block_template, block_json) = build_sample_block();
@@ -137,7 +152,7 @@ mod property_tests {
 
     #[test]
     fn prop_block_import_sanity() {
-        let mut importer = Importer::new();
+        let mut importer = importer();
         let (_, block_json) = build_sample_block();
         let vector_path = write_block_json(&block_json).expect("failed to write block json");
 
@@ -151,7 +166,7 @@ mod property_tests {
     #[test]
     fn prop_state_transition_stability() {
         let mut state = State::new();
-        let mut importer = Importer::new();
+        let mut importer = importer();
 
         let (_, block_json) = build_sample_block();
         let vector_path = write_block_json(&block_json).expect("failed to write block json");
@@ -1,6 +1,6 @@
 //! Diagnostic tests to capture specific validation failures
 
-use jamliquor::{Importer, schema::Block};
+use jamliquor::{error_code::error_code, Importer, schema::Block};
 use std::path::Path;
 
 #[test]
@@ -29,29 +29,14 @@ fn test_detailed_block_import_failure() {
                 depth += 1;
             }
             
-            // Try to identify the specific validation step that failed
-            let error_str = e.to_string();
-            
-            if error_str.contains("Ticket count mismatch") {
-                println!("   🔍 Diagnosis: Ticket count mismatch - header.tickets_mark vs extrinsic.tickets");
-            } else if error_str.contains("Invalid block structure") && error_str.contains("Ticket count") {
-                println!("   🔍 Diagnosis: Ticket count mismatch - header.tickets_mark vs extrinsic.tickets");
-            } else if error_str.contains("slot") {
-                println!("   🔍 Diagnosis: Slot validation failed");
-            } else if error_str.contains("parent") {
-                println!("   🔍 Diagnosis: Parent hash validation failed");
-            } else if error_str.contains("structure") {
-                println!("   🔍 Diagnosis: Block structure validation failed");
-            } else if error_str.contains("extrinsic") {
-                println!("   🔍 Diagnosis: Extrinsic validation failed");
-            } else if error_str.contains("coretime") {
-                println!("   🔍 Diagnosis: CoreTime validation failed");
-            } else if error_str.contains("entropy") {
-                println!("   🔍 Diagnosis: Entropy validation failed");
-            } else if error_str.contains("signature") {
-                println!("   🔍 Diagnosis: Signature validation failed");
-            } else {
-                println!("   🔍 Diagnosis: Unknown validation failure");
+            // Identify the rejecting subsystem from the structured error code
+            match error_code(&e) {
+                Some(code) => {
+                    println!("   🔍 Diagnosis: {} rejected the block with `{}`", code.subsystem(), code);
+                }
+                None => {
+                    println!("   🔍 Diagnosis: Unknown validation failure");
+                }
             }
         }
     }
@@ -27,5 +27,6 @@ pub mod utils {
 }
 
 /// Declare test modules
+pub mod common;
 pub mod integration;
 pub mod unit;
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use ed25519_dalek::{Signer, SigningKey};
+use jamliquor::audit::{Announcement, AuditTarget, Auditor, TRANCHE_PERIOD};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::erasure::ErasureCoder;
+use jamliquor::refine::refine;
+use jamliquor::schema::{BlockchainError, OpaqueHash};
+use jamliquor::stf;
+use jamliquor::verification::{collect_checks, SignatureVerifier, VerificationConfig};
+
+use super::work_package_tests::bundle;
+use crate::common::{block, header, public_keys, seal, service, signing_keys, validator_state};
+
+fn target(core: u16) -> AuditTarget {
+    let coder = ErasureCoder::for_spec(&ChainSpec::tiny()).unwrap();
+    let refined = refine(
+        &mut service(b"", 2),
+        &coder,
+        &bundle(b"signed transfer"),
+        core,
+    )
+    .unwrap();
+    AuditTarget {
+        core,
+        report: refined.report,
+    }
+}
+
+#[test]
+fn no_shows_escalate_audits_to_later_tranches() {
+    let keys = signing_keys(6);
+    let validators = public_keys(&keys);
+    let target = target(200);
+    let report = target.report.hash();
+    let auditor = |key: &SigningKey| {
+        Auditor::new(
+            key.clone(),
+            validators.clone(),
+            341,
+            &header(OpaqueHash::default(), 10, 0),
+            vec![target.clone()],
+            Duration::ZERO,
+        )
+        .unwrap()
+    };
+
+    // Tranche 0 covers 10 of 341 cores: find a validator that skips core 200.
+    let mut auditor = keys
+        .iter()
+        .map(auditor)
+        .find_map(|mut auditor| auditor.tick(Duration::ZERO).is_empty().then_some(auditor))
+        .unwrap();
+    assert!(auditor.tick(Duration::from_secs(7)).is_empty());
+    assert_eq!(auditor.tranche_at(TRANCHE_PERIOD * 2), 2);
+
+    // Every other validator announces the report in tranche 0 and never judges it.
+    let block = header(OpaqueHash::default(), 10, 0).hash();
+    let reports = vec![(200, report)];
+    let message = Announcement::message(0, &block, &reports);
+    for (i, key) in keys.iter().enumerate() {
+        if i == usize::from(auditor.validator_index()) {
+            continue;
+        }
+        let mut announcement = Announcement {
+            tranche: 0,
+            block,
+            validator_index: i as u16,
+            reports: reports.clone(),
+            signature: key.sign(&message).to_bytes().to_vec(),
+        };
+        assert!(announcement.verify(&validators[i]).is_ok());
+        auditor.observe_announcement(&announcement).unwrap();
+
+        announcement.validator_index = (i as u16 + 1) % 6;
+        assert!(matches!(
+            auditor.observe_announcement(&announcement),
+            Err(BlockchainError::InvalidSignature { .. })
+        ));
+    }
+    assert_eq!(auditor.no_shows(&report, 0), 0);
+    assert_eq!(auditor.no_shows(&report, 1), 5);
+
+    // Five no-shows out of six validators: the report is picked up in tranche 1.
+    let announcements = auditor.tick(TRANCHE_PERIOD);
+    assert_eq!(announcements.len(), 1);
+    assert_eq!(announcements[0].tranche, 1);
+    assert_eq!(announcements[0].reports, reports);
+    assert!(announcements[0]
+        .verify(&validators[usize::from(auditor.validator_index())])
+        .is_ok());
+    assert_eq!(auditor.pending().len(), 1);
+    assert!(!auditor.is_audited(&report));
+}
+
+#[test]
+fn invalid_judgements_form_well_formed_verdicts() {
+    let spec = ChainSpec::tiny();
+    let coder = ErasureCoder::for_spec(&spec).unwrap();
+    let keys = signing_keys(6);
+    let validators = public_keys(&keys);
+    let target = target(1);
+    let report = target.report.hash();
+    let auditor = |i: usize| {
+        let mut auditor = Auditor::new(
+            keys[i].clone(),
+            validators.clone(),
+            spec.cores_count,
+            &header(OpaqueHash::default(), 42, 0),
+            vec![target.clone()],
+            Duration::from_secs(100),
+        )
+        .unwrap();
+        // Tiny has two cores, both audited in tranche 0.
+        assert_eq!(auditor.tick(Duration::from_secs(101)).len(), 1);
+        auditor
+    };
+
+    let bundle = bundle(b"signed transfer");
+    let mut honest = auditor(0);
+    let valid = honest
+        .audit(1, &bundle, &mut service(b"", 2), &coder)
+        .unwrap();
+    assert!(valid.valid);
+    assert!(valid.verify(&validators[0]).is_ok());
+    assert!(honest.pending().is_empty());
+    assert!(honest.is_audited(&report));
+    assert_eq!(honest.disputes(0), None);
+
+    // The report does not match what the other auditors' refinement produces.
+    let judge = |i: usize, prefix: &'static [u8]| {
+        auditor(i)
+            .audit(1, &bundle, &mut service(prefix, 2), &coder)
+            .unwrap()
+    };
+    let invalid = judge(3, b"forked ");
+    assert!(!invalid.valid);
+    honest.observe_judgement(&invalid).unwrap();
+    assert!(!honest.is_audited(&report));
+    // Two votes cannot fill a verdict of five.
+    assert_eq!(honest.disputes(0), None);
+
+    for (i, prefix) in [(1, &b""[..]), (4, b"forked "), (5, b"forked ")] {
+        honest.observe_judgement(&judge(i, prefix)).unwrap();
+    }
+    let disputes = honest.disputes(0).unwrap();
+    let votes = disputes["verdicts"][0]["votes"].as_array().unwrap();
+    let tally: Vec<_> = votes
+        .iter()
+        .map(|vote| {
+            (
+                vote["index"].as_u64().unwrap(),
+                vote["vote"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    // Wonky: ⌊V/3⌋ = 2 positive votes out of ⌊2V/3⌋ + 1 = 5.
+    assert_eq!(
+        tally,
+        [(0, true), (1, true), (3, false), (4, false), (5, false)]
+    );
+
+    let prior = validator_state();
+    let mut block = block(OpaqueHash::default(), 43, 0);
+    block.extrinsic.disputes = disputes;
+    let checks = collect_checks(&block.extrinsic, &validators).unwrap();
+    assert_eq!(checks.len(), 5);
+    SignatureVerifier::new(VerificationConfig::single_threaded())
+        .verify(&checks)
+        .unwrap();
+    stf::apply(&prior, &seal(&prior, block), &spec).unwrap();
+}
@@ -0,0 +1,160 @@
+use jamliquor::authorer::{verify_seal, Authorer, EpochSealing, ExtrinsicPool, SealKind};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::schema::{BlockchainError, OpaqueHash, Preimage, State, TicketBody};
+use jamliquor::Importer;
+use serde_json::{json, Value};
+
+use crate::common::signing_keys;
+
+fn authorers() -> Vec<Authorer> {
+    signing_keys(6)
+        .into_iter()
+        .map(|key| Authorer::new(key, ChainSpec::tiny()))
+        .collect()
+}
+
+fn state(authorers: &[Authorer]) -> State {
+    let mut state = State::new();
+    state.active_validators = authorers.iter().map(|a| *a.public_key()).collect();
+    state
+}
+
+fn guarantee(core: u16, slot: u64, accumulate_gas: u64) -> Value {
+    json!({
+        "slot": slot,
+        "report": {
+            "core_index": core,
+            "results": [{ "service_id": 7, "accumulate_gas": accumulate_gas }],
+        },
+        "signatures": [],
+    })
+}
+
+fn assurance(anchor: OpaqueHash, validator_index: u16) -> Value {
+    json!({
+        "anchor": format!("0x{}", hex::encode(anchor.as_bytes())),
+        "bitfield": "0x01",
+        "validator_index": validator_index,
+        "signature": format!("0x{}", hex::encode([0u8; 64])),
+    })
+}
+
+#[test]
+fn fallback_sealed_blocks_import_on_our_own_node() {
+    let authorers = authorers();
+    let parent = OpaqueHash::new([1; 32]);
+    let mut state = state(&authorers);
+    state.ticket_state.entropy = OpaqueHash::new([7; 32]);
+    let sealing = EpochSealing::of_state(&state);
+    let mut importer = Importer::from_state(ChainSpec::tiny(), parent, None, state);
+
+    let mut pool = ExtrinsicPool::new();
+    pool.add_guarantee(guarantee(1, 0, 100));
+    pool.add_guarantee(guarantee(0, 0, 5_000));
+    pool.add_guarantee(guarantee(0, 1, 200));
+    pool.add_guarantee(guarantee(1, 1, 300));
+    pool.add_assurance(assurance(parent, 3));
+    pool.add_assurance(assurance(parent, 1));
+    pool.add_assurance(assurance(OpaqueHash::new([2; 32]), 2));
+    pool.add_assurance(assurance(parent, 1));
+    for (requester, blob) in [(2, b"b"), (1, b"a"), (1, b"a"), (0, b"x")] {
+        pool.add_preimage(Preimage {
+            requester,
+            blob: blob.to_vec(),
+        });
+    }
+
+    for slot in 1..=3u32 {
+        let state = importer.state().clone();
+        let claims: Vec<_> = authorers
+            .iter()
+            .filter_map(|a| Some((a, a.claim(&state, slot)?)))
+            .collect();
+        assert_eq!(claims.len(), 1, "one fallback key per slot");
+        let (authorer, claim) = &claims[0];
+        assert_eq!(claim.seal, SealKind::Fallback);
+
+        let head = importer.best_head();
+        let authored = authorer.build(head, &state, claim, &pool).unwrap();
+        let block = &authored.block;
+        verify_seal(&block.header, authorer.public_key(), &sealing.entropy, None).unwrap();
+        assert_eq!(
+            *block.header.extrinsic_hash.as_bytes(),
+            block.extrinsic.compute_hash().unwrap()
+        );
+        if slot == 1 {
+            // Over-limit and second guarantees per core are left out, the rest sorted.
+            let slots: Vec<_> = block
+                .extrinsic
+                .guarantees
+                .iter()
+                .map(|g| g["slot"].clone())
+                .collect();
+            assert_eq!(slots, vec![json!(1), json!(0)]);
+            let assurers: Vec<_> = block
+                .extrinsic
+                .assurances
+                .iter()
+                .map(|a| a["validator_index"].as_u64().unwrap())
+                .collect();
+            assert_eq!(assurers, vec![1, 3]);
+            let requesters: Vec<_> = block
+                .extrinsic
+                .preimages
+                .iter()
+                .map(|p| p.requester)
+                .collect();
+            assert_eq!(requesters, vec![1, 2]);
+        }
+
+        importer.import_decoded(block.clone()).unwrap();
+        assert_eq!(importer.best_head(), block.header.hash());
+        assert_eq!(importer.last_state_root(), Some(authored.state_root));
+        pool.remove_included(&block.extrinsic);
+    }
+}
+
+#[test]
+fn ticket_sealed_slots_are_claimed_by_ticket_holders() {
+    let mut authorers = authorers();
+    let spec = ChainSpec::tiny();
+    let mut state = state(&authorers);
+    state.last_slot = 12;
+    state.ticket_state.entropy = OpaqueHash::new([7; 32]);
+    state.ticket_state.sealing_tickets = (0..spec.epoch_length as u8)
+        .map(|i| TicketBody {
+            id: OpaqueHash::new([i; 32]),
+            attempt: i % 2,
+        })
+        .collect();
+    let sealing = EpochSealing::of_state(&state);
+    authorers[4].add_ticket(OpaqueHash::new([3; 32]));
+
+    let claims: Vec<_> = authorers
+        .iter()
+        .filter_map(|a| a.claim(&state, 15))
+        .collect();
+    assert_eq!(claims.len(), 1);
+    assert_eq!(claims[0].author_index, 4);
+    assert_eq!(claims[0].seal, SealKind::Ticket(sealing.tickets[3].clone()));
+    assert!(authorers[4].claim(&state, 16).is_none());
+    assert!(authorers[4].claim(&state, 12).is_none());
+
+    let parent = OpaqueHash::new([1; 32]);
+    let authored = authorers[4]
+        .build(parent, &state, &claims[0], &ExtrinsicPool::new())
+        .unwrap();
+    let header = &authored.block.header;
+    let author = authorers[4].public_key();
+    verify_seal(header, author, &sealing.entropy, Some(&sealing.tickets[3])).unwrap();
+    assert!(matches!(
+        verify_seal(header, author, &sealing.entropy, None),
+        Err(BlockchainError::InvalidSignature { .. })
+    ));
+    let mut forged = header.clone();
+    forged.slot = 16;
+    assert!(verify_seal(&forged, author, &sealing.entropy, Some(&sealing.tickets[3])).is_err());
+
+    let mut importer = Importer::from_state(spec, parent, None, state);
+    importer.import_decoded(authored.block).unwrap();
+}
@@ -0,0 +1,123 @@
+use jamliquor::availability::{
+    verify_import, AvailabilityStore, Expired, Retention, IMPORTS_RETENTION_SLOTS,
+    RECENT_HISTORY_LEN,
+};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::erasure::{ErasureCoder, SEGMENT_SIZE};
+use jamliquor::refine::{refine, Entry, Invocation, InvocationResult, RefinedPackage};
+use jamliquor::schema::BlockchainError;
+use jamliquor::stf::ReportedPackage;
+use jamliquor::work_package::{ImportSpec, SegmentRootLookup, WorkPackageBundle};
+
+use super::work_package_tests::bundle;
+
+/// Exports two segments and outputs the first byte of every import.
+fn exporting(invocation: &Invocation) -> InvocationResult {
+    let exports = match invocation.entry {
+        Entry::IsAuthorized => Vec::new(),
+        Entry::Refine => vec![vec![0xaa; 10], vec![0xbb; 20]],
+    };
+    InvocationResult {
+        output: Ok(invocation.imports.iter().map(|s| s[0]).collect()),
+        gas_used: 1,
+        exports,
+    }
+}
+
+fn refined(bundle: &WorkPackageBundle) -> RefinedPackage {
+    let coder = ErasureCoder::for_spec(&ChainSpec::tiny()).unwrap();
+    refine(&mut exporting, &coder, bundle, 0).unwrap()
+}
+
+fn reported(refined: &RefinedPackage) -> ReportedPackage {
+    ReportedPackage {
+        work_package_hash: refined.report.package_spec.hash,
+        exports_root: refined.report.package_spec.exports_root,
+        core_index: 0,
+    }
+}
+
+#[test]
+fn imports_resolve_by_segments_root_or_reported_package() {
+    let mut store = AvailabilityStore::new(Retention::for_spec(&ChainSpec::tiny()));
+    let exporter = bundle(b"first");
+    let first = refined(&exporter);
+    store.put_refined(10, &exporter, &first);
+    store.record_reported(&[reported(&first)]);
+    let root = first.report.package_spec.exports_root;
+    assert_eq!(store.segment(&root, 1).unwrap().len(), SEGMENT_SIZE);
+    assert!(store.bundle(&first.report.package_spec.hash).is_some());
+
+    let mut importer = bundle(b"second").package;
+    importer.items[0].import_segments = vec![
+        ImportSpec {
+            tree_root: first.report.package_spec.hash,
+            index: 1,
+        },
+        ImportSpec {
+            tree_root: root,
+            index: 0,
+        },
+    ];
+    let second = store
+        .bundle_for(importer.clone(), vec![b"second".to_vec()])
+        .unwrap();
+    assert_eq!(
+        second.imports,
+        vec![first.exports[1].clone(), first.exports[0].clone()]
+    );
+    assert_eq!(
+        second.segment_root_lookup,
+        vec![SegmentRootLookup {
+            work_package_hash: first.report.package_spec.hash,
+            segment_tree_root: root,
+        }]
+    );
+    verify_import(&root, 1, &second.imports[0], &second.justifications[0]).unwrap();
+    assert!(verify_import(&root, 0, &second.imports[0], &second.justifications[0]).is_err());
+    assert!(verify_import(&root, 1, &[0xbb; 20], &second.justifications[0]).is_err());
+
+    let output = refined(&second).report.results[0].result.clone();
+    assert_eq!(output, Ok(vec![0xbb, 0xaa]));
+
+    importer.items[0].import_segments[1].index = 2;
+    assert!(matches!(
+        store.bundle_for(importer, vec![b"second".to_vec()]),
+        Err(BlockchainError::SegmentUnavailable { index: 2, .. })
+    ));
+}
+
+#[test]
+fn retention_windows_expire_bundles_then_segments() {
+    let spec = ChainSpec::tiny();
+    let mut store = AvailabilityStore::new(Retention::for_spec(&spec));
+    let exporter = bundle(b"first");
+    let first = refined(&exporter);
+    let (hash, root) = (
+        first.report.package_spec.hash,
+        first.report.package_spec.exports_root,
+    );
+    store.put_refined(10, &exporter, &first);
+
+    store.record_reported(&[reported(&first)]);
+    for _ in 1..RECENT_HISTORY_LEN {
+        store.record_reported(&[]);
+    }
+    assert_eq!(store.segments_root_of(&hash), Some(root));
+    store.record_reported(&[]);
+    assert_eq!(store.segments_root_of(&hash), None);
+
+    let audit_end = 10 + u64::from(spec.epoch_length);
+    assert_eq!(store.expire(audit_end), Expired::default());
+    assert_eq!(
+        store.expire(audit_end + 1),
+        Expired {
+            bundles: 1,
+            exports: 0
+        }
+    );
+    assert!(store.bundle(&hash).is_none());
+    assert!(store.segment(&root, 0).is_some());
+    assert_eq!(store.expire(11 + IMPORTS_RETENTION_SLOTS).exports, 1);
+    assert!(store.segment(&root, 0).is_none());
+}
@@ -0,0 +1,104 @@
+use jamliquor::block_tree::BlockTree;
+use jamliquor::schema::{OpaqueHash, State};
+
+use crate::common::{block, child, header, importer};
+
+#[test]
+fn importer_accepts_competing_forks() {
+    let mut importer = importer();
+    let a = child(&importer, OpaqueHash::default(), 1);
+    let a_hash = a.header.hash();
+    importer.import_decoded(a).unwrap();
+
+    let b = child(&importer, a_hash, 2);
+    let c = child(&importer, a_hash, 3);
+    let c_hash = c.header.hash();
+    importer.import_decoded(b).unwrap();
+    importer.import_decoded(c).unwrap();
+
+    assert_eq!(importer.tree().len(), 4, "root plus three blocks");
+    assert_eq!(
+        importer.best_head(),
+        c_hash,
+        "higher slot wins without tickets"
+    );
+    assert_eq!(importer.state().get_last_slot(), 3);
+
+    // Unknown parents are still rejected once the chain is anchored.
+    let orphan = block(OpaqueHash::new([7u8; 32]), 4, 3);
+    assert!(importer.import_decoded(orphan).is_err());
+
+    // Re-importing a known block is rejected.
+    assert!(importer
+        .import_decoded(child(&importer, a_hash, 3))
+        .is_err());
+}
+
+#[test]
+fn ticket_sealed_chain_is_preferred() {
+    let root = OpaqueHash::default();
+    let mut tree = BlockTree::new(root, None, State::new());
+
+    let ticketed = header(root, 1, 0);
+    let ticketed_hash = ticketed.hash();
+    tree.insert(root, ticketed, State::new(), true);
+
+    let fallback = header(root, 1, 1);
+    let fallback_hash = fallback.hash();
+    tree.insert(root, fallback.clone(), State::new(), false);
+    let longer = header(fallback_hash, 2, 2);
+    tree.insert(fallback_hash, longer, State::new(), false);
+
+    assert_eq!(tree.best(), ticketed_hash);
+}
+
+#[test]
+fn equivocating_chains_are_never_best() {
+    let root = OpaqueHash::default();
+    let mut tree = BlockTree::new(root, None, State::new());
+
+    let honest = header(root, 1, 0);
+    let honest_hash = honest.hash();
+    tree.insert(root, honest, State::new(), false);
+
+    // Author 1 produces two different blocks for slot 2.
+    let mut first = header(root, 2, 1);
+    first.seal = vec![1u8; 96];
+    let first_hash = first.hash();
+    tree.insert(root, first, State::new(), true);
+    let descendant = header(first_hash, 3, 2);
+    let descendant_hash = descendant.hash();
+    tree.insert(first_hash, descendant, State::new(), true);
+    assert_eq!(tree.best(), descendant_hash);
+    let mut second = header(root, 2, 1);
+    second.seal = vec![2u8; 96];
+    tree.insert(root, second, State::new(), true);
+
+    assert!(tree.get(&first_hash).unwrap().equivocated);
+    assert!(tree.get(&descendant_hash).unwrap().chain_equivocated);
+    assert_eq!(tree.best(), honest_hash);
+}
+
+#[test]
+fn prune_to_discards_competing_branches() {
+    let root = OpaqueHash::default();
+    let mut tree = BlockTree::new(root, None, State::new());
+
+    let a = header(root, 1, 0);
+    let a_hash = a.hash();
+    tree.insert(root, a, State::new(), false);
+    let b = header(root, 1, 1);
+    let b_hash = b.hash();
+    tree.insert(root, b, State::new(), false);
+    let a2 = header(a_hash, 2, 2);
+    let a2_hash = a2.hash();
+    tree.insert(a_hash, a2, State::new(), false);
+
+    let pruned = tree.prune_to(a_hash);
+
+    assert_eq!(pruned, [b_hash], "only the competing branch is pruned");
+    assert_eq!(tree.root(), a_hash);
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.best(), a2_hash);
+    assert_eq!(tree.get(&a2_hash).unwrap().depth, 1);
+}
@@ -0,0 +1,108 @@
+use std::fs;
+
+use jamliquor::chain_import::{InvalidBlockPolicy, IMPORT_FINALITY_LAG};
+use jamliquor::codec::Encode;
+use jamliquor::schema::OpaqueHash;
+
+use crate::common::{child, importer};
+
+#[test]
+fn directory_of_block_files_imports_in_name_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut scratch = importer();
+    let mut parent = OpaqueHash::default();
+    for slot in 1..=4 {
+        let next = child(&scratch, parent, slot);
+        parent = next.header.hash();
+        let file = dir.path().join(format!("{slot:04}.json"));
+        fs::write(file, serde_json::to_vec(&next).unwrap()).unwrap();
+        scratch.import_decoded(next).unwrap();
+    }
+
+    let mut importer = importer();
+    let report = importer
+        .import_chain(dir.path(), InvalidBlockPolicy::Stop)
+        .unwrap();
+    assert_eq!(report.imported, 4);
+    assert_eq!(report.last_slot, Some(4));
+    assert!(report.rejected.is_empty());
+    assert_eq!(importer.best_head(), parent);
+    assert_eq!(report.phases.phases().len(), 6);
+}
+
+#[test]
+fn long_chains_are_finalized_as_they_import() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chain.jlb");
+    let mut scratch = importer();
+    let mut parent = OpaqueHash::default();
+    let mut stream = Vec::new();
+    let count = 2 * IMPORT_FINALITY_LAG as u32 + 10;
+    for slot in 1..=count {
+        let next = child(&scratch, parent, slot);
+        parent = next.header.hash();
+        next.encode_to(&mut stream);
+        scratch.import_decoded(next).unwrap();
+    }
+    fs::write(&path, stream).unwrap();
+
+    let mut importer = importer();
+    let report = importer
+        .import_chain(&path, InvalidBlockPolicy::Stop)
+        .unwrap();
+    assert_eq!(report.imported, count as usize);
+    assert_eq!(importer.best_head(), parent);
+    assert_eq!(importer.tree().len(), IMPORT_FINALITY_LAG as usize + 11);
+    assert_eq!(
+        importer
+            .tree()
+            .get(&importer.finalized_head())
+            .unwrap()
+            .slot(),
+        u64::from(count) - IMPORT_FINALITY_LAG - 10
+    );
+}
+
+/// A binary stream `a, bad, child-of-bad, fork` where `bad` names a wrong state root.
+fn write_stream(path: &std::path::Path) -> [OpaqueHash; 4] {
+    let mut scratch = importer();
+    let a = child(&scratch, OpaqueHash::default(), 1);
+    scratch.import_decoded(a.clone()).unwrap();
+    let mut bad = child(&scratch, a.header.hash(), 2);
+    bad.header.parent_state_root = OpaqueHash::new([0xee; 32]);
+    let orphan = child(&scratch, bad.header.hash(), 3);
+    let fork = child(&scratch, a.header.hash(), 2);
+
+    let mut stream = Vec::new();
+    for block in [&a, &bad, &orphan, &fork] {
+        block.encode_to(&mut stream);
+    }
+    fs::write(path, stream).unwrap();
+    [&a, &bad, &orphan, &fork].map(|block| block.header.hash())
+}
+
+#[test]
+fn invalid_blocks_in_a_stream_stop_or_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chain.jlb");
+    let [a, _, _, fork] = write_stream(&path);
+
+    let mut importer = importer();
+    let report = importer
+        .import_chain(&path, InvalidBlockPolicy::Stop)
+        .unwrap();
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].slot, Some(2));
+    assert_eq!(importer.best_head(), a);
+
+    let mut skipping = crate::common::importer();
+    let report = skipping
+        .import_chain(&path, InvalidBlockPolicy::Skip)
+        .unwrap();
+    assert_eq!(report.imported, 2);
+    let slots: Vec<_> = report.rejected.iter().map(|block| block.slot).collect();
+    assert_eq!(slots, [Some(2), Some(3)]);
+    assert!(format!("{:#}", report.rejected[1].error).contains("Parent hash mismatch"));
+    assert_eq!(skipping.best_head(), fork);
+}
@@ -0,0 +1,114 @@
+use jamliquor::codec::{decode_exact, decode_natural, encode_natural, Encode};
+use jamliquor::error_code::ErrorCode;
+use jamliquor::schema::{
+    Block, BlockchainError, EpochMark, Extrinsic, Header, OpaqueHash, Preimage, TicketBody,
+    TicketEnvelope,
+};
+use jamliquor::Codec;
+use serde_json::json;
+
+fn build_sample_block() -> Block {
+    Block {
+        header: Header {
+            parent: OpaqueHash::new([1u8; 32]),
+            parent_state_root: OpaqueHash::new([2u8; 32]),
+            extrinsic_hash: OpaqueHash::new([3u8; 32]),
+            slot: 43,
+            epoch_mark: Some(EpochMark {
+                entropy: OpaqueHash::new([4u8; 32]),
+                tickets_entropy: OpaqueHash::new([5u8; 32]),
+                validators: vec![OpaqueHash::new([6u8; 32])],
+            }),
+            tickets_mark: Some(vec![TicketBody {
+                id: OpaqueHash::new([7u8; 32]),
+                attempt: 1,
+            }]),
+            offenders_mark: vec![OpaqueHash::new([8u8; 32])],
+            author_index: 3,
+            entropy_source: vec![9u8; 96],
+            seal: vec![10u8; 96],
+        },
+        extrinsic: Extrinsic {
+            tickets: vec![TicketEnvelope {
+                attempt: 1,
+                signature: vec![11u8; 784],
+            }],
+            preimages: vec![Preimage {
+                requester: 7,
+                blob: vec![1, 2, 3],
+            }],
+            guarantees: vec![json!({"slot": 42, "report": {"core_index": 0}})],
+            assurances: Vec::new(),
+            disputes: serde_json::Value::Null,
+        },
+    }
+}
+
+#[test]
+fn natural_encoding_roundtrips_across_length_classes() {
+    for value in [
+        0u64,
+        1,
+        127,
+        128,
+        16_383,
+        16_384,
+        1 << 21,
+        (1 << 56) - 1,
+        1 << 56,
+        u64::MAX,
+    ] {
+        let mut out = Vec::new();
+        encode_natural(value, &mut out);
+        let mut input = out.as_slice();
+        assert_eq!(decode_natural(&mut input).unwrap(), value);
+        assert!(input.is_empty(), "natural {value} left trailing bytes");
+    }
+
+    let mut out = Vec::new();
+    encode_natural(127, &mut out);
+    assert_eq!(out, vec![0x7f], "values below 2^7 use a single byte");
+}
+
+#[test]
+fn binary_block_roundtrip() {
+    let block = build_sample_block();
+    let bytes = Codec::Binary.encode_block(&block).unwrap();
+    let decoded = Codec::Binary.decode_block(&bytes).unwrap();
+    assert_eq!(decoded, block);
+}
+
+#[test]
+fn json_and_binary_decode_to_same_block() {
+    let block = build_sample_block();
+    let json = Codec::Json.encode_block(&block).unwrap();
+    let binary = Codec::Binary.encode_block(&block).unwrap();
+    assert_eq!(
+        Codec::Json.read_block(json.as_slice()).unwrap(),
+        Codec::Binary.read_block(binary.as_slice()).unwrap()
+    );
+}
+
+#[test]
+fn binary_decode_rejects_truncated_and_trailing_input() {
+    let bytes = build_sample_block().encode();
+
+    let err = Codec::Binary
+        .decode_block(&bytes[..bytes.len() - 1])
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::Malformed));
+    assert!(err.to_string().contains("Codec error"), "{err}");
+
+    let mut padded = bytes.clone();
+    padded.push(0);
+    let err = decode_exact::<Block>(&padded).unwrap_err();
+    assert!(matches!(err, BlockchainError::CodecError { .. }));
+}
+
+#[test]
+fn codec_from_path_uses_extension() {
+    assert_eq!(Codec::from_path("block.jlb"), Codec::Binary);
+    assert_eq!(Codec::from_path("block.bin"), Codec::Json);
+    assert_eq!(Codec::from_path("block.json"), Codec::Json);
+    assert_eq!(Codec::from_path("block"), Codec::Json);
+}
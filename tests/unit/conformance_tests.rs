@@ -0,0 +1,71 @@
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
+
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::conformance::{self, Fuzzer, Message, Target};
+use jamliquor::coretime::CoreTimeLedger;
+use jamliquor::merkle::merklize;
+use jamliquor::schema::{OpaqueHash, State};
+
+use crate::common::{block, header, seal, validator_state};
+
+fn connect(socket: &std::path::Path) -> UnixStream {
+    for _ in 0..100 {
+        if let Ok(stream) = UnixStream::connect(socket) {
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("target did not start listening");
+}
+
+#[test]
+fn fuzzer_session_over_a_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("target.sock");
+    let listen_at = socket.clone();
+    thread::spawn(move || conformance::listen(listen_at, ChainSpec::tiny()));
+
+    let mut fuzzer = Fuzzer::new(connect(&socket));
+    let peer = fuzzer.handshake("local-fuzzer").unwrap();
+    assert_eq!(peer.name, "jamliquor");
+    assert_eq!(peer.fuzz_version, conformance::FUZZ_PROTOCOL_VERSION);
+
+    let mut state = validator_state();
+    state.coretime = CoreTimeLedger::starting_at(0);
+    let key_values = state.to_key_values();
+    let genesis = header(OpaqueHash::default(), 0, 0);
+    let genesis_root = fuzzer.initialize(&genesis, &key_values).unwrap();
+    assert_eq!(genesis_root, merklize(&key_values));
+    assert_eq!(fuzzer.get_state(&genesis.hash()).unwrap(), key_values);
+
+    let mut first = block(genesis.hash(), 1, 0);
+    first.header.parent_state_root = OpaqueHash::new(genesis_root);
+    let first = seal(&state, first);
+    let root = fuzzer.import_block(&first).unwrap();
+    let posterior = fuzzer.get_state(&first.header.hash()).unwrap();
+    assert_eq!(merklize(&posterior), root);
+
+    // An invalid block is reported without ending the session.
+    let mut orphan = block(OpaqueHash::new([9u8; 32]), 2, 0);
+    orphan.header.parent_state_root = OpaqueHash::new(root);
+    assert!(fuzzer.import_block(&orphan).is_err());
+    let mut second = block(first.header.hash(), 2, 0);
+    second.header.parent_state_root = OpaqueHash::new(root);
+    let second = seal(&State::from_key_values(&posterior).unwrap(), second);
+    assert_ne!(fuzzer.import_block(&second).unwrap(), root);
+}
+
+#[test]
+fn session_must_start_with_the_handshake() {
+    let (mut target_end, fuzzer_end) = UnixStream::pair().unwrap();
+    let session = thread::spawn(move || Target::new(ChainSpec::tiny()).serve(&mut target_end));
+
+    let mut fuzzer = Fuzzer::new(fuzzer_end);
+    let response = fuzzer
+        .request(&Message::GetState(OpaqueHash::default()))
+        .unwrap();
+    assert!(matches!(response, Message::Error(_)));
+    assert!(session.join().unwrap().is_err());
+}
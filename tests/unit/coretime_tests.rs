@@ -1,7 +1,7 @@
 use jamliquor::coretime::{
     CoreTimeLedger, MAX_CORETIME_PER_CORE, MAX_DISPUTE_AGE, MAX_GUARANTEE_LOOKBACK,
 };
-use jamliquor::schema::BlockchainError;
+use jamliquor::error_code::{ErrorCode, Subsystem};
 use serde_json::{json, Value};
 
 #[test]
@@ -65,7 +65,8 @@ fn validate_and_apply_rejects_coretime_overuse() {
         .validate_and_apply(50, &guarantees, &[], &Value::Null)
         .expect_err("CoreTime validation should fail when consumption exceeds limits");
 
-    assert!(matches!(err, BlockchainError::CoreTimeBalanceError { .. }));
+    assert_eq!(err.code(), Some(ErrorCode::WorkReportGasTooHigh));
+    assert_eq!(err.index(), Some(0));
 }
 
 #[test]
@@ -94,8 +95,7 @@ fn validate_and_apply_rejects_stale_disputes() {
         .validate_and_apply(10, &guarantees, &assurances, &disputes)
         .expect_err("CoreTime validation should fail for stale disputes");
 
-    assert!(matches!(
-        err,
-        BlockchainError::CoreTimeValidationError { .. }
-    ));
+    assert_eq!(err.code(), Some(ErrorCode::BadJudgementAge));
+    assert_eq!(err.subsystem(), Some(Subsystem::Disputes));
+    assert_eq!(err.index(), Some(0));
 }
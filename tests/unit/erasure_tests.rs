@@ -0,0 +1,80 @@
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::erasure::{
+    gf_mul, paged_proofs, segments_root, ErasureCoder, SEGMENTS_PER_PAGE, SEGMENT_SIZE,
+};
+use jamliquor::schema::BlockchainError;
+use jamliquor::vectors::run_erasure_vectors;
+
+#[test]
+fn cantor_basis_and_any_sufficient_subset_reconstructs() {
+    // Basis elements have coordinates 1 << i and satisfy v_i^2 + v_i = v_(i-1).
+    for i in 1..16 {
+        let v = 1u16 << i;
+        assert_eq!(gf_mul(v, v) ^ v, v >> 1);
+    }
+
+    let tiny = ErasureCoder::for_spec(&ChainSpec::tiny()).unwrap();
+    let data: Vec<u8> = (0..10u8).collect();
+    let chunks = tiny.encode(&data);
+    assert_eq!(chunks.len(), 6);
+    // Chunk 0 takes the first word of each four-octet piece.
+    assert_eq!(chunks[0], [0, 1, 4, 5, 8, 9]);
+    for a in 0..6 {
+        for b in a + 1..6 {
+            let subset = [(a, chunks[a].clone()), (b, chunks[b].clone())];
+            assert_eq!(tiny.reconstruct(&subset, data.len()).unwrap(), data);
+        }
+    }
+    assert!(matches!(
+        tiny.reconstruct(
+            &[(3, chunks[3].clone()), (3, chunks[3].clone())],
+            data.len()
+        ),
+        Err(BlockchainError::ErasureCoding { .. })
+    ));
+
+    let full = ErasureCoder::for_spec(&ChainSpec::full()).unwrap();
+    let segment: Vec<u8> = (0..SEGMENT_SIZE).map(|i| (i * 7 % 251) as u8).collect();
+    let chunks = full.encode(&segment);
+    assert_eq!((chunks.len(), chunks[0].len()), (1023, 12));
+    for (skip, step) in [(681, 1), (0, 3), (1, 2)] {
+        let subset: Vec<_> = chunks
+            .iter()
+            .cloned()
+            .enumerate()
+            .skip(skip)
+            .step_by(step)
+            .collect();
+        let reconstructed = full.reconstruct(&subset, segment.len());
+        if subset.len() < 342 {
+            assert!(reconstructed.is_err());
+        } else {
+            assert_eq!(reconstructed.unwrap(), segment);
+        }
+    }
+}
+
+#[test]
+fn paged_proofs_cover_64_segments_each() {
+    let segments: Vec<Vec<u8>> = (0..130u8).map(|i| vec![i; SEGMENT_SIZE]).collect();
+    let proofs = paged_proofs(&segments);
+    assert_eq!(proofs.len(), 3);
+    assert!(proofs.iter().all(|proof| proof.len() == SEGMENT_SIZE));
+    // 256 leaves: a two-hash justification, then the page's leaf hashes.
+    assert_eq!(proofs[0][0], 2);
+    assert_eq!(proofs[0][65], SEGMENTS_PER_PAGE as u8);
+    assert_eq!(proofs[2][66], 2);
+
+    assert_ne!(segments_root(&segments), segments_root(&segments[..129]));
+    assert_eq!(segments_root(&[]), [0; 32]);
+}
+
+#[test]
+fn vendored_vectors_match_the_interleaved_layout() {
+    for spec in [ChainSpec::tiny(), ChainSpec::full()] {
+        let dir = format!("tests/fixtures/erasure/{}", spec.name);
+        let outcomes = run_erasure_vectors(&dir, &spec).unwrap();
+        assert_eq!(outcomes.len(), 1, "{dir}");
+        assert!(outcomes[0].passed(), "{:?}", outcomes[0]);
+    }
+}
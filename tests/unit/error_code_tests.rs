@@ -0,0 +1,42 @@
+use jamliquor::error_code::{error_code, ErrorCode, Subsystem};
+use jamliquor::schema::{BlockchainError, OpaqueHash, Preimage};
+
+use crate::common::{block, importer, sealed};
+
+#[test]
+fn error_codes_round_trip_through_their_vector_names() {
+    for code in ErrorCode::ALL {
+        assert_eq!(code.as_str().parse::<ErrorCode>().unwrap(), *code);
+        assert_eq!(
+            serde_json::to_value(code).unwrap(),
+            serde_json::Value::String(code.as_str().to_string())
+        );
+    }
+    assert!("not_a_code".parse::<ErrorCode>().is_err());
+}
+
+#[test]
+fn rejected_block_reports_code_subsystem_and_item() {
+    let mut block = block(OpaqueHash::default(), 1, 0);
+    block.extrinsic.preimages = vec![
+        Preimage {
+            requester: 1,
+            blob: vec![1],
+        },
+        Preimage {
+            requester: 0,
+            blob: vec![2],
+        },
+    ];
+
+    let mut importer = importer();
+    let block = sealed(&importer, block);
+    let err = importer.import_decoded(block).unwrap_err();
+    assert_eq!(error_code(&err), Some(ErrorCode::PreimageUnneeded));
+    let rejection = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+        .unwrap();
+    assert_eq!(rejection.subsystem(), Some(Subsystem::Preimages));
+    assert_eq!(rejection.index(), Some(1));
+}
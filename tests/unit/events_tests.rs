@@ -0,0 +1,88 @@
+use std::sync::{Arc, Mutex};
+
+use jamliquor::coretime::MAX_CORETIME_PER_CORE;
+use jamliquor::events::ImportEvent;
+use jamliquor::schema::OpaqueHash;
+use jamliquor::Importer;
+use serde_json::json;
+
+use crate::common::{block, child, importer, sealed};
+
+/// An importer whose observer records a one-line summary of every event.
+fn observed_importer() -> (Importer, Arc<Mutex<Vec<String>>>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut importer = importer();
+    let sink = Arc::clone(&log);
+    importer.observe(move |event: &ImportEvent<'_>| {
+        let line = match event {
+            ImportEvent::BlockImported { slot, best, .. } => format!("imported {slot} {best}"),
+            ImportEvent::BlockRejected { slot, .. } => format!("rejected {slot}"),
+            ImportEvent::EpochChanged { epoch, .. } => format!("epoch {epoch}"),
+            ImportEvent::Offenders { offenders, .. } => format!("offenders {}", offenders.len()),
+            ImportEvent::WorkReported { package, .. } => {
+                format!("reported core {}", package.core_index)
+            }
+            ImportEvent::CoreTimeLimitApproached { core, consumed, .. } => {
+                format!("coretime {core:?} {consumed}")
+            }
+            ImportEvent::Finalized { slot, pruned, .. } => {
+                format!("finalized {slot} pruned {}", pruned.len())
+            }
+        };
+        sink.lock().unwrap().push(line);
+    });
+    (importer, log)
+}
+
+#[test]
+fn import_and_rejection_events_are_reported_in_order() {
+    let (mut importer, log) = observed_importer();
+
+    let mut first = block(OpaqueHash::default(), 13, 0);
+    first.header.offenders_mark = vec![OpaqueHash::new([7; 32])];
+    first.extrinsic.guarantees = vec![json!({
+        "slot": 13,
+        "report": {
+            "core_index": 1,
+            "package_spec": { "hash": format!("0x{}", "11".repeat(32)) },
+            "results": [{ "accumulate_gas": MAX_CORETIME_PER_CORE - 10 }]
+        }
+    })];
+    let first = importer.import_decoded(sealed(&importer, first)).unwrap();
+
+    let mut rejected = child(&importer, first.header.hash(), 14);
+    rejected.header.parent_state_root = OpaqueHash::new([0xee; 32]);
+    assert!(importer.import_decoded(rejected).is_err());
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "imported 13 true",
+            "epoch 1",
+            "offenders 1",
+            "reported core 1",
+            "coretime Some(1) 1014",
+            "rejected 14",
+        ]
+    );
+}
+
+#[test]
+fn finality_event_lists_pruned_blocks() {
+    let (mut importer, log) = observed_importer();
+    let a = importer
+        .import_decoded(child(&importer, OpaqueHash::default(), 1))
+        .unwrap();
+    let b = importer
+        .import_decoded(child(&importer, a.header.hash(), 2))
+        .unwrap();
+    importer
+        .import_decoded(child(&importer, a.header.hash(), 3))
+        .unwrap();
+    log.lock().unwrap().clear();
+
+    let pruned = importer.finalize(b.header.hash(), None).unwrap();
+    // The root and `a` are finalized ancestors, only the fork at slot 3 is pruned.
+    assert_eq!(pruned.len(), 1);
+    assert_eq!(*log.lock().unwrap(), ["finalized 2 pruned 1"]);
+}
@@ -0,0 +1,102 @@
+use ed25519_dalek::{Signer, SigningKey};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::finality::{precommit_payload, supermajority, FinalityVote, Justification};
+use jamliquor::schema::{EpochMark, OpaqueHash};
+use jamliquor::Importer;
+
+use crate::common::{block, child, public_keys, sealed, signing_keys};
+
+fn justify(
+    keys: &[SigningKey],
+    signers: &[u16],
+    block_hash: OpaqueHash,
+    slot: u32,
+) -> Justification {
+    let payload = precommit_payload(&block_hash, slot);
+    Justification {
+        block_hash,
+        slot,
+        votes: signers
+            .iter()
+            .map(|&i| FinalityVote {
+                validator_index: i,
+                signature: keys[i as usize].sign(&payload).to_bytes().to_vec(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn justification_requires_supermajority_of_valid_votes() {
+    let keys = signing_keys(6);
+    let validators = public_keys(&keys);
+    let hash = OpaqueHash::new([5u8; 32]);
+    assert_eq!(supermajority(validators.len()), 5);
+
+    assert!(justify(&keys, &[0, 1, 2, 3, 4], hash, 7)
+        .verify(&validators)
+        .is_ok());
+    assert!(justify(&keys, &[0, 1, 2, 3], hash, 7)
+        .verify(&validators)
+        .is_err());
+    assert!(justify(&keys, &[0, 1, 2, 3, 3], hash, 7)
+        .verify(&validators)
+        .is_err());
+
+    let mut forged = justify(&keys, &[0, 1, 2, 3, 4], hash, 7);
+    forged.slot = 8;
+    assert!(forged.verify(&validators).is_err());
+    assert!(forged.verify(&[]).is_err());
+}
+
+#[test]
+fn finalize_prunes_forks_and_resumes_from_the_store() {
+    let keys = signing_keys(6);
+    let dir = tempfile::tempdir().unwrap();
+    let mut importer = Importer::open(ChainSpec::tiny(), dir.path()).unwrap();
+
+    let mut first = block(OpaqueHash::default(), 1, 0);
+    first.header.entropy_source = vec![0u8; 96];
+    first.header.epoch_mark = Some(EpochMark {
+        entropy: OpaqueHash::default(),
+        tickets_entropy: OpaqueHash::default(),
+        validators: public_keys(&keys),
+    });
+    // The epoch mark activates the set on the empty store.
+    let first = sealed(&importer, first);
+    let first_hash = first.header.hash();
+    importer.import_decoded(first).unwrap();
+
+    let kept = child(&importer, first_hash, 2);
+    let kept_hash = kept.header.hash();
+    let fork = child(&importer, first_hash, 3);
+    let fork_hash = fork.header.hash();
+    importer.import_decoded(kept).unwrap();
+    importer.import_decoded(fork).unwrap();
+    assert_eq!(importer.best_head(), fork_hash);
+
+    // A justification for a different block, or without a supermajority, is rejected.
+    let wrong = justify(&keys, &[0, 1, 2, 3, 4], fork_hash, 2);
+    assert!(importer.finalize(kept_hash, Some(&wrong)).is_err());
+    let weak = justify(&keys, &[0, 1], kept_hash, 2);
+    assert!(importer.finalize(kept_hash, Some(&weak)).is_err());
+    assert_eq!(importer.tree().len(), 4);
+
+    let justification = justify(&keys, &[0, 1, 2, 3, 5], kept_hash, 2);
+    let pruned = importer.finalize(kept_hash, Some(&justification)).unwrap();
+    assert!(pruned.contains(&fork_hash));
+    assert_eq!(importer.finalized_head(), kept_hash);
+    assert_eq!(importer.best_head(), kept_hash);
+    assert_eq!(importer.tree().len(), 1);
+
+    // A fresh importer picks up where the finalized chain left off.
+    let finalized = importer.state().clone();
+    drop(importer);
+    let mut resumed = Importer::open(ChainSpec::tiny(), dir.path()).unwrap();
+    assert_eq!(resumed.finalized_head(), kept_hash);
+    assert_eq!(resumed.state().get_last_slot(), 2);
+    assert_eq!(*resumed.state(), finalized);
+    assert!(resumed.import_decoded(block(fork_hash, 4, 3)).is_err());
+    let next = child(&resumed, kept_hash, 4);
+    resumed.import_decoded(next).unwrap();
+}
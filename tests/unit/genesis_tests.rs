@@ -0,0 +1,115 @@
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::genesis::{Genesis, VALIDATOR_RECORD_LEN};
+use jamliquor::merkle::{merklize, KeyValues};
+use jamliquor::schema::{Header, OpaqueHash};
+use jamliquor::state_keys::component_key;
+use jamliquor::Importer;
+use serde_json::{json, Value};
+
+use crate::common::{child, header, public_keys, signing_keys};
+
+/// GP-serialized validator key records whose Ed25519 keys are those of `signing_keys`.
+fn validator_records(count: u8) -> Vec<u8> {
+    let mut records = Vec::new();
+    for key in public_keys(&signing_keys(count)) {
+        let mut record = vec![0u8; VALIDATOR_RECORD_LEN];
+        record[32..64].copy_from_slice(key.as_bytes());
+        records.extend(record);
+    }
+    records
+}
+
+pub fn gp_key_values(spec: &ChainSpec) -> KeyValues {
+    let mut kvs = KeyValues::new();
+    kvs.insert(component_key(1), vec![0xaa; 40]);
+    let mut gamma = validator_records(spec.validators_count as u8);
+    gamma.extend([0u8; 144]);
+    gamma.push(1); // fallback keys
+    kvs.insert(component_key(4), gamma);
+    kvs.insert(
+        component_key(8),
+        validator_records(spec.validators_count as u8),
+    );
+    kvs.insert(component_key(11), 5u32.to_le_bytes().to_vec());
+    kvs
+}
+
+fn document(header: &Header, kvs: &KeyValues, root: [u8; 32]) -> Value {
+    let keyvals: Vec<Value> = kvs
+        .iter()
+        .map(|(key, value)| {
+            json!({ "key": format!("0x{}", hex::encode(key)), "value": format!("0x{}", hex::encode(value)) })
+        })
+        .collect();
+    json!({
+        "header": header,
+        "state": { "state_root": format!("0x{}", hex::encode(root)), "keyvals": keyvals },
+    })
+}
+
+#[test]
+fn gp_key_values_bootstrap_the_importer() {
+    let spec = ChainSpec::tiny();
+    let kvs = gp_key_values(&spec);
+    let genesis_header = header(OpaqueHash::default(), 5, 0);
+    let genesis =
+        Genesis::from_json(&document(&genesis_header, &kvs, merklize(&kvs)), &spec).unwrap();
+
+    assert_eq!(genesis.hash(), genesis_header.hash());
+    assert_eq!(genesis.state.last_slot, 5);
+    assert_eq!(genesis.state.active_validators.len(), 6);
+    assert_eq!(
+        genesis.state.pending_validators[1],
+        public_keys(&signing_keys(2))[1]
+    );
+    assert!(!genesis.state.ticket_state.sealed_by_tickets);
+
+    let mut importer = Importer::from_genesis(spec, &genesis);
+    assert_eq!(importer.finalized_head(), genesis.hash());
+    assert_eq!(importer.coretime().last_block_slot(), Some(5));
+
+    let block = child(&importer, genesis.hash(), 6);
+    assert_eq!(
+        block.header.parent_state_root,
+        OpaqueHash::new(genesis.state_root)
+    );
+    let mut wrong_root = block.clone();
+    wrong_root.header.parent_state_root = OpaqueHash::default();
+    assert!(importer.import_decoded(wrong_root).is_err());
+    importer.import_decoded(block).unwrap();
+    assert_eq!(
+        importer.state().active_validators,
+        genesis.state.active_validators
+    );
+}
+
+#[test]
+fn typed_state_and_root_checks() {
+    let spec = ChainSpec::tiny();
+    let genesis_header = header(OpaqueHash::default(), 0, 0);
+    let typed = json!({
+        "header": genesis_header,
+        "state": {
+            "tau": 0,
+            "kappa": [{ "ed25519": format!("0x{}", "01".repeat(32)) }],
+            "gamma": {
+                "gamma_k": [format!("0x{}", "02".repeat(32))],
+                "gamma_s": { "tickets": [] },
+            },
+        },
+    });
+    let genesis = Genesis::from_json(&typed, &spec).unwrap();
+    assert_eq!(
+        genesis.state.active_validators,
+        vec![OpaqueHash::new([1u8; 32])]
+    );
+    assert_eq!(
+        genesis.state.pending_validators,
+        vec![OpaqueHash::new([2u8; 32])]
+    );
+    assert!(genesis.state.ticket_state.sealed_by_tickets);
+    assert_eq!(genesis.state_root, genesis.state.state_root());
+
+    let kvs = gp_key_values(&spec);
+    assert!(Genesis::from_json(&document(&genesis_header, &kvs, [9u8; 32]), &spec).is_err());
+}
@@ -1,15 +1,20 @@
-use jamliquor::Importer;
+use ed25519_dalek::Signer;
+use jamliquor::authorer::{Authorer, SealKind, ENTROPY_CONTEXT, FALLBACK_SEAL_CONTEXT};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::codec::Encode;
+use jamliquor::schema::{Block, BlockchainError, OpaqueHash, Preimage, TicketEnvelope};
+use jamliquor::{Codec, Importer};
+use std::io::Cursor;
 use std::path::PathBuf;
 
-#[test]
-fn test_importer_initialization() {}
+use crate::common::{block, child, importer, seal, sealed, signing_keys, validator_state};
 
 #[test]
-fn test_set_initial_state() {}
+fn test_importer_initialization() {}
 
 #[test]
 fn test_import_block_invalid_path() {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let invalid_path = PathBuf::from("nonexistent_file.json");
     let result = importer.import_block(&invalid_path);
     assert!(result.is_err(), "Import should fail for nonexistent file");
@@ -17,8 +22,207 @@ fn test_import_block_invalid_path() {
 
 #[test]
 fn test_import_block_invalid_json() {
-    let mut importer = Importer::new();
+    let mut importer = importer();
     let invalid_json_path = PathBuf::from("tests/vectors/codec/data/invalid_block.json");
     let result = importer.import_block(&invalid_json_path);
     assert!(result.is_err(), "Import should fail for invalid JSON");
 }
+
+fn build_importable_block() -> Block {
+    let mut block = block(OpaqueHash::default(), 43, 0);
+    block.extrinsic.preimages = vec![Preimage {
+        requester: 1,
+        blob: vec![1, 2, 3],
+    }];
+    seal(&validator_state(), block)
+}
+
+#[test]
+fn test_import_block_bytes_with_both_codecs() {
+    let block = build_importable_block();
+
+    for codec in [Codec::Json, Codec::Binary] {
+        let mut importer = importer();
+        let bytes = codec.encode_block(&block).unwrap();
+        let imported = importer
+            .import_block_bytes(&bytes, codec)
+            .expect("block should import from memory");
+        assert_eq!(imported, block);
+        assert_eq!(importer.state().get_last_slot(), 43);
+    }
+}
+
+#[test]
+fn test_import_block_reader() {
+    let block = build_importable_block();
+    let bytes = Codec::Binary.encode_block(&block).unwrap();
+
+    let mut importer = importer();
+    importer
+        .import_block_reader(Cursor::new(bytes), Codec::Binary)
+        .expect("block should import from reader");
+    assert_eq!(importer.state().get_counter(), 1);
+}
+
+#[test]
+fn test_import_decoded() {
+    let mut importer = importer();
+    let imported = importer
+        .import_decoded(build_importable_block())
+        .expect("decoded block should import");
+    assert_eq!(imported.header.slot, 43);
+}
+
+#[test]
+fn test_import_block_bytes_rejects_wrong_codec() {
+    let block = build_importable_block();
+    let bytes = Codec::Binary.encode_block(&block).unwrap();
+
+    let mut importer = importer();
+    assert!(importer.import_block_bytes(&bytes, Codec::Json).is_err());
+    assert_eq!(importer.state().get_last_slot(), 0);
+}
+
+#[test]
+fn test_failed_import_leaves_importer_unchanged() {
+    let mut importer = importer();
+    let mut first = build_importable_block();
+    first.extrinsic.guarantees = vec![serde_json::json!({
+        "slot": 43,
+        "report": { "core_index": 0, "results": [{ "accumulate_gas": 10 }] }
+    })];
+    importer
+        .import_decoded(first)
+        .expect("first block should import");
+    let before = importer.tree().clone();
+
+    // Guarantees are valid, so the CoreTime ledger is written before the stale
+    // dispute verdict rejects the block.
+    let mut stale_dispute = build_importable_block();
+    stale_dispute.header.slot = 44;
+    stale_dispute.extrinsic.guarantees = vec![serde_json::json!({
+        "slot": 44,
+        "report": { "core_index": 1, "results": [{ "accumulate_gas": 20 }] }
+    })];
+    stale_dispute.extrinsic.disputes = serde_json::json!({
+        "verdicts": [{ "target": "0x00", "age": 1_000, "votes": [] }]
+    });
+    assert!(importer
+        .import_decoded(sealed(&importer, stale_dispute))
+        .is_err());
+    assert_eq!(
+        importer.tree(),
+        &before,
+        "rejected block must not alter the importer"
+    );
+
+    let mut bad_ticket = build_importable_block();
+    bad_ticket.header.slot = 45;
+    bad_ticket.extrinsic.tickets = vec![TicketEnvelope {
+        attempt: 0,
+        signature: vec![0u8; 8],
+    }];
+    assert!(importer
+        .import_decoded(sealed(&importer, bad_ticket))
+        .is_err());
+    assert_eq!(
+        importer.tree(),
+        &before,
+        "rejected block must not alter the importer"
+    );
+
+    assert_eq!(importer.coretime().total_consumed(), 10);
+    assert_eq!(importer.state().get_last_slot(), 43);
+}
+
+#[test]
+fn test_check_block_reports_without_importing() {
+    let mut importer = importer();
+    let first = importer
+        .import_decoded(build_importable_block())
+        .expect("first block should import");
+    let before = importer.tree().clone();
+
+    let mut child = build_importable_block();
+    child.header.parent = first.header.hash();
+    child.header.parent_state_root = OpaqueHash::new(importer.last_state_root().unwrap());
+    child.header.slot = 44;
+    child.extrinsic.guarantees = vec![serde_json::json!({
+        "slot": 44,
+        "report": {
+            "core_index": 0,
+            "auth_gas_used": 5,
+            "results": [{ "accumulate_gas": 20 }, { "accumulate_gas": 30 }]
+        }
+    })];
+    let child = sealed(&importer, child);
+
+    let report = importer.check_block(&child);
+    assert!(report.is_valid(), "{:?}", report.error);
+    assert_eq!(report.gas_used, 50);
+    assert_eq!(report.coretime_consumed, 55);
+    assert_eq!(report.phases.phases().len(), 6);
+    assert_eq!(importer.tree(), &before, "check_block must not import");
+
+    importer
+        .import_decoded(child)
+        .expect("checked block should import");
+    assert_eq!(report.state_root, importer.last_state_root());
+}
+
+#[test]
+fn test_check_block_reports_rejection() {
+    let mut importer = importer();
+    let block = importer
+        .import_decoded(build_importable_block())
+        .expect("block should import");
+
+    let report = importer.check_block(&block);
+    assert!(!report.is_valid());
+    assert!(report.state_root.is_none());
+    assert!(format!("{:#}", report.error.unwrap()).contains("already imported"));
+}
+
+#[test]
+fn test_import_rejects_foreign_or_chosen_seals() {
+    let mut importer = importer();
+    let block = child(&importer, OpaqueHash::default(), 1);
+    let author = usize::from(block.header.author_index);
+    let keys = signing_keys(6);
+    let rejection = |importer: &mut Importer, block: &Block| {
+        let err = importer.import_decoded(block.clone()).unwrap_err();
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<BlockchainError>())
+            .map(ToString::to_string)
+            .unwrap()
+    };
+
+    // Sealed by another validator in the author's name.
+    let other = Authorer::new(keys[(author + 1) % keys.len()].clone(), ChainSpec::tiny());
+    let mut forged = block.clone();
+    forged.header = other.seal(forged.header, &OpaqueHash::default(), &SealKind::Fallback);
+    assert!(rejection(&mut importer, &forged).contains("seal"));
+
+    // The author's own signatures over a seal output of its choosing.
+    let key = &keys[author];
+    let chosen = [0x42u8; 32];
+    let mut unsealed = block.header.clone();
+    unsealed.seal = Vec::new();
+    let input = [FALLBACK_SEAL_CONTEXT, &[0u8; 32][..]].concat();
+    let mut seal = key
+        .sign(&[&input[..], &unsealed.encode()].concat())
+        .to_bytes()
+        .to_vec();
+    seal.extend_from_slice(&chosen);
+    let mut entropy_source = key
+        .sign(&[ENTROPY_CONTEXT, &chosen[..]].concat())
+        .to_bytes()
+        .to_vec();
+    entropy_source.extend_from_slice(&block.header.entropy_source[64..]);
+    let mut chosen_output = block.clone();
+    chosen_output.header.seal = seal;
+    chosen_output.header.entropy_source = entropy_source;
+    assert!(rejection(&mut importer, &chosen_output).contains("VRF output"));
+
+    importer.import_decoded(block).unwrap();
+}
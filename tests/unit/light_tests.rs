@@ -0,0 +1,204 @@
+use jamliquor::authorer::{fallback_index, Authorer, EpochSealing, SealKind};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::error_code::ErrorCode;
+use jamliquor::light::LightClient;
+use jamliquor::merkle::{merklize, KeyValues, StateKey};
+use jamliquor::proof::StateProof;
+use jamliquor::schema::{BlockchainError, EpochMark, Header, OpaqueHash, TicketBody};
+
+use crate::common::{header, signing_keys_from};
+
+fn authorers(seed: u8) -> Vec<Authorer> {
+    signing_keys_from(seed, 6)
+        .into_iter()
+        .map(|key| Authorer::new(key, ChainSpec::tiny()))
+        .collect()
+}
+
+fn keys(authorers: &[Authorer]) -> Vec<OpaqueHash> {
+    authorers.iter().map(|a| *a.public_key()).collect()
+}
+
+/// `header` sealed by the fallback key of its slot among `authorers`.
+fn sealed(mut header: Header, authorers: &[Authorer], entropy: &OpaqueHash) -> Header {
+    let phase = header.slot % ChainSpec::tiny().epoch_length;
+    let author = fallback_index(entropy, phase, authorers.len());
+    header.author_index = author as u16;
+    authorers[author].seal(header, entropy, &SealKind::Fallback)
+}
+
+#[test]
+fn follows_headers_across_an_epoch_hand_over() {
+    let (first, second, third) = (authorers(10), authorers(20), authorers(30));
+    let entropy = OpaqueHash::new([7; 32]);
+    let anchor = OpaqueHash::new([1; 32]);
+    let mut client = LightClient::new(ChainSpec::tiny(), anchor, 0, keys(&first), keys(&second))
+        .with_sealing(EpochSealing {
+            entropy,
+            tickets: Vec::new(),
+        })
+        .with_retention(5);
+
+    let mut head = anchor;
+    for slot in 1..=11 {
+        head = client
+            .import_header(sealed(header(head, slot, 0), &first, &entropy))
+            .unwrap();
+    }
+    assert_eq!(client.validators(), keys(&first));
+
+    let mut bad = header(head, 12, 0);
+    bad.author_index = 6;
+    assert!(matches!(
+        client.import_header(bad),
+        Err(BlockchainError::InvalidAuthorIndex { .. })
+    ));
+    assert!(matches!(
+        client.import_header(sealed(header(anchor, 12, 0), &first, &entropy)),
+        Err(BlockchainError::ParentHashMismatch { .. })
+    ));
+
+    let mut first_of_epoch = header(head, 12, 0);
+    let next_entropy = OpaqueHash::new([8; 32]);
+    first_of_epoch.epoch_mark = Some(EpochMark {
+        entropy: next_entropy,
+        tickets_entropy: next_entropy,
+        validators: keys(&third),
+    });
+    // Only the announced entropy and the incoming set seal the first header of the epoch.
+    let stale = sealed(first_of_epoch.clone(), &first, &next_entropy);
+    assert!(matches!(
+        client.import_header(stale),
+        Err(BlockchainError::InvalidSignature { .. })
+    ));
+    let unsealed = Header {
+        author_index: sealed(first_of_epoch.clone(), &second, &next_entropy).author_index,
+        ..first_of_epoch.clone()
+    };
+    assert!(matches!(
+        client.import_header(unsealed),
+        Err(BlockchainError::InvalidSignature { .. })
+    ));
+    let mut usurped = sealed(first_of_epoch.clone(), &second, &next_entropy);
+    usurped.author_index = (usurped.author_index + 1) % 6;
+    let usurped =
+        second[usize::from(usurped.author_index)].seal(usurped, &next_entropy, &SealKind::Fallback);
+    let err = client.import_header(usurped).unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::UnexpectedAuthor));
+    assert_eq!(client.validators(), keys(&first));
+
+    head = client
+        .import_header(sealed(first_of_epoch.clone(), &second, &next_entropy))
+        .unwrap();
+    assert_eq!(client.validators(), keys(&second));
+    assert_eq!(client.pending_validators(), keys(&third));
+
+    let mut repeated = first_of_epoch;
+    repeated.parent = head;
+    repeated.slot = 13;
+    assert!(matches!(
+        client.import_header(sealed(repeated, &second, &next_entropy)),
+        Err(BlockchainError::InvalidBlockStructure { .. })
+    ));
+    assert!(matches!(
+        client.import_header(sealed(header(head, 12, 0), &second, &next_entropy)),
+        Err(BlockchainError::InvalidSlot { .. })
+    ));
+
+    assert_eq!(client.head(), head);
+    assert_eq!(client.head_slot(), 12);
+    assert_eq!(client.retained(), 5);
+    assert!(client.header(&head).is_some());
+}
+
+#[test]
+fn ticket_sealed_epochs_follow_the_announced_tickets() {
+    let (first, second) = (authorers(10), authorers(20));
+    let entropy = OpaqueHash::default();
+    let anchor = OpaqueHash::new([1; 32]);
+    let mut client = LightClient::new(ChainSpec::tiny(), anchor, 0, keys(&first), keys(&second));
+
+    let tickets: Vec<_> = (0..12u8)
+        .map(|i| TicketBody {
+            id: OpaqueHash::new([i; 32]),
+            attempt: i % 2,
+        })
+        .collect();
+    let mut announcing = header(anchor, 1, 0);
+    announcing.tickets_mark = Some(tickets.clone());
+    let head = client
+        .import_header(sealed(announcing, &first, &entropy))
+        .unwrap();
+
+    let next_entropy = OpaqueHash::new([8; 32]);
+    let mut first_of_epoch = header(head, 13, 2);
+    first_of_epoch.epoch_mark = Some(EpochMark {
+        entropy: next_entropy,
+        tickets_entropy: next_entropy,
+        validators: keys(&first),
+    });
+    // Slot 13 is phase 1 of epoch 1: any holder of the second ticket may seal it.
+    let fallback = sealed(first_of_epoch.clone(), &second, &next_entropy);
+    assert!(matches!(
+        client.import_header(fallback),
+        Err(BlockchainError::InvalidSignature { .. })
+    ));
+    let wrong_ticket = second[2].seal(
+        first_of_epoch.clone(),
+        &next_entropy,
+        &SealKind::Ticket(tickets[0].clone()),
+    );
+    assert!(client.import_header(wrong_ticket).is_err());
+    let ticket_sealed = second[2].seal(
+        first_of_epoch,
+        &next_entropy,
+        &SealKind::Ticket(tickets[1].clone()),
+    );
+    client.import_header(ticket_sealed).unwrap();
+    assert_eq!(client.validators(), keys(&second));
+}
+
+#[test]
+fn verifies_state_values_against_a_parent_state_root() {
+    let key = |byte: u8| -> StateKey { [byte; 31] };
+    let mut kvs = KeyValues::new();
+    kvs.insert(key(0x10), vec![1, 2, 3]);
+    kvs.insert(key(0x80), vec![7; 100]);
+    kvs.insert(key(0x90), vec![4]);
+
+    let anchor = OpaqueHash::new([1; 32]);
+    let validators = authorers(10);
+    let mut client = LightClient::new(ChainSpec::tiny(), anchor, 0, keys(&validators), Vec::new());
+    let mut next = header(anchor, 1, 0);
+    next.parent_state_root = OpaqueHash::new(merklize(&kvs));
+    // Without a validator set no header can be authenticated.
+    let mut unanchored = LightClient::new(ChainSpec::tiny(), anchor, 0, Vec::new(), Vec::new());
+    assert!(matches!(
+        unanchored.import_header(next.clone()),
+        Err(BlockchainError::InvalidAuthorIndex { .. })
+    ));
+    let hash = client
+        .import_header(sealed(next, &validators, &OpaqueHash::default()))
+        .unwrap();
+
+    for present in [key(0x10), key(0x80)] {
+        let proof = StateProof::generate(&kvs, &[present]);
+        client
+            .verify_state(&hash, &present, Some(&kvs[&present]), &proof)
+            .unwrap();
+        assert!(client
+            .verify_state(&hash, &present, Some(&[9]), &proof)
+            .is_err());
+    }
+
+    let absent = key(0x81);
+    let proof = StateProof::generate(&kvs, &[absent]);
+    client.verify_state(&hash, &absent, None, &proof).unwrap();
+    let truncated = StateProof {
+        nodes: proof.nodes[..1].to_vec(),
+    };
+    assert!(matches!(
+        client.verify_state(&hash, &absent, None, &truncated),
+        Err(BlockchainError::InvalidStateProof { .. })
+    ));
+}
@@ -0,0 +1,63 @@
+use jamliquor::merkle::{
+    collect, encode_leaf, merklize, merklize_with_nodes, KeyValues, Node, EMPTY_ROOT,
+};
+use jamliquor::schema::{blake2b_256, OpaqueHash, State};
+use std::collections::HashMap;
+
+fn key(first: u8, last: u8) -> [u8; 31] {
+    let mut key = [0u8; 31];
+    key[0] = first;
+    key[30] = last;
+    key
+}
+
+#[test]
+fn empty_and_single_leaf_roots() {
+    assert_eq!(merklize(&KeyValues::new()), EMPTY_ROOT);
+
+    let mut kvs = KeyValues::new();
+    kvs.insert(key(1, 0), vec![7u8; 4]);
+    assert_eq!(merklize(&kvs), blake2b_256(&encode_leaf(&key(1, 0), &[7u8; 4])));
+
+    match Node::decode(&encode_leaf(&key(1, 0), &[7u8; 40])).unwrap() {
+        Node::Regular { value_hash, .. } => assert_eq!(value_hash, blake2b_256(&[7u8; 40])),
+        other => panic!("expected a regular leaf, got {other:?}"),
+    }
+}
+
+#[test]
+fn nodes_rebuild_the_key_values() {
+    let mut kvs = KeyValues::new();
+    kvs.insert(key(0x00, 1), vec![1u8; 3]);
+    kvs.insert(key(0x00, 2), vec![2u8; 64]);
+    kvs.insert(key(0x80, 0), Vec::new());
+    kvs.insert(key(0xff, 9), vec![3u8; 32]);
+
+    let mut nodes = HashMap::new();
+    let root = merklize_with_nodes(&kvs, &mut |hash, bytes| {
+        nodes.insert(hash, bytes.to_vec());
+    });
+    assert_eq!(root, merklize(&kvs));
+
+    let rebuilt = collect(&root, &|hash| Ok(nodes.get(hash).cloned())).unwrap();
+    assert_eq!(rebuilt, kvs);
+
+    nodes.remove(&root);
+    assert!(collect(&root, &|hash| Ok(nodes.get(hash).cloned())).is_err());
+}
+
+#[test]
+fn state_root_tracks_every_component() {
+    let mut state = State::new();
+    state.last_block_hash = Some([4u8; 32]);
+    state.active_validators = vec![OpaqueHash::new([1u8; 32]); 3];
+    let root = state.state_root();
+
+    let restored = State::from_key_values(&state.to_key_values()).unwrap();
+    assert_eq!(restored.last_state_root, Some(root));
+    assert_eq!(restored.active_validators, state.active_validators);
+    assert_eq!(restored.last_block_hash, state.last_block_hash);
+
+    state.pending_validators.push(OpaqueHash::new([2u8; 32]));
+    assert_ne!(state.state_root(), root);
+}
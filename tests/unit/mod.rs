@@ -1,5 +1,29 @@
+mod audit_tests;
+mod authorer_tests;
+mod availability_tests;
+mod block_tree_tests;
+mod chain_import_tests;
+mod codec_tests;
+mod conformance_tests;
 mod coretime_tests;
+mod erasure_tests;
+mod error_code_tests;
+mod events_tests;
+mod finality_tests;
+mod genesis_tests;
 mod importer_tests;
+mod light_tests;
+mod merkle_tests;
+mod proof_tests;
+mod refine_tests;
+mod snapshot_tests;
+mod state_diff_tests;
+mod stf_tests;
+mod store_tests;
+mod trace_tests;
+mod vectors_tests;
+mod verification_tests;
+mod work_package_tests;
 
 #[test]
 fn test_project_setup() {}
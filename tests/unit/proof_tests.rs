@@ -0,0 +1,83 @@
+use jamliquor::codec::{decode_exact, Encode};
+use jamliquor::merkle::{encode_branch, merklize, KeyValues};
+use jamliquor::proof::{verify_proof, StateProof};
+use jamliquor::schema::{blake2b_256, BlockchainError, OpaqueHash, State};
+use jamliquor::state_keys::{
+    component_key, describe_key, lookup_key, preimage_key, storage_key, ACTIVE_VALIDATORS, TIMESLOT,
+};
+
+#[test]
+fn state_proofs_cover_present_and_absent_components() {
+    let mut state = State::new();
+    state.active_validators = (0..6).map(|i| OpaqueHash::new([i; 32])).collect();
+    let root = state.state_root();
+    let kvs = state.to_key_values();
+
+    let absent = component_key(1);
+    let keys = [
+        component_key(TIMESLOT),
+        component_key(ACTIVE_VALIDATORS),
+        absent,
+    ];
+    let proof = decode_exact::<StateProof>(&state.prove(&keys).encode()).unwrap();
+
+    for key in &keys[..2] {
+        verify_proof(&root, &proof, key, Some(&kvs[key])).unwrap();
+        assert!(verify_proof(&root, &proof, key, None).is_err());
+    }
+    verify_proof(&root, &proof, &absent, None).unwrap();
+    assert!(verify_proof(&root, &proof, &absent, Some(&[0])).is_err());
+
+    // A proof for one key does not cover the others, nor another root.
+    let single = state.prove(&[component_key(TIMESLOT)]);
+    assert!(single.len() < proof.len());
+    assert!(matches!(
+        verify_proof(&root, &single, &keys[1], Some(&kvs[&keys[1]])),
+        Err(BlockchainError::InvalidStateProof { .. })
+    ));
+    assert!(verify_proof(&[1; 32], &proof, &keys[0], Some(&kvs[&keys[0]])).is_err());
+}
+
+#[test]
+fn service_storage_preimage_and_lookup_items_are_provable() {
+    let hash = OpaqueHash::new([0xab; 32]);
+    let storage = storage_key(7, b"balance");
+    let preimage = preimage_key(7, &hash);
+    let lookup = lookup_key(7, &hash, 100);
+    assert!(describe_key(&storage).starts_with("service 7 "));
+
+    let mut kvs = KeyValues::new();
+    kvs.insert(storage, vec![1; 8]);
+    kvs.insert(preimage, vec![2; 100]);
+    kvs.insert(lookup, vec![1, 0, 0, 0, 0]);
+    for i in 0..20u8 {
+        kvs.insert(storage_key(i.into(), &[i]), vec![i]);
+    }
+    let root = merklize(&kvs);
+
+    let missing = storage_key(7, b"nonce");
+    let proof = StateProof::generate(&kvs, &[storage, preimage, lookup, missing]);
+    for key in [storage, preimage, lookup] {
+        verify_proof(&root, &proof, &key, Some(&kvs[&key])).unwrap();
+    }
+    verify_proof(&root, &proof, &missing, None).unwrap();
+    assert!(verify_proof(&root, &proof, &preimage, Some(&[2; 99])).is_err());
+}
+
+#[test]
+fn proofs_branching_deeper_than_the_key_are_rejected() {
+    // 249 branches along the all-zero key: one more than the key has bits.
+    let key = [0u8; 31];
+    let mut nodes = Vec::new();
+    let mut child = [1u8; 32];
+    for _ in 0..249 {
+        let node = encode_branch(&child, &[2u8; 32]);
+        child = blake2b_256(&node);
+        nodes.push(node);
+    }
+    let proof = StateProof { nodes };
+    assert!(matches!(
+        verify_proof(&child, &proof, &key, None),
+        Err(BlockchainError::InvalidStateProof { .. })
+    ));
+}
@@ -0,0 +1,106 @@
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::erasure::{segment_justification, segments_root, ErasureCoder, SEGMENT_SIZE};
+use jamliquor::refine::{refine, Invocation, InvocationResult};
+use jamliquor::schema::{BlockchainError, OpaqueHash};
+use jamliquor::stf;
+use jamliquor::work_package::{ImportSpec, WorkError, WorkPackageBundle};
+
+use super::work_package_tests::bundle;
+use crate::common::{block, seal, service, validator_state};
+
+#[test]
+fn refined_reports_are_accepted_by_the_reports_stf() {
+    let spec = ChainSpec::tiny();
+    let coder = ErasureCoder::for_spec(&spec).unwrap();
+    let bundle = bundle(b"signed transfer");
+
+    let refined = refine(&mut service(b"", 2), &coder, &bundle, 1).unwrap();
+    let report = &refined.report;
+    assert_eq!(refined.exports.len(), 2);
+    assert!(refined.exports.iter().all(|s| s.len() == SEGMENT_SIZE));
+    assert_eq!(refined.chunks.len(), 6);
+    assert_eq!(report.package_spec.hash, bundle.package.hash());
+    assert_eq!(
+        report.package_spec.exports_root,
+        OpaqueHash::new(segments_root(&refined.exports))
+    );
+    assert_eq!(
+        report.package_spec.erasure_root,
+        OpaqueHash::new(segments_root(&refined.chunks))
+    );
+    assert_eq!(report.results[0].result, Ok(b"signed transfer".to_vec()));
+    assert_eq!(report.results[0].refine_gas_used, 400);
+    assert_eq!(report.auth_gas_used, 10);
+
+    let prior = validator_state();
+    let mut block = block(OpaqueHash::default(), 43, 0);
+    block.extrinsic.guarantees = vec![report.guarantee(42)];
+    let (_, outputs) = stf::apply(&prior, &seal(&prior, block), &spec).unwrap();
+    assert_eq!(outputs.reported.len(), 1);
+    assert_eq!(
+        outputs.reported[0].work_package_hash,
+        report.package_spec.hash
+    );
+    assert_eq!(
+        outputs.reported[0].exports_root,
+        report.package_spec.exports_root
+    );
+    assert_eq!(outputs.reported[0].core_index, 1);
+    assert_eq!(outputs.gas_used, 100);
+}
+
+#[test]
+fn bad_exports_and_unauthorized_packages() {
+    let coder = ErasureCoder::for_spec(&ChainSpec::tiny()).unwrap();
+    let bundle = bundle(b"signed transfer");
+
+    // One export fewer than declared: the item fails but still exports zero segments.
+    let refined = refine(&mut service(b"", 1), &coder, &bundle, 0).unwrap();
+    assert_eq!(refined.report.results[0].result, Err(WorkError::BadExports));
+    assert_eq!(refined.exports, vec![vec![0; SEGMENT_SIZE]; 2]);
+
+    let mut unauthorized = |_: &Invocation| InvocationResult {
+        output: Err(WorkError::Panic),
+        gas_used: 0,
+        exports: Vec::new(),
+    };
+    assert!(matches!(
+        refine(&mut unauthorized, &coder, &bundle, 0),
+        Err(BlockchainError::InvalidWorkPackage { .. })
+    ));
+
+    let mut tampered = bundle.clone();
+    tampered.extrinsics[0].push(0);
+    assert!(matches!(
+        refine(&mut service(b"", 2), &coder, &tampered, 0),
+        Err(BlockchainError::InvalidWorkPackage { .. })
+    ));
+
+    // Imports must verify under the segments root they name.
+    let exports = refine(&mut service(b"", 2), &coder, &bundle, 0)
+        .unwrap()
+        .exports;
+    let mut importing = bundle.clone();
+    importing.package.items[0].import_segments = vec![ImportSpec {
+        tree_root: OpaqueHash::new(segments_root(&exports)),
+        index: 1,
+    }];
+    importing.imports = vec![exports[1].clone()];
+    importing.justifications = vec![segment_justification(&exports, 1)
+        .into_iter()
+        .map(OpaqueHash::new)
+        .collect()];
+    refine(&mut service(b"", 2), &coder, &importing, 0).unwrap();
+    for tamper in [
+        |b: &mut WorkPackageBundle| b.imports[0][0] ^= 1,
+        |b: &mut WorkPackageBundle| b.package.items[0].import_segments[0].index = 0,
+        |b: &mut WorkPackageBundle| b.justifications[0].clear(),
+    ] {
+        let mut forged = importing.clone();
+        tamper(&mut forged);
+        assert!(matches!(
+            refine(&mut service(b"", 2), &coder, &forged, 0),
+            Err(BlockchainError::InvalidWorkPackage { .. })
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::schema::OpaqueHash;
+use jamliquor::snapshot::{Snapshot, SnapshotAnchor};
+use jamliquor::store::{FileStore, MemoryStore};
+use jamliquor::Importer;
+
+use crate::common::{child, genesis, importer};
+
+#[test]
+fn exported_snapshot_bootstraps_an_empty_store() {
+    let source = tempfile::tempdir().unwrap();
+    let genesis = genesis();
+    let mut importer =
+        Importer::open_with_genesis(ChainSpec::tiny(), source.path(), &genesis).unwrap();
+    let a = child(&importer, genesis.hash(), 1);
+    let a_hash = a.header.hash();
+    importer.import_decoded(a).unwrap();
+    let b = child(&importer, a_hash, 2);
+    let b_hash = b.header.hash();
+    importer.import_decoded(b).unwrap();
+    let anchor = SnapshotAnchor::Child(Box::new(child(&importer, b_hash, 3).header));
+    drop(importer);
+
+    let file = source.path().join("head.snap");
+    let store = FileStore::open(source.path()).unwrap();
+    Snapshot::export(&store, &b_hash)
+        .unwrap()
+        .save(&file)
+        .unwrap();
+    let snapshot = Snapshot::load(&file).unwrap();
+    assert_eq!(snapshot.hash(), b_hash);
+    assert_eq!(snapshot.verify(&anchor).unwrap().get_last_slot(), 2);
+
+    let target = tempfile::tempdir().unwrap();
+    let mut store = FileStore::open(target.path()).unwrap();
+    snapshot.import_into(&mut store, &anchor).unwrap();
+    assert!(
+        snapshot.import_into(&mut store, &anchor).is_err(),
+        "store is no longer empty"
+    );
+    drop(store);
+
+    let mut resumed = Importer::open(ChainSpec::tiny(), target.path()).unwrap();
+    assert_eq!(resumed.finalized_head(), b_hash);
+    let next = child(&resumed, b_hash, 3);
+    resumed.import_decoded(next).unwrap();
+}
+
+#[test]
+fn tampered_snapshots_are_rejected() {
+    let mut importer = importer();
+    let a = child(&importer, OpaqueHash::default(), 1);
+    let header = a.header.clone();
+    importer.import_decoded(a).unwrap();
+    let snapshot = Snapshot::new(header, importer.state());
+    let anchor = SnapshotAnchor::Finalized {
+        hash: snapshot.hash(),
+        state_root: importer.state().state_root(),
+    };
+    assert!(Importer::from_snapshot(ChainSpec::tiny(), &snapshot, &anchor).is_ok());
+
+    let mut wrong_value = snapshot.clone();
+    let (_, value) = wrong_value.key_values.iter_mut().next().unwrap();
+    value.push(0);
+    assert!(wrong_value.verify(&anchor).is_err());
+
+    let mut wrong_header = snapshot.clone();
+    wrong_header.header.seal = vec![1u8; 96];
+    assert!(wrong_header.verify(&anchor).is_err());
+    assert!(Importer::from_snapshot(ChainSpec::tiny(), &wrong_header, &anchor).is_err());
+    assert!(wrong_header
+        .import_into(&mut MemoryStore::new(), &anchor)
+        .is_err());
+
+    // A self-consistent snapshot of a different state fails against the anchored root.
+    let mut forged_state = importer.state().clone();
+    forged_state
+        .active_validators
+        .push(OpaqueHash::new([0xee; 32]));
+    let forged = Snapshot::new(snapshot.header.clone(), &forged_state);
+    assert!(forged.verify(&anchor).is_err());
+    let self_anchored = SnapshotAnchor::Finalized {
+        hash: forged.hash(),
+        state_root: forged.state_root,
+    };
+    assert!(forged.verify(&self_anchored).is_ok());
+}
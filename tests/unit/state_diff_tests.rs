@@ -0,0 +1,89 @@
+use jamliquor::merkle::KeyValues;
+use jamliquor::schema::{OpaqueHash, State};
+use jamliquor::state_diff::{diff_key_values, diff_states};
+use jamliquor::state_keys::{classify_key, component_key, storage_key, KeyKind, TIMESLOT};
+
+#[test]
+fn typed_states_diff_by_field() {
+    let mut expected = State::new();
+    expected.active_validators = (0..4).map(|i| OpaqueHash::new([i; 32])).collect();
+    let mut actual = expected.clone();
+    assert!(diff_states(&expected, &actual).is_empty());
+
+    actual.active_validators[2] = OpaqueHash::new([9; 32]);
+    actual.ticket_state.valid_tickets = 3;
+    actual.last_slot = 12;
+    let diff = diff_states(&expected, &actual);
+    assert_eq!(diff.len(), 3);
+
+    let validator = diff.field("active_validators[2]").unwrap();
+    assert_eq!(
+        validator.actual,
+        Some(format!("0x{}", hex::encode([9; 32])))
+    );
+    assert!(diff.field("active_validators[1]").is_none());
+    let tickets = diff.field("tickets.valid_tickets").unwrap();
+    assert_eq!(
+        (tickets.expected.as_deref(), tickets.actual.as_deref()),
+        (Some("0"), Some("3"))
+    );
+    assert_eq!(
+        diff.field("timeslot").unwrap().actual.as_deref(),
+        Some("12")
+    );
+    assert!(diff
+        .to_string()
+        .contains("C(8) kappa (active validators): differs"));
+
+    actual.active_validators.pop();
+    let diff = diff_states(&expected, &actual);
+    assert_eq!(
+        diff.field("active_validators.len")
+            .unwrap()
+            .actual
+            .as_deref(),
+        Some("3")
+    );
+    assert_eq!(diff.field("active_validators[3]").unwrap().actual, None);
+}
+
+#[test]
+fn raw_keys_decode_into_their_components() {
+    let storage = storage_key(7, b"balance");
+    assert!(matches!(
+        classify_key(&storage),
+        KeyKind::ServiceData { service: 7, .. }
+    ));
+    assert_eq!(
+        classify_key(&component_key(TIMESLOT)),
+        KeyKind::Component(TIMESLOT)
+    );
+
+    let mut account = [0u8; 31];
+    account[0] = 255;
+    account[1] = 5;
+    assert_eq!(classify_key(&account), KeyKind::ServiceAccount(5));
+
+    let info = |balance: u64| [&[0xcc; 32][..], &balance.to_le_bytes(), &[1, 2]].concat();
+    let mut expected = KeyValues::new();
+    expected.insert(account, info(100));
+    expected.insert(storage, vec![1, 2, 3]);
+    let mut actual = expected.clone();
+    actual.insert(account, info(250));
+    actual.insert(storage, vec![1, 2, 4, 5]);
+    actual.insert(component_key(1), vec![0]);
+
+    let diff = diff_key_values(&expected, &actual);
+    assert_eq!(diff.len(), 3);
+    let balance = diff.field("balance").unwrap();
+    assert_eq!(
+        (balance.expected.as_deref(), balance.actual.as_deref()),
+        (Some("100"), Some("250"))
+    );
+    assert!(diff.field("code_hash").is_none());
+    let bytes = diff.field("bytes[2..]").unwrap();
+    assert_eq!(bytes.actual.as_deref(), Some("0x0405"));
+    assert!(diff
+        .to_string()
+        .contains("C(1) alpha (authorizer pools): only in actual"));
+}
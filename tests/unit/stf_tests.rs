@@ -0,0 +1,156 @@
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::error_code::{error_code, ErrorCode};
+use jamliquor::schema::{Block, OpaqueHash, State, TicketBody, TicketEnvelope};
+use jamliquor::stf;
+use serde_json::json;
+
+use crate::common::{block, importer, seal, validator_state};
+
+fn build_block(slot: u32) -> Block {
+    let mut block = block(OpaqueHash::default(), slot, 1);
+    block.header.extrinsic_hash = OpaqueHash::new([slot as u8; 32]);
+    block.header.offenders_mark = vec![OpaqueHash::new([9u8; 32])];
+    block.extrinsic.guarantees = vec![json!({
+        "slot": slot,
+        "report": {
+            "core_index": 1,
+            "package_spec": {
+                "hash": format!("0x{}", "11".repeat(32)),
+                "exports_root": format!("0x{}", "22".repeat(32)),
+            },
+            "results": [{ "accumulate_gas": 5 }]
+        }
+    })];
+    block
+}
+
+#[test]
+fn apply_returns_posterior_without_touching_prior() {
+    let prior = validator_state();
+    let block = seal(&prior, build_block(43));
+
+    let (posterior, outputs) = stf::apply(&prior, &block, &ChainSpec::tiny()).unwrap();
+
+    assert_eq!(prior, validator_state(), "prior state must be left untouched");
+    assert_eq!(posterior.get_last_slot(), 43);
+    assert_eq!(posterior.coretime.total_consumed(), 5);
+    assert_eq!(
+        posterior.last_block_hash,
+        Some(*block.header.hash().as_bytes())
+    );
+
+    assert_eq!(outputs.offenders, vec![OpaqueHash::new([9u8; 32])]);
+    assert_eq!(outputs.reported.len(), 1);
+    assert_eq!(
+        outputs.reported[0].work_package_hash,
+        OpaqueHash::new([0x11; 32])
+    );
+    assert_eq!(
+        outputs.reported[0].exports_root,
+        OpaqueHash::new([0x22; 32])
+    );
+    assert_eq!(outputs.reported[0].core_index, 1);
+    assert_eq!(outputs.accumulation_root, OpaqueHash::default());
+    assert_eq!(outputs.coretime_consumed, 5);
+}
+
+#[test]
+fn apply_matches_importer() {
+    let prior = validator_state();
+    let block = seal(&prior, build_block(43));
+    let (posterior, _) = stf::apply(&prior, &block, &ChainSpec::tiny()).unwrap();
+
+    let mut importer = importer();
+    importer.import_decoded(block).unwrap();
+
+    assert_eq!(importer.state(), &posterior);
+}
+
+#[test]
+fn apply_enforces_chain_spec_limits() {
+    let spec = ChainSpec::tiny();
+    let prior = validator_state();
+
+    let mut block = seal(&prior, build_block(43));
+    block.header.author_index = spec.validators_count;
+    assert!(stf::apply(&prior, &block, &spec).is_err());
+
+    // Sealed in the first epoch, which both specs draw the same fallback key for.
+    let mut block = build_block(7);
+    block.extrinsic.guarantees[0]["report"]["core_index"] = json!(spec.cores_count);
+    let block = seal(&prior, block);
+    assert!(stf::apply(&prior, &block, &spec).is_err());
+    assert!(stf::apply(&prior, &block, &ChainSpec::full()).is_ok());
+}
+
+#[test]
+fn tickets_mark_seals_the_next_epoch_with_tickets() {
+    let spec = ChainSpec::tiny();
+
+    let prior = validator_state();
+    let mut marked = build_block(1);
+    marked.header.tickets_mark = Some(Vec::new());
+    let marked = seal(&prior, marked);
+    let (state, outputs) = stf::apply(&prior, &marked, &spec).unwrap();
+    assert!(!outputs.ticket_sealed);
+
+    let mut next_epoch = build_block(spec.epoch_length);
+    next_epoch.header.parent = marked.header.hash();
+    next_epoch.header.parent_state_root = OpaqueHash::new(state.last_state_root.unwrap());
+    let next_epoch = seal(&state, next_epoch);
+    let (state, outputs) = stf::apply(&state, &next_epoch, &spec).unwrap();
+    assert!(outputs.ticket_sealed);
+
+    let mut skipped = build_block(3 * spec.epoch_length);
+    skipped.header.parent = next_epoch.header.hash();
+    skipped.header.parent_state_root = OpaqueHash::new(state.last_state_root.unwrap());
+    let skipped = seal(&state, skipped);
+    let (_, outputs) = stf::apply(&state, &skipped, &spec).unwrap();
+    assert!(
+        !outputs.ticket_sealed,
+        "no tickets mark was issued for this epoch"
+    );
+}
+
+#[test]
+fn tickets_mark_in_outside_in_order_is_accepted() {
+    // Z(γ_a) interleaves the lowest and highest identifiers, so the mark is not sorted.
+    let mut block = build_block(43);
+    let mark: Vec<_> = [1u8, 9, 2, 5]
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| TicketBody {
+            id: OpaqueHash::new([id; 32]),
+            attempt: i as u8 % 2,
+        })
+        .collect();
+    block.extrinsic.tickets = mark
+        .iter()
+        .map(|body| TicketEnvelope {
+            attempt: body.attempt,
+            signature: vec![0u8; 64],
+        })
+        .collect();
+    block.header.tickets_mark = Some(mark);
+    let prior = validator_state();
+    stf::apply(&prior, &seal(&prior, block), &ChainSpec::tiny()).unwrap();
+}
+
+#[test]
+fn blocks_must_be_sealed_by_the_slots_author() {
+    let spec = ChainSpec::tiny();
+    let prior = validator_state();
+    assert!(stf::apply(&prior, &block(OpaqueHash::default(), 1, 0), &spec).is_err());
+    let sealed = seal(&prior, block(OpaqueHash::default(), 1, 0));
+    stf::apply(&prior, &sealed, &spec).unwrap();
+
+    let mut impostor = block(OpaqueHash::default(), 1, 0);
+    impostor.header.author_index = (sealed.header.author_index + 1) % spec.validators_count;
+    impostor.header.entropy_source = sealed.header.entropy_source.clone();
+    impostor.header.seal = sealed.header.seal.clone();
+    let err = stf::apply(&prior, &impostor, &spec).unwrap_err();
+    assert_eq!(error_code(&err), Some(ErrorCode::UnexpectedAuthor));
+
+    // Without a validator set nothing can be sealed.
+    assert!(stf::apply(&State::new(), &sealed, &spec).is_err());
+}
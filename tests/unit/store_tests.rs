@@ -0,0 +1,136 @@
+use std::fs;
+
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::schema::{OpaqueHash, State};
+use jamliquor::store::{self, FileStore, MemoryStore, Store};
+use jamliquor::{stf, Importer};
+
+use crate::common::{child, genesis, importer};
+
+#[test]
+fn memory_store_indexes_blocks_and_states() {
+    let importer = importer();
+    let mut store = MemoryStore::new();
+    let a = child(&importer, OpaqueHash::default(), 5);
+    let b = child(&importer, OpaqueHash::new([1; 32]), 5);
+    store.put_block(&a, [1u8; 32]).unwrap();
+    store.put_block(&b, [2u8; 32]).unwrap();
+    assert_eq!(store.block_hashes_at(5).unwrap().len(), 2);
+
+    store.delete_block(&a.header.hash()).unwrap();
+    assert_eq!(store.block_hashes().unwrap(), vec![b.header.hash()]);
+    assert!(store.get_block(&a.header.hash()).unwrap().is_none());
+
+    let mut state = State::new();
+    state.last_block_hash = Some([3u8; 32]);
+    let root = store::save_state(&mut store, &state).unwrap();
+    assert_eq!(root, state.state_root());
+    let loaded = store::load_state(&store, &root).unwrap();
+    assert_eq!(loaded.last_block_hash, state.last_block_hash);
+    assert_eq!(loaded.last_state_root, Some(root));
+}
+
+#[test]
+fn importer_reopens_data_dir_at_last_head() {
+    let dir = tempfile::tempdir().unwrap();
+    let genesis = genesis();
+    let mut importer =
+        Importer::open_with_genesis(ChainSpec::tiny(), dir.path(), &genesis).unwrap();
+
+    let a = child(&importer, genesis.hash(), 1);
+    let a_hash = a.header.hash();
+    importer.import_decoded(a).unwrap();
+    let b = child(&importer, a_hash, 2);
+    let b_hash = b.header.hash();
+    importer.import_decoded(b).unwrap();
+    let c = child(&importer, a_hash, 3);
+    let c_hash = c.header.hash();
+    importer.import_decoded(c).unwrap();
+    let best = importer.best_head();
+    drop(importer);
+
+    let mut reopened = Importer::open(ChainSpec::tiny(), dir.path()).unwrap();
+    assert_eq!(reopened.tree().len(), 4);
+    assert_eq!(reopened.best_head(), best);
+    assert_eq!(reopened.state().get_last_slot(), 3);
+
+    // Finalizing drops the pruned fork and its state from disk, but keeps the finalized
+    // chain's blocks.
+    let store = FileStore::open(dir.path()).unwrap();
+    let nodes = store.node_hashes().unwrap().len();
+    drop(store);
+    assert_eq!(reopened.finalize(b_hash, None).unwrap(), [c_hash]);
+    drop(reopened);
+    let store = FileStore::open(dir.path()).unwrap();
+    assert_eq!(store.block_hashes().unwrap(), [a_hash, b_hash]);
+    assert!(store.node_hashes().unwrap().len() < nodes);
+    let finalized = store::finalized(&store).unwrap().unwrap();
+    store::load_state(&store, &finalized.state_root).unwrap();
+    drop(store);
+    let mut resumed = Importer::open(ChainSpec::tiny(), dir.path()).unwrap();
+    assert_eq!(resumed.finalized_head(), b_hash);
+    assert_eq!(resumed.tree().len(), 1);
+    let next = child(&resumed, b_hash, 4);
+    resumed.import_decoded(next).unwrap();
+}
+
+#[test]
+fn crash_at_every_write_recovers_pre_or_post_state() {
+    let mut outcomes = (0, 0);
+    for crash_at in 0.. {
+        let dir = tempfile::tempdir().unwrap();
+        let mut importer =
+            Importer::open_with_genesis(ChainSpec::tiny(), dir.path(), &genesis()).unwrap();
+        let a = child(&importer, genesis().hash(), 1);
+        let a_hash = a.header.hash();
+        importer.import_decoded(a).unwrap();
+        let pre_state = importer.state().clone();
+        let b = child(&importer, a_hash, 2);
+        let b_hash = b.header.hash();
+        let (post_state, _) = stf::apply(&pre_state, &b, importer.spec()).unwrap();
+        drop(importer);
+
+        let store = FileStore::open(dir.path())
+            .unwrap()
+            .crash_at_write(crash_at);
+        let mut importer = Importer::with_store(ChainSpec::tiny(), Box::new(store)).unwrap();
+        let imported = importer.import_decoded(b).is_ok();
+        drop(importer);
+        let clean = imported
+            && fs::metadata(dir.path().join("wal.log")).unwrap().len() == 0
+            && store::best_head(&FileStore::open(dir.path()).unwrap()).unwrap() == Some(b_hash);
+
+        let recovered = Importer::open(ChainSpec::tiny(), dir.path()).unwrap();
+        if recovered.best_head() == b_hash {
+            assert_eq!(recovered.tree().len(), 3, "crash at write {crash_at}");
+            assert_eq!(recovered.state(), &post_state, "crash at write {crash_at}");
+            outcomes.1 += 1;
+        } else {
+            assert_eq!(recovered.best_head(), a_hash, "crash at write {crash_at}");
+            assert_eq!(recovered.tree().len(), 2, "crash at write {crash_at}");
+            assert_eq!(recovered.state(), &pre_state, "crash at write {crash_at}");
+            outcomes.0 += 1;
+        }
+
+        if clean {
+            break;
+        }
+        assert!(crash_at < 100, "import never completed without a crash");
+    }
+    assert!(outcomes.0 > 0 && outcomes.1 > 1, "{outcomes:?}");
+}
+
+#[test]
+fn batch_left_by_a_failed_apply_is_replayed_by_the_next_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    // Write 0 is the log append: the apply fails at the first data file.
+    let mut store = FileStore::open(dir.path()).unwrap().crash_at_write(1);
+    assert!(store.put_meta("first", b"1").is_err());
+    assert!(store.get_meta("first").unwrap().is_none());
+
+    store.restore_power();
+    store.put_meta("second", b"2").unwrap();
+    assert_eq!(store.get_meta("first").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(store.get_meta("second").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(fs::metadata(dir.path().join("wal.log")).unwrap().len(), 0);
+}
@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::coretime::CoreTimeLedger;
+use jamliquor::merkle::{merklize, KeyValues};
+use jamliquor::genesis::Genesis;
+use jamliquor::schema::OpaqueHash;
+use jamliquor::state_keys::{component_key, describe_key, TIMESLOT};
+use jamliquor::trace::{replay_trace, StepVerdict};
+use jamliquor::Importer;
+use serde_json::{json, Value};
+
+use super::genesis_tests::gp_key_values;
+use crate::common::{block, child, seal, validator_state};
+
+fn raw_state(root: [u8; 32], kvs: &KeyValues) -> Value {
+    let keyvals: Vec<Value> = kvs
+        .iter()
+        .map(|(key, value)| {
+            json!({ "key": format!("0x{}", hex::encode(key)), "value": format!("0x{}", hex::encode(value)) })
+        })
+        .collect();
+    json!({ "state_root": format!("0x{}", hex::encode(root)), "keyvals": keyvals })
+}
+
+/// Write a three-step trace produced by the importer itself.
+fn write_trace(dir: &Path) {
+    let mut state = validator_state();
+    state.coretime = CoreTimeLedger::starting_at(0);
+    let mut pre_kvs = state.to_key_values();
+    let mut pre_root = merklize(&pre_kvs);
+    let genesis_hash = OpaqueHash::new([7u8; 32]);
+    state.last_block_hash = Some(*genesis_hash.as_bytes());
+    state.last_state_root = Some(pre_root);
+
+    let mut importer = Importer::from_state(ChainSpec::tiny(), genesis_hash, None, state);
+    let mut parent = genesis_hash;
+    for slot in 1..=3 {
+        let block = child(&importer, parent, slot);
+        parent = block.header.hash();
+        importer.import_decoded(block.clone()).unwrap();
+        let post_kvs = importer.state().to_key_values();
+        let post_root = importer.state().last_state_root.unwrap();
+
+        let step = json!({
+            "pre_state": raw_state(pre_root, &pre_kvs),
+            "block": block,
+            "post_state": raw_state(post_root, &post_kvs),
+        });
+        let file = dir.join(format!("{slot:08}.json"));
+        std::fs::write(file, serde_json::to_vec(&step).unwrap()).unwrap();
+        (pre_root, pre_kvs) = (post_root, post_kvs);
+    }
+}
+
+#[test]
+fn importer_generated_trace_replays_cleanly() {
+    let dir = tempfile::tempdir().unwrap();
+    write_trace(dir.path());
+
+    let report = replay_trace(dir.path(), &ChainSpec::tiny()).unwrap();
+    assert_eq!(report.steps.len(), 3);
+    assert!(report.passed(), "{:?}", report.failure());
+}
+
+#[test]
+fn diverging_post_state_names_the_first_differing_component() {
+    let dir = tempfile::tempdir().unwrap();
+    write_trace(dir.path());
+
+    // Claim a different timeslot in the second step's posterior state.
+    let file = dir.path().join("00000002.json");
+    let mut step: Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+    let tau = format!("0x{}", hex::encode(component_key(TIMESLOT)));
+    for entry in step["post_state"]["keyvals"].as_array_mut().unwrap() {
+        if entry["key"] == tau {
+            entry["value"] = json!("0x63000000");
+        }
+    }
+    std::fs::write(&file, serde_json::to_vec(&step).unwrap()).unwrap();
+
+    let report = replay_trace(dir.path(), &ChainSpec::tiny()).unwrap();
+    assert_eq!(report.steps.len(), 2, "replay stops at the failing step");
+    let failure = report.failure().unwrap();
+    assert_eq!(failure.slot, 2);
+    assert!(failure.error.is_none());
+    let diff = failure.first_difference.as_ref().unwrap();
+    assert_eq!(diff.key, component_key(TIMESLOT));
+    assert_eq!(diff.component, describe_key(&component_key(TIMESLOT)));
+    assert!(diff.component.contains("tau"));
+    assert_eq!(diff.expected, Some(vec![0x63, 0, 0, 0]));
+    assert_eq!(diff.actual, Some(2u32.to_le_bytes().to_vec()));
+}
+
+#[test]
+fn gp_serialized_traces_compare_only_gp_encoded_components() {
+    let spec = ChainSpec::tiny();
+    let pre_kvs = gp_key_values(&spec);
+    let pre_root = merklize(&pre_kvs);
+    let mut next = block(OpaqueHash::new([7u8; 32]), 6, 0);
+    next.header.parent_state_root = OpaqueHash::new(pre_root);
+    let next = seal(&Genesis::decode_state(&pre_kvs, &spec).unwrap(), next);
+    let mut post_kvs = pre_kvs.clone();
+    post_kvs.insert(component_key(TIMESLOT), 6u32.to_le_bytes().to_vec());
+    let post_root = merklize(&post_kvs);
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("00000001.json");
+    let write = |post_kvs: &KeyValues| {
+        let step = json!({
+            "pre_state": raw_state(pre_root, &pre_kvs),
+            "block": next,
+            "post_state": raw_state(post_root, post_kvs),
+        });
+        std::fs::write(&file, serde_json::to_vec(&step).unwrap()).unwrap();
+    };
+
+    write(&post_kvs);
+    let report = replay_trace(dir.path(), &spec).unwrap();
+    assert!(report.failure().is_none(), "{:?}", report.failure());
+    assert!(!report.passed(), "unmodelled components are not verified");
+    let step = &report.steps[0];
+    assert_eq!(step.verdict(), StepVerdict::Partial);
+    assert_eq!(report.partial().count(), 1);
+    assert_ne!(step.expected_root, step.actual_root);
+    let unmodelled: Vec<_> = [1, 4, 8]
+        .map(|index| describe_key(&component_key(index)))
+        .into();
+    assert_eq!(step.unmodelled, unmodelled);
+
+    post_kvs.insert(component_key(TIMESLOT), 7u32.to_le_bytes().to_vec());
+    write(&post_kvs);
+    let report = replay_trace(dir.path(), &spec).unwrap();
+    let failure = report.failure().unwrap();
+    assert_eq!(failure.verdict(), StepVerdict::Failed);
+    let diff = failure.first_difference.as_ref().unwrap();
+    assert_eq!(diff.key, component_key(TIMESLOT));
+}
@@ -0,0 +1,62 @@
+use std::fs;
+
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::codec::Encode;
+use jamliquor::schema::{Block, OpaqueHash};
+use jamliquor::vectors::{run_category, run_codec_vectors};
+
+use crate::common::block;
+
+fn sample_block() -> Block {
+    let mut block = block(OpaqueHash::new([1u8; 32]), 7, 1);
+    block.header.parent_state_root = OpaqueHash::new([2u8; 32]);
+    block
+}
+
+#[test]
+fn codec_vectors_compare_json_with_binary() {
+    // Self-generated pairs: the runner only round-trips the internal binary codec.
+    let dir = tempfile::tempdir().unwrap();
+    let block = sample_block();
+    fs::write(
+        dir.path().join("block.json"),
+        serde_json::to_vec(&block).unwrap(),
+    )
+    .unwrap();
+    fs::write(dir.path().join("block.bin"), block.encode()).unwrap();
+    fs::write(
+        dir.path().join("header_0.json"),
+        serde_json::to_vec(&block.header).unwrap(),
+    )
+    .unwrap();
+    let mut tampered = block.header.encode();
+    tampered[40] ^= 1;
+    fs::write(dir.path().join("header_0.bin"), tampered).unwrap();
+    // Pairs of unmodelled types are skipped.
+    fs::write(dir.path().join("work_report.json"), b"{}").unwrap();
+    fs::write(dir.path().join("work_report.bin"), b"").unwrap();
+
+    let outcomes = run_codec_vectors(dir.path()).unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes[0].passed(), "{:?}", outcomes[0]);
+    let failure = outcomes[1].failure.as_deref().unwrap();
+    assert!(failure.contains("at byte 40"), "{failure}");
+}
+
+#[test]
+fn unknown_categories_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(run_category(dir.path(), "safrole", &ChainSpec::tiny()).is_err());
+}
+
+#[test]
+fn erasure_vectors_match_the_reference_shards() {
+    // Upstream vectors from the jamtestvectors submodule, one directory per chain spec.
+    for spec in [ChainSpec::tiny(), ChainSpec::full()] {
+        let outcomes = run_category("tests/vectors", "erasure", &spec).unwrap();
+        assert!(!outcomes.is_empty(), "no {} erasure vectors", spec.name);
+        for outcome in &outcomes {
+            assert!(outcome.passed(), "{outcome:?}");
+        }
+    }
+}
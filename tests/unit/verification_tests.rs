@@ -0,0 +1,205 @@
+use ed25519_dalek::{Signer, SigningKey};
+use jamliquor::chain_spec::ChainSpec;
+use jamliquor::error_code::{error_code, ErrorCode, Subsystem};
+use jamliquor::schema::{blake2b_256, Block, EpochMark, Extrinsic, OpaqueHash, State};
+use jamliquor::stf;
+use jamliquor::verification::{
+    collect_checks, SignatureVerifier, VerificationConfig, AVAILABLE_CONTEXT, VALID_CONTEXT,
+};
+use jamliquor::Importer;
+use serde_json::{json, Value};
+
+use crate::common::{header, public_keys, seal, signing_keys};
+
+fn assurance(key: &SigningKey, validator_index: u16) -> Value {
+    let anchor = [9u8; 32];
+    let bitfield = [1u8];
+    let mut payload = anchor.to_vec();
+    payload.extend_from_slice(&bitfield);
+    let mut message = AVAILABLE_CONTEXT.to_vec();
+    message.extend_from_slice(&blake2b_256(&payload));
+    json!({
+        "anchor": format!("0x{}", hex::encode(anchor)),
+        "bitfield": format!("0x{}", hex::encode(bitfield)),
+        "validator_index": validator_index,
+        "signature": format!("0x{}", hex::encode(key.sign(&message).to_bytes())),
+    })
+}
+
+fn extrinsic(keys: &[SigningKey]) -> Extrinsic {
+    let target = [5u8; 32];
+    let mut message = VALID_CONTEXT.to_vec();
+    message.extend_from_slice(&target);
+    Extrinsic {
+        tickets: Vec::new(),
+        preimages: Vec::new(),
+        guarantees: Vec::new(),
+        assurances: (0..keys.len())
+            .map(|i| assurance(&keys[i], i as u16))
+            .collect(),
+        disputes: json!({
+            "verdicts": [{
+                "target": format!("0x{}", hex::encode(target)),
+                "age": 0,
+                "votes": (0..3).map(|i| json!({
+                    "vote": true,
+                    "index": i,
+                    "signature": format!("0x{}", hex::encode(keys[i].sign(&message).to_bytes())),
+                })).collect::<Vec<_>>(),
+            }]
+        }),
+    }
+}
+
+#[test]
+fn parallel_and_single_threaded_verification_agree_and_cache_results() {
+    let keys = signing_keys(4);
+    let validators = public_keys(&keys);
+    let mut extrinsic = extrinsic(&keys);
+    let checks = collect_checks(&extrinsic, &validators).unwrap();
+    assert_eq!(checks.len(), 7);
+
+    for threads in [1, 4] {
+        let mut verifier = SignatureVerifier::new(VerificationConfig {
+            threads,
+            cache_capacity: 3,
+        });
+        verifier.verify(&checks).unwrap();
+        assert_eq!(verifier.cached(), 3, "cache is bounded");
+        verifier.verify(&checks).unwrap();
+    }
+
+    // Assurance 1 signed by the wrong validator.
+    extrinsic.assurances[1] = assurance(&keys[0], 1);
+    let checks = collect_checks(&extrinsic, &validators).unwrap();
+    for config in [
+        VerificationConfig::single_threaded(),
+        VerificationConfig::default(),
+    ] {
+        let mut verifier = SignatureVerifier::new(config);
+        for _ in 0..2 {
+            let err = verifier.verify(&checks).unwrap_err();
+            assert_eq!(err.code(), Some(ErrorCode::BadSignature));
+            assert_eq!(err.subsystem(), Some(Subsystem::Assurances));
+            assert_eq!(err.index(), Some(1));
+        }
+    }
+}
+
+#[test]
+fn importer_rejects_bad_signatures_when_verification_is_enabled() {
+    let keys = signing_keys(4);
+    let mut state = State::new();
+    state.active_validators = public_keys(&keys);
+    let parent = OpaqueHash::new([1; 32]);
+    let mut block = seal(
+        &state,
+        Block {
+            header: header(parent, 1, 0),
+            extrinsic: extrinsic(&keys),
+        },
+    );
+    let mut importer = Importer::from_state(ChainSpec::tiny(), parent, None, state);
+    importer.enable_verification(VerificationConfig::default());
+
+    block.extrinsic.assurances[3] = assurance(&keys[3], 7);
+    assert!(!importer.check_block(&block).is_valid());
+    let err = importer.import_decoded(block.clone()).unwrap_err();
+    assert_eq!(error_code(&err), Some(ErrorCode::BadSignature));
+
+    block.extrinsic.assurances[3] = assurance(&keys[3], 3);
+    assert!(importer.check_block(&block).is_valid());
+    importer.import_decoded(block).unwrap();
+}
+
+#[test]
+fn unsigned_or_malformed_items_are_rejected_before_verification() {
+    let keys = signing_keys(4);
+    let validators = public_keys(&keys);
+    let rejection = |extrinsic: &Extrinsic| {
+        let err = collect_checks(extrinsic, &validators).unwrap_err();
+        (err.code().unwrap(), err.subsystem().unwrap(), err.index())
+    };
+
+    let mut unsigned = extrinsic(&keys);
+    for signatures in [
+        None,
+        Some(json!([])),
+        Some(json!([{ "validator_index": 0, "signature": "0x00" }])),
+    ] {
+        let mut guarantee = json!({ "slot": 1, "report": { "core_index": 0 } });
+        if let Some(signatures) = signatures {
+            guarantee["signatures"] = signatures;
+        }
+        unsigned.guarantees = vec![guarantee];
+        assert_eq!(
+            rejection(&unsigned),
+            (
+                ErrorCode::InsufficientGuarantees,
+                Subsystem::Guarantees,
+                Some(0)
+            )
+        );
+    }
+    unsigned.guarantees = vec![json!({ "report": {}, "signatures": [{ "validator_index": "x" }] })];
+    assert_eq!(
+        rejection(&unsigned),
+        (ErrorCode::Malformed, Subsystem::Guarantees, Some(0))
+    );
+
+    let mut malformed = extrinsic(&keys);
+    malformed.assurances[2] = json!({ "validator_index": 2 });
+    assert_eq!(
+        rejection(&malformed),
+        (ErrorCode::Malformed, Subsystem::Assurances, Some(2))
+    );
+    malformed.assurances[2]["bitfield"] = json!("0x01");
+    malformed.assurances[2]["signature"] = json!("0xzz");
+    assert_eq!(
+        rejection(&malformed),
+        (ErrorCode::Malformed, Subsystem::Assurances, Some(2))
+    );
+
+    let mut short_verdict = extrinsic(&keys);
+    short_verdict.disputes["verdicts"][0]["votes"]
+        .as_array_mut()
+        .unwrap()
+        .pop();
+    assert_eq!(
+        rejection(&short_verdict),
+        (ErrorCode::BadVoteSplit, Subsystem::Disputes, Some(0))
+    );
+    short_verdict.disputes = json!({ "verdicts": "none" });
+    assert_eq!(
+        rejection(&short_verdict),
+        (ErrorCode::Malformed, Subsystem::Disputes, Some(0))
+    );
+}
+
+#[test]
+fn blocks_with_an_epoch_mark_are_checked_against_the_incoming_set() {
+    let keys = signing_keys(8);
+    let mut prior = State::new();
+    prior.active_validators = public_keys(&keys[..4]);
+    prior.pending_validators = public_keys(&keys[4..]);
+    let mut next = header(OpaqueHash::new([1; 32]), 12, 0);
+    next.epoch_mark = Some(EpochMark {
+        entropy: OpaqueHash::default(),
+        tickets_entropy: OpaqueHash::default(),
+        validators: public_keys(&keys[..4]),
+    });
+
+    let validators = stf::signing_validators(&prior, &next);
+    assert_eq!(&validators[..], &prior.pending_validators[..]);
+    let extrinsic = extrinsic(&keys[4..]);
+    let verifier = SignatureVerifier::new(VerificationConfig::single_threaded());
+    verifier
+        .check(&collect_checks(&extrinsic, &validators).unwrap())
+        .unwrap();
+    next.epoch_mark = None;
+    let outgoing = stf::signing_validators(&prior, &next);
+    let err = verifier
+        .check(&collect_checks(&extrinsic, &outgoing).unwrap())
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::BadSignature));
+}
@@ -0,0 +1,67 @@
+use jamliquor::codec::{decode_exact, Encode};
+use jamliquor::schema::OpaqueHash;
+use jamliquor::work_package::{
+    Authorizer, ExtrinsicSpec, ImportSpec, RefineContext, SegmentRootLookup, WorkError, WorkItem,
+    WorkPackage, WorkPackageBundle,
+};
+
+pub fn bundle(extrinsic: &[u8]) -> WorkPackageBundle {
+    let item = WorkItem {
+        service: 7,
+        code_hash: OpaqueHash::new([7; 32]),
+        payload: b"transfer".to_vec(),
+        refine_gas_limit: 1_000,
+        accumulate_gas_limit: 100,
+        import_segments: Vec::new(),
+        extrinsic: vec![ExtrinsicSpec::of(extrinsic)],
+        export_count: 2,
+    };
+    WorkPackageBundle {
+        package: WorkPackage {
+            authorization: b"token".to_vec(),
+            auth_code_host: 1,
+            authorizer: Authorizer {
+                code_hash: OpaqueHash::new([1; 32]),
+                params: vec![9],
+            },
+            context: RefineContext {
+                anchor: OpaqueHash::new([2; 32]),
+                lookup_anchor_slot: 40,
+                prerequisites: vec![OpaqueHash::new([3; 32])],
+                ..RefineContext::default()
+            },
+            items: vec![item],
+        },
+        extrinsics: vec![extrinsic.to_vec()],
+        imports: Vec::new(),
+        justifications: Vec::new(),
+        segment_root_lookup: Vec::new(),
+    }
+}
+
+#[test]
+fn bundles_round_trip_through_the_codec() {
+    let mut bundle = bundle(b"signed transfer");
+    bundle.package.items[0].import_segments = vec![ImportSpec {
+        tree_root: OpaqueHash::new([4; 32]),
+        index: 3,
+    }];
+    bundle.imports = vec![vec![5; 16]];
+    bundle.justifications = vec![vec![OpaqueHash::new([6; 32]); 2]];
+    bundle.segment_root_lookup = vec![SegmentRootLookup {
+        work_package_hash: OpaqueHash::new([4; 32]),
+        segment_tree_root: OpaqueHash::new([8; 32]),
+    }];
+
+    let encoded = bundle.encode();
+    assert_eq!(decode_exact::<WorkPackageBundle>(&encoded).unwrap(), bundle);
+    let package = decode_exact::<WorkPackage>(&bundle.package.encode()).unwrap();
+    assert_eq!(package.hash(), bundle.package.hash());
+    assert!(decode_exact::<WorkPackageBundle>(&encoded[..encoded.len() - 1]).is_err());
+
+    assert_ne!(
+        bundle.package.authorizer.hash(),
+        Authorizer::default().hash()
+    );
+    assert_eq!(WorkError::BadExports.name(), "bad_exports");
+}